@@ -0,0 +1,125 @@
+/*
+    This file is part of Donet.
+
+    Copyright © 2024 Max Rodriguez <me@maxrdz.com>
+
+    Donet is free software; you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License,
+    as published by the Free Software Foundation, either version 3
+    of the License, or (at your option) any later version.
+
+    Donet is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public
+    License along with Donet. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Baseline timings for the DC file hashing, field packing, and
+//! datagram reading hot paths. Run with `cargo bench -p donet-core`.
+
+use donet_core::datagram::datagram::Datagram;
+use donet_core::datagram::iterator::DatagramIterator;
+use donet_core::dcfile::DCFile;
+use donet_core::dcnumeric::DCNumericType;
+use donet_core::dconfig::DCFileConfig;
+use donet_core::dctype::{DCNumber, DCTypeEnum};
+use donet_core::read_dc;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+/// Number of classes in the synthetic DC file.
+const DC_CLASSES: usize = 500;
+/// Number of parameters in the wide field.
+const WIDE_FIELD_PARAMS: usize = 256;
+/// Number of elements in the large array.
+const ARRAY_ELEMENTS: usize = 4096;
+
+/// Runs `routine` repeatedly for about a second, after a warm up,
+/// and prints the mean time taken per iteration.
+fn bench<T>(name: &str, mut routine: impl FnMut() -> T) {
+    let warm_up: Instant = Instant::now();
+
+    while warm_up.elapsed() < Duration::from_millis(200) {
+        black_box(routine());
+    }
+
+    let mut iterations: u32 = 0;
+    let start: Instant = Instant::now();
+
+    while start.elapsed() < Duration::from_secs(1) {
+        black_box(routine());
+        iterations += 1;
+    }
+    let mean: Duration = start.elapsed() / iterations;
+
+    println!("{:<24} {:>12?}/iter ({} iterations)", name, mean, iterations);
+}
+
+/// Generates a DC file with many keywords, imports, typedefs, and
+/// classes, each class with fields of varied types and keywords.
+fn synthetic_dc_file() -> String {
+    let mut dc: String = String::default();
+
+    dc.push_str("keyword p2p;\nkeyword unreliable;\n");
+
+    for i in 0..DC_CLASSES {
+        dc.push_str(&format!("from game.objects import DistributedThing{i}/AI/UD\n"));
+        dc.push_str(&format!("typedef uint32 /100 thing{i}_position_t;\n"));
+    }
+
+    for i in 0..DC_CLASSES {
+        dc.push_str(&format!(
+            "dclass DistributedThing{i} {{\n\
+            \x20   setName(string) required broadcast ram db;\n\
+            \x20   setPosition(int32 /100, int32 /100, int32 /100) broadcast ram;\n\
+            \x20   setColor(uint8 %360, uint8, uint8) required broadcast p2p;\n\
+            \x20   setInventory(uint16[]) ownrecv db;\n\
+            \x20   requestMove(int16 x, int16 y) clsend airecv unreliable;\n\
+            }};\n"
+        ));
+    }
+    dc
+}
+
+fn main() {
+    let dc_string: String = synthetic_dc_file();
+    let dcf: DCFile = read_dc(DCFileConfig::default(), dc_string.clone()).expect("Invalid DC file.");
+
+    bench("read_dc", || {
+        read_dc(DCFileConfig::default(), black_box(dc_string.clone())).expect("Invalid DC file.")
+    });
+    bench("DCFile::get_legacy_hash", || black_box(&dcf).get_legacy_hash());
+
+    // A field with many divisor-scaled parameters, each packed in turn.
+    let mut param: DCNumericType = DCNumericType::from(DCTypeEnum::TInt32);
+    param.set_divisor(100).expect("Invalid divisor.");
+
+    bench("pack wide field", || {
+        let mut dg: Datagram = Datagram::default();
+
+        for i in 0..WIDE_FIELD_PARAMS {
+            let value: DCNumber = DCNumber::FloatingPoint(i as f64 * 1.25);
+            param
+                .pack_value(&mut dg, black_box(value))
+                .expect("Failed to pack.");
+        }
+        dg
+    });
+
+    let mut array: Datagram = Datagram::default();
+    array.add_size((ARRAY_ELEMENTS * 4) as u16).unwrap();
+
+    for i in 0..ARRAY_ELEMENTS {
+        array.add_u32(i as u32).unwrap();
+    }
+
+    bench("unpack large array", || {
+        let mut dgi: DatagramIterator = black_box(array.clone()).into();
+        let size: usize = usize::from(dgi.read_size().unwrap());
+
+        (0..size / 4).map(|_| dgi.read_u32().unwrap()).sum::<u32>()
+    });
+}
@@ -20,6 +20,46 @@
 //! Utils for swapping little-endian bytes to the compiling
 //! processor's native endianness (byte order).
 
+/// Byte order used for the multi-byte values of a datagram.
+///
+/// The Donet wire format is always little-endian. Big-endian is
+/// only meant for exporting data to tools that expect it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Endianness {
+    #[default]
+    Little,
+    Big,
+}
+
+impl Endianness {
+    /// Reorders the bytes of a 2 byte value in little endian byte
+    /// order into this byte order, or back if already reordered.
+    pub fn reorder_16(self, v: u16) -> u16 {
+        match self {
+            Self::Little => v,
+            Self::Big => v.swap_bytes(),
+        }
+    }
+
+    /// Reorders the bytes of a 4 byte value in little endian byte
+    /// order into this byte order, or back if already reordered.
+    pub fn reorder_32(self, v: u32) -> u32 {
+        match self {
+            Self::Little => v,
+            Self::Big => v.swap_bytes(),
+        }
+    }
+
+    /// Reorders the bytes of an 8 byte value in little endian byte
+    /// order into this byte order, or back if already reordered.
+    pub fn reorder_64(self, v: u64) -> u64 {
+        match self {
+            Self::Little => v,
+            Self::Big => v.swap_bytes(),
+        }
+    }
+}
+
 /// Swaps 2 bytes in little endian byte order to big endian.
 /// Returns the input if the processor is little endian.
 #[cfg(target_endian = "big")]
@@ -20,7 +20,10 @@
 //! Provides structure to write network packets (datagrams).
 
 use crate::datagram::byte_order as endianness;
+use crate::datagram::byte_order::Endianness;
+use crate::datagram::iterator::{DatagramIterator, IteratorError, MessageHeader};
 use crate::globals::*;
+use crate::protocol::{EjectReason, Protocol};
 use anyhow::Result;
 use thiserror::Error;
 
@@ -31,6 +34,8 @@ pub enum DatagramError {
     DatagramOverflow(&'static str),
     #[error("impossible cast; {0}")]
     ImpossibleCast(&'static str),
+    #[error("invalid length prefix; {0}")]
+    InvalidLength(&'static str),
 }
 
 impl From<DatagramError> for std::io::Error {
@@ -39,6 +44,53 @@ impl From<DatagramError> for std::io::Error {
     }
 }
 
+/// Width of the length prefix that frames each datagram on a
+/// stream connection, such as a TCP connection between MDs.
+///
+/// Astron uses a 16-bit length prefix, so it is the default for
+/// compatibility. A 32-bit length prefix allows datagrams larger
+/// than 64 kilobytes. Both ends of a connection **must** use the
+/// same width, or the stream will be deframed incorrectly.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FramingWidth {
+    #[default]
+    U16,
+    U32,
+}
+
+/// Creates a [`FramingWidth`] from a number of bits (16 or 32).
+impl TryFrom<u8> for FramingWidth {
+    type Error = DatagramError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            16 => Ok(Self::U16),
+            32 => Ok(Self::U32),
+            _ => Err(DatagramError::ImpossibleCast(
+                "Datagram framing width must be 16 or 32 bits.",
+            )),
+        }
+    }
+}
+
+impl FramingWidth {
+    /// Returns the size of the length prefix in bytes.
+    pub const fn size(&self) -> usize {
+        match self {
+            Self::U16 => std::mem::size_of::<u16>(),
+            Self::U32 => std::mem::size_of::<u32>(),
+        }
+    }
+
+    /// Returns the maximum datagram size that can be framed.
+    pub const fn max_payload(&self) -> usize {
+        match self {
+            Self::U16 => u16::MAX as usize,
+            Self::U32 => u32::MAX as usize,
+        }
+    }
+}
+
 /// Representation of a new network message (datagram) to be sent.
 #[derive(Debug, Clone)]
 pub struct Datagram {
@@ -46,6 +98,7 @@ pub struct Datagram {
     index: usize,
     /// See [`Datagram::override_cap`].
     cap: usize,
+    endianness: Endianness,
 }
 
 impl Default for Datagram {
@@ -54,6 +107,7 @@ impl Default for Datagram {
             buffer: vec![],
             index: 0,
             cap: usize::from(DgSizeTag::MAX),
+            endianness: Endianness::default(),
         }
     }
 }
@@ -73,6 +127,46 @@ impl std::ops::Add for Datagram {
 }
 
 impl Datagram {
+    /// Creates an empty [`Datagram`] that adds multi-byte values
+    /// in the given byte order.
+    ///
+    /// Datagrams sent over the network must always be little-endian,
+    /// which is the byte order of [`Datagram::default`].
+    pub fn with_endianness(endianness: Endianness) -> Datagram {
+        Self {
+            endianness,
+            ..Default::default()
+        }
+    }
+
+    #[inline(always)]
+    pub fn get_endianness(&self) -> Endianness {
+        self.endianness
+    }
+
+    /// Creates a [`Datagram`] from a buffer holding a single datagram
+    /// framed with a 16-bit length prefix, as sent over the wire.
+    ///
+    /// The length prefix is validated against the size of the rest
+    /// of the buffer, and is not included in the new datagram.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Datagram, DatagramError> {
+        let Some((prefix, payload)) = bytes.split_first_chunk::<2>() else {
+            return Err(DatagramError::InvalidLength(
+                "Buffer is too short to hold a length prefix.",
+            ));
+        };
+
+        if usize::from(DgSizeTag::from_le_bytes(*prefix)) != payload.len() {
+            return Err(DatagramError::InvalidLength(
+                "Length prefix does not match the size of the buffer.",
+            ));
+        }
+        let mut dg: Datagram = Datagram::default();
+
+        dg.add_data(payload.to_vec())?;
+        Ok(dg)
+    }
+
     /// Checks if we can add `length` number of bytes to the datagram.
     fn check_add_length(&mut self, length: usize) -> Result<(), DatagramError> {
         let new_index: usize = self.index + length;
@@ -119,7 +213,7 @@ impl Datagram {
     pub fn add_u16(&mut self, mut v: u16) -> Result<(), DatagramError> {
         self.check_add_length(2)?;
 
-        v = endianness::swap_le_16(v);
+        v = self.endianness.reorder_16(endianness::swap_le_16(v));
 
         self.buffer.push((v & 0x00ff) as u8);
         self.buffer.push(((v & 0xff00) >> 8) as u8);
@@ -132,7 +226,7 @@ impl Datagram {
     pub fn add_u32(&mut self, mut v: u32) -> Result<(), DatagramError> {
         self.check_add_length(4)?;
 
-        v = endianness::swap_le_32(v);
+        v = self.endianness.reorder_32(endianness::swap_le_32(v));
 
         self.buffer.push((v & 0x000000ff) as u8);
         self.buffer.push(((v & 0x0000ff00) >> 8) as u8);
@@ -147,7 +241,7 @@ impl Datagram {
     pub fn add_u64(&mut self, mut v: u64) -> Result<(), DatagramError> {
         self.check_add_length(8)?;
 
-        v = endianness::swap_le_64(v);
+        v = self.endianness.reorder_64(endianness::swap_le_64(v));
 
         self.buffer.push((v & 0x00000000000000ff) as u8);
         self.buffer.push(((v & 0x000000000000ff00) >> 8) as u8);
@@ -186,13 +280,13 @@ impl Datagram {
     /// 32-bit IEEE 754 floating point. same bitwise operations.
     #[inline(always)]
     pub fn add_f32(&mut self, v: f32) -> Result<(), DatagramError> {
-        self.add_u32(v as u32)
+        self.add_u32(v.to_bits())
     }
 
     /// 64-bit IEEE 754 floating point. same bitwise operations.
     #[inline(always)]
     pub fn add_f64(&mut self, v: f64) -> Result<(), DatagramError> {
-        self.add_u64(v as u64)
+        self.add_u64(v.to_bits())
     }
 
     /// Adds a Datagram / Field length tag to the end of the datagram.
@@ -219,6 +313,20 @@ impl Datagram {
         self.add_u32(v)
     }
 
+    /// Adds a length prefix for framing a datagram of `size` bytes
+    /// on a stream, using the given [`FramingWidth`].
+    pub fn add_frame_size(&mut self, width: FramingWidth, size: usize) -> Result<(), DatagramError> {
+        if size > width.max_payload() {
+            return Err(DatagramError::ImpossibleCast(
+                "Datagram size does not fit in the framing length prefix.",
+            ));
+        }
+        match width {
+            FramingWidth::U16 => self.add_u16(size as u16),
+            FramingWidth::U32 => self.add_u32(size as u32),
+        }
+    }
+
     /// Added for convenience, rather than adding the parent and the zone separately.
     #[inline(always)]
     pub fn add_location(&mut self, parent: DoId, zone: Zone) -> Result<(), DatagramError> {
@@ -289,6 +397,12 @@ impl Datagram {
         Ok(())
     }
 
+    /// Embeds another datagram at the end of this datagram, as a blob.
+    /// Read back with [`super::iterator::DatagramIterator::read_datagram`].
+    pub fn add_datagram(&mut self, dg: &Datagram) -> Result<(), DatagramError> {
+        self.add_blob(dg.get_data())
+    }
+
     /// Reserves an amount of bytes in the datagram buffer.
     pub fn add_buffer(&mut self, size: usize) -> Result<usize, DatagramError> {
         self.check_add_length(size)?;
@@ -343,6 +457,14 @@ impl Datagram {
         self.add_u16(msg_type)
     }
 
+    /// Reads the server header at the start of this datagram.
+    ///
+    /// See [`DatagramIterator::read_server_header`], which also leaves
+    /// an iterator at the payload that follows the header.
+    pub fn parse_server_header(&self) -> Result<MessageHeader, IteratorError> {
+        DatagramIterator::from(self.clone()).read_server_header()
+    }
+
     /// Appends a control header, which is very similar to a server header,
     /// but it always has only one recipient, which is the control channel,
     /// and does not require a sender (or 'from') channel to be provided.
@@ -357,6 +479,14 @@ impl Datagram {
         self.add_u16(msg_type)
     }
 
+    /// Appends a `CLIENT_EJECT` message, with the code
+    /// and human readable string of the given reason.
+    pub fn add_client_eject(&mut self, reason: EjectReason) -> Result<(), DatagramError> {
+        self.add_u16(Protocol::ClientEject.into())?;
+        self.add_u16(reason.into())?;
+        self.add_string(reason.reason())
+    }
+
     /// Returns the size of this [`Datagram`].
     pub fn size(&self) -> usize {
         self.buffer.len()
@@ -383,7 +513,9 @@ impl Datagram {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::Protocol;
+    use crate::datagram::iterator::DatagramIterator;
+    use std::collections::HashSet;
+    use strum::IntoEnumIterator;
 
     #[test]
     fn add_boolean() {
@@ -502,6 +634,31 @@ mod tests {
         ]);
     }
 
+    #[test]
+    #[rustfmt::skip]
+    fn frame_size_widths() {
+        let mut dg: Datagram = Datagram::default();
+
+        assert!(dg.add_frame_size(FramingWidth::U16, 300).is_ok());
+        assert!(dg.add_frame_size(FramingWidth::U32, 70000).is_ok());
+
+        assert_eq!(dg.get_data(), vec![
+            44, 1, // 300 (u16)
+            112, 17, 1, 0, // 70000 (u32)
+        ]);
+
+        // 70000 bytes cannot be described with a 16-bit prefix.
+        assert_eq!(
+            dg.add_frame_size(FramingWidth::U16, 70000),
+            Err(DatagramError::ImpossibleCast(
+                "Datagram size does not fit in the framing length prefix."
+            )),
+        );
+        assert_eq!(FramingWidth::try_from(16), Ok(FramingWidth::U16));
+        assert_eq!(FramingWidth::try_from(32), Ok(FramingWidth::U32));
+        assert!(FramingWidth::try_from(24).is_err());
+    }
+
     #[test]
     fn overflow_test() {
         let mut dg: Datagram = Datagram::default();
@@ -527,4 +684,73 @@ mod tests {
             "Datagram overflow occurred, but failed to respond with DatagramOverflow err."
         );
     }
+
+    #[test]
+    fn add_client_eject() {
+        let expected: [(EjectReason, u16); 6] = [
+            (EjectReason::BadVersion, 124),
+            (EjectReason::BadDCHash, 125),
+            (EjectReason::HeartbeatTimeout, 345),
+            (EjectReason::SecurityViolation, 113),
+            (EjectReason::ServerFull, 150),
+            (EjectReason::AdminKick, 151),
+        ];
+
+        for (reason, code) in expected {
+            assert_eq!(u16::from(reason), code);
+        }
+
+        let mut codes: HashSet<u16> = HashSet::default();
+
+        for reason in EjectReason::iter() {
+            let mut dg: Datagram = Datagram::default();
+            dg.add_client_eject(reason).unwrap();
+
+            let mut dgi: DatagramIterator = dg.into();
+
+            assert_eq!(dgi.read_msg_type().unwrap(), Protocol::ClientEject);
+            assert_eq!(dgi.read_u16().unwrap(), u16::from(reason));
+            assert_eq!(dgi.read_string().unwrap(), reason.reason());
+            assert_eq!(dgi.get_remaining(), 0);
+
+            assert!(codes.insert(reason.into()), "Duplicate code for {:?}.", reason);
+        }
+    }
+
+    #[test]
+    fn from_bytes_round_trip() {
+        let mut dg: Datagram = Datagram::default();
+
+        dg.add_internal_header(vec![4000], 1000, Protocol::SSObjectSetField.into())
+            .unwrap();
+        dg.add_string("payload").unwrap();
+
+        let mut framed: Datagram = Datagram::default();
+
+        framed.add_frame_size(FramingWidth::U16, dg.size()).unwrap();
+        framed.add_data(dg.get_data()).unwrap();
+
+        let parsed: Datagram = Datagram::from_bytes(framed.get_buffer()).unwrap();
+        assert_eq!(parsed.get_buffer(), dg.get_buffer());
+
+        // an empty datagram is just its length prefix
+        assert_eq!(Datagram::from_bytes(&[0, 0]).unwrap().size(), 0);
+    }
+
+    #[test]
+    fn from_bytes_length_mismatch() {
+        let mismatched: [&[u8]; 4] = [
+            &[],           // no room for the length prefix
+            &[3],          // truncated length prefix
+            &[3, 0, 1, 2], // prefix past the end of the buffer
+            &[1, 0, 1, 2], // trailing bytes after the datagram
+        ];
+
+        for bytes in mismatched {
+            assert!(matches!(
+                Datagram::from_bytes(bytes),
+                Err(DatagramError::InvalidLength(_))
+            ));
+        }
+    }
 }
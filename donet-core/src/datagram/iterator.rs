@@ -19,8 +19,9 @@
 
 //! Provides structure for iterating over network packets (datagrams).
 
-use super::datagram::{Datagram, DatagramError};
+use super::datagram::{Datagram, DatagramError, FramingWidth};
 use crate::datagram::byte_order as endianness;
+use crate::datagram::byte_order::Endianness;
 use crate::globals::*;
 use crate::protocol::*;
 use std::mem;
@@ -41,6 +42,8 @@ pub enum IteratorError {
     /// sane range for the given field type.
     #[error("field constraint violation")]
     FieldConstraintViolation,
+    /// This error kind is returned when the payload of a
+    /// string read from the datagram is not valid UTF-8.
     #[error("could not convert bytes to UTF-8")]
     Utf8Error(FromUtf8Error),
     #[error("invalid read; {0}")]
@@ -61,17 +64,28 @@ impl From<IteratorError> for std::io::Error {
     }
 }
 
+/// The header of a message routed between services of the cluster,
+/// as added by [`Datagram::add_internal_header`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageHeader {
+    pub recipients: Vec<Channel>,
+    pub sender: Channel,
+    pub msg_type: MsgType,
+}
+
 /// Utility for iterating value by value of a datagram message.
 #[derive(Debug)]
 pub struct DatagramIterator {
     datagram: Datagram,
     index: usize,
+    endianness: Endianness,
 }
 
 /// Create a new [`DatagramIterator`] from a [`Datagram`].
 impl From<Datagram> for DatagramIterator {
     fn from(value: Datagram) -> Self {
         Self {
+            endianness: value.get_endianness(),
             datagram: value,
             index: 0,
         }
@@ -79,6 +93,19 @@ impl From<Datagram> for DatagramIterator {
 }
 
 impl DatagramIterator {
+    #[inline(always)]
+    pub fn get_endianness(&self) -> Endianness {
+        self.endianness
+    }
+
+    /// Sets the byte order that multi-byte values are read in.
+    ///
+    /// Defaults to the byte order of the iterated [`Datagram`].
+    #[inline(always)]
+    pub fn set_endianness(&mut self, endianness: Endianness) {
+        self.endianness = endianness
+    }
+
     pub fn check_read_length(&mut self, bytes: usize) -> Result<(), IteratorError> {
         let new_index: usize = self.index + bytes;
 
@@ -113,6 +140,18 @@ impl DatagramIterator {
         self.datagram.size() - self.index
     }
 
+    /// Returns a slice of all the unread bytes left in the datagram,
+    /// and moves the index to the end of the buffer.
+    ///
+    /// Useful for forwarding the payload of a message untouched
+    /// after reading its header.
+    pub fn read_remaining(&mut self) -> &[u8] {
+        let start: usize = self.index;
+        self.index = self.datagram.size();
+
+        &self.datagram.get_buffer()[start..]
+    }
+
     /// Reads the next number of bytes in the datagram.
     pub fn read_data(&mut self, bytes: usize) -> Result<Vec<u8>, IteratorError> {
         self.check_read_length(bytes)?;
@@ -170,7 +209,7 @@ impl DatagramIterator {
         let value: u16 = (data[self.index] as u16) | ((data[self.index + 1] as u16) << 8);
         self.index += 2;
 
-        Ok(endianness::swap_le_16(value))
+        Ok(self.endianness.reorder_16(endianness::swap_le_16(value)))
     }
 
     pub fn read_u32(&mut self) -> Result<u32, IteratorError> {
@@ -183,7 +222,7 @@ impl DatagramIterator {
             | ((data[self.index + 3] as u32) << 24);
 
         self.index += 4;
-        Ok(endianness::swap_le_32(value))
+        Ok(self.endianness.reorder_32(endianness::swap_le_32(value)))
     }
 
     pub fn read_u64(&mut self) -> Result<u64, IteratorError> {
@@ -200,7 +239,7 @@ impl DatagramIterator {
             | ((data[self.index + 7] as u64) << 56);
 
         self.index += 8;
-        Ok(endianness::swap_le_64(value))
+        Ok(self.endianness.reorder_64(endianness::swap_le_64(value)))
     }
 
     // Signed integer aliases, same read operation.
@@ -227,13 +266,13 @@ impl DatagramIterator {
     /// 32-bit IEEE 754 floating point in native endianness.
     #[inline]
     pub fn read_f32(&mut self) -> Result<f32, IteratorError> {
-        self.read_u32().map(|v| v as f32)
+        self.read_u32().map(f32::from_bits)
     }
 
     /// 64-bit IEEE 754 floating point in native endianness.
     #[inline]
     pub fn read_f64(&mut self) -> Result<f64, IteratorError> {
-        self.read_u64().map(|v| v as f64)
+        self.read_u64().map(f64::from_bits)
     }
 
     #[inline]
@@ -265,6 +304,15 @@ impl DatagramIterator {
         self.read_u16()
     }
 
+    /// Reads a length prefix that frames a datagram on a
+    /// stream, using the given [`FramingWidth`].
+    pub fn read_frame_size(&mut self, width: FramingWidth) -> Result<usize, IteratorError> {
+        match width {
+            FramingWidth::U16 => self.read_u16().map(usize::from),
+            FramingWidth::U32 => self.read_u32().map(|v| v as usize),
+        }
+    }
+
     #[inline]
     pub fn read_channel(&mut self) -> Result<Channel, IteratorError> {
         self.read_u64()
@@ -286,7 +334,7 @@ impl DatagramIterator {
 
         let dg_payload: Vec<u8> = self.read_data(usize::from(dg_size))?;
 
-        let mut new_dg: Datagram = Datagram::default();
+        let mut new_dg: Datagram = Datagram::with_endianness(self.endianness);
 
         if let Err(e) = new_dg.add_data(dg_payload) {
             return Err(IteratorError::DatagramError(e));
@@ -317,6 +365,36 @@ impl DatagramIterator {
         ))
     }
 
+    /// Reads the header of a server message, which is its recipients,
+    /// sender, and message type, leaving the iterator at the payload.
+    ///
+    /// Messages with no recipients are malformed, and control messages
+    /// have no sender, so both are returned as an invalid read.
+    pub fn read_server_header(&mut self) -> Result<MessageHeader, IteratorError> {
+        let recipient_count: u8 = self.read_recipient_count()?;
+
+        if recipient_count == 0 {
+            return Err(IteratorError::InvalidRead("Server header has no recipients."));
+        }
+        let mut recipients: Vec<Channel> = vec![];
+
+        for _ in 0..recipient_count {
+            recipients.push(self.read_channel()?);
+        }
+
+        if recipients == [CONTROL_CHANNEL] {
+            return Err(IteratorError::InvalidRead(
+                "Control messages do not have a server header.",
+            ));
+        }
+
+        Ok(MessageHeader {
+            recipients,
+            sender: self.read_channel()?,
+            msg_type: self.read_u16()?,
+        })
+    }
+
     /// Get the recipient count in a datagram message.
     /// Does not advance the index.
     pub fn peek_recipient_count(&mut self) -> Result<u8, IteratorError> {
@@ -451,6 +529,149 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn dgi_read_embedded_datagram() -> Result<(), IteratorError> {
+        let mut embedded: Datagram = Datagram::default();
+        let mut dg: Datagram = Datagram::default();
+
+        let results: Vec<Result<(), DatagramError>> = vec![
+            embedded.add_internal_header(vec![4000], 1000, Protocol::SSObjectDeleteRAM.into()),
+            embedded.add_doid(123_456),
+            dg.add_channel(5000),
+            dg.add_datagram(&embedded),
+            dg.add_u8(u8::MAX),
+        ];
+
+        for dg_res in &results {
+            assert!(dg_res.is_ok());
+        }
+        // the embedded datagram is a blob of its own bytes
+        assert_eq!(dg.size(), 8 + 2 + embedded.size() + 1);
+
+        let mut dgi: DatagramIterator = dg.into();
+
+        assert_eq!(dgi.read_channel()?, 5000);
+
+        let read_back: Datagram = dgi.read_datagram()?;
+
+        assert_eq!(read_back.get_data(), embedded.get_data());
+        assert_eq!(dgi.read_u8()?, u8::MAX);
+        assert_eq!(dgi.get_remaining(), 0);
+
+        let mut nested: DatagramIterator = read_back.into();
+
+        assert_eq!(nested.read_recipient_count()?, 1);
+        assert_eq!(nested.read_channel()?, 4000);
+        assert_eq!(nested.read_channel()?, 1000);
+        assert_eq!(nested.read_msg_type()?, Protocol::SSObjectDeleteRAM);
+        assert_eq!(nested.read_doid()?, 123_456);
+
+        // the length tag of the embedded datagram is past the end
+        let mut truncated: Datagram = Datagram::default();
+        assert!(truncated.add_data(vec![10, 0, 1, 2]).is_ok());
+
+        let mut dgi: DatagramIterator = truncated.into();
+        assert!(dgi.read_datagram().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn dgi_read_remaining_payload() -> Result<(), IteratorError> {
+        let mut dg: Datagram = Datagram::default();
+        let payload: Vec<u8> = vec![0, 125, u8::MAX, 3, 4];
+
+        dg.add_internal_header(vec![4000, 5000], 1234, Protocol::SSObjectSetField.into())
+            .expect("Failed to add server header.");
+        dg.add_data(payload.clone()).expect("Failed to add payload.");
+
+        let mut dgi: DatagramIterator = dg.into();
+
+        let recipients: u8 = dgi.read_recipient_count()?;
+        assert_eq!(recipients, 2);
+
+        // we don't care about the recipients, so skip them
+        dgi.skip(usize::from(recipients) * mem::size_of::<Channel>())?;
+
+        assert_eq!(dgi.read_channel()?, 1234);
+        assert_eq!(dgi.read_msg_type()?, Protocol::SSObjectSetField);
+        assert_eq!(dgi.read_remaining(), payload.as_slice());
+        assert_eq!(dgi.get_remaining(), 0);
+
+        // reading the remaining bytes of an exhausted iterator
+        assert!(dgi.read_remaining().is_empty());
+        assert_eq!(dgi.skip(1), Err(IteratorError::EndOfFile));
+        Ok(())
+    }
+
+    #[test]
+    fn dgi_read_errors() {
+        let mut dg: Datagram = Datagram::default();
+
+        dg.add_u16(2).unwrap();
+        dg.add_data(vec![0xC3, 0x28]).unwrap(); // invalid UTF-8 sequence
+        dg.add_u16(10).unwrap(); // string length past the end
+        dg.add_data(vec![b'a', b'b']).unwrap();
+
+        let mut dgi: DatagramIterator = dg.into();
+
+        let err: IteratorError = dgi.read_string().unwrap_err();
+        assert!(matches!(err, IteratorError::Utf8Error(_)));
+
+        // the iterator error is a standard error, not an IO error
+        let err: &dyn std::error::Error = &err;
+        assert_eq!(err.to_string(), "could not convert bytes to UTF-8");
+
+        assert_eq!(dgi.read_string(), Err(IteratorError::EndOfFile));
+
+        let mut dgi: DatagramIterator = Datagram::default().into();
+        assert_eq!(dgi.read_u32(), Err(IteratorError::EndOfFile));
+    }
+
+    #[test]
+    fn dgi_read_frame_size() -> Result<(), IteratorError> {
+        let mut dg: Datagram = Datagram::default();
+
+        dg.add_frame_size(FramingWidth::U16, 300).unwrap();
+        dg.add_frame_size(FramingWidth::U32, 70000).unwrap();
+
+        let mut dgi: DatagramIterator = dg.into();
+
+        assert_eq!(dgi.read_frame_size(FramingWidth::U16)?, 300);
+        assert_eq!(dgi.read_frame_size(FramingWidth::U32)?, 70000);
+        assert_eq!(
+            dgi.read_frame_size(FramingWidth::U16),
+            Err(IteratorError::EndOfFile)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn dgi_read_large_ids() -> Result<(), IteratorError> {
+        let mut dg: Datagram = Datagram::default();
+
+        let recipient: Channel = CHANNEL_MAX - 1;
+        let sender: Channel = CHANNEL_MAX;
+        let do_id: DoId = DOID_MAX - 1;
+
+        dg.add_internal_header(vec![recipient], sender, Protocol::SSObjectSetField.into())
+            .unwrap();
+        dg.add_doid(do_id).unwrap();
+        dg.add_location(DOID_MAX, ZONE_MAX).unwrap();
+        dg.add_channel(location_as_channel(DOID_MAX, ZONE_MAX)).unwrap();
+
+        let mut dgi: DatagramIterator = dg.into();
+
+        assert_eq!(dgi.read_recipient_count()?, 1);
+        assert_eq!(dgi.read_channel()?, recipient);
+        assert_eq!(dgi.read_channel()?, sender);
+        assert_eq!(dgi.read_msg_type()?, Protocol::SSObjectSetField);
+        assert_eq!(dgi.read_doid()?, do_id);
+        assert_eq!(dgi.read_doid()?, DOID_MAX);
+        assert_eq!(dgi.read_zone()?, ZONE_MAX);
+        assert_eq!(dgi.read_channel()?, CHANNEL_MAX);
+        Ok(())
+    }
+
     #[test]
     fn dgi_read_message_type() -> Result<(), IteratorError> {
         let mut dg: Datagram = Datagram::default();
@@ -475,4 +696,93 @@ mod tests {
         }
         Ok(())
     }
+
+    #[test]
+    fn dgi_round_trip_endianness() -> Result<(), IteratorError> {
+        let expected: [(Endianness, Vec<u8>); 2] = [
+            (Endianness::Little, vec![0x34, 0x12, 0x78, 0x56, 0x34, 0x12]),
+            (Endianness::Big, vec![0x12, 0x34, 0x12, 0x34, 0x56, 0x78]),
+        ];
+
+        for (endianness, bytes) in expected {
+            let mut dg: Datagram = Datagram::with_endianness(endianness);
+
+            dg.add_u16(0x1234).unwrap();
+            dg.add_u32(0x12345678).unwrap();
+            dg.add_i64(-2).unwrap();
+            assert_eq!(dg.get_data()[..6], bytes);
+
+            let mut dgi: DatagramIterator = dg.into();
+
+            assert_eq!(dgi.get_endianness(), endianness);
+            assert_eq!(dgi.read_u16()?, 0x1234);
+            assert_eq!(dgi.read_u32()?, 0x12345678);
+            assert_eq!(dgi.read_i64()?, -2);
+        }
+
+        // reading in the wrong byte order swaps the bytes
+        let mut dg: Datagram = Datagram::default();
+        dg.add_u16(0x1234).unwrap();
+
+        let mut dgi: DatagramIterator = dg.into();
+        dgi.set_endianness(Endianness::Big);
+
+        assert_eq!(dgi.read_u16()?, 0x3412);
+        Ok(())
+    }
+
+    #[test]
+    fn dgi_read_server_header() -> Result<(), IteratorError> {
+        let recipients: Vec<Channel> = vec![4000, 4002, 1_000_000_000_000];
+
+        let mut dg: Datagram = Datagram::default();
+        dg.add_internal_header(recipients.clone(), 1000, Protocol::SSObjectDeleteRAM.into())
+            .unwrap();
+        dg.add_doid(123_456).unwrap();
+
+        let header: MessageHeader = dg.parse_server_header()?;
+
+        assert_eq!(
+            header,
+            MessageHeader {
+                recipients,
+                sender: 1000,
+                msg_type: Protocol::SSObjectDeleteRAM.into(),
+            }
+        );
+
+        // the payload follows the header
+        let mut dgi: DatagramIterator = dg.clone().into();
+
+        assert_eq!(dgi.read_server_header()?, header);
+        assert_eq!(dgi.read_doid()?, 123_456);
+
+        // truncated headers, and headers with no recipients
+        let mut truncated: Datagram = Datagram::default();
+        truncated.add_data(dg.get_data()[..20].to_vec()).unwrap();
+
+        assert_eq!(truncated.parse_server_header(), Err(IteratorError::EndOfFile));
+        assert_eq!(
+            Datagram::default().parse_server_header(),
+            Err(IteratorError::EndOfFile)
+        );
+
+        let mut no_recipients: Datagram = Datagram::default();
+        no_recipients.add_internal_header(vec![], 1000, 0).unwrap();
+
+        assert!(matches!(
+            no_recipients.parse_server_header(),
+            Err(IteratorError::InvalidRead(_))
+        ));
+
+        // control messages are not server messages
+        let mut control: Datagram = Datagram::default();
+        control.add_control_header(Protocol::MDAddChannel.into()).unwrap();
+
+        assert!(matches!(
+            control.parse_server_header(),
+            Err(IteratorError::InvalidRead(_))
+        ));
+        Ok(())
+    }
 }
@@ -24,6 +24,7 @@
 //! - Constructing datagrams with appropriate headers and payloads.
 //! - Iterating through and extracting information from received datagrams.
 //! - Converting endianness of datagram bytes to native byte order.
+//! - Building and reading datagrams in big-endian byte order, for tooling.
 //! - Datagram-level error handling.
 
 pub mod byte_order;
@@ -0,0 +1,521 @@
+/*
+    This file is part of Donet.
+
+    Copyright © 2024 Max Rodriguez <me@maxrdz.com>
+
+    Donet is free software; you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License,
+    as published by the Free Software Foundation, either version 3
+    of the License, or (at your option) any later version.
+
+    Donet is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public
+    License along with Donet. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Runtime access to the fields of a Distributed Class by name,
+//! for packing and unpacking field values without generated code.
+
+use crate::datagram::datagram::Datagram;
+use crate::datagram::iterator::DatagramIterator;
+use crate::dcfield::{ClassField, DCField};
+use crate::dctype::{DCNumber, DCTypeDefinition, DCTypeEnum};
+use crate::globals;
+
+/// A single value of a field parameter, as packed on the wire.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    Number(DCNumber),
+    String(String),
+    Blob(Vec<u8>),
+    /// The value of each member of a struct, in order.
+    Struct(Vec<FieldValue>),
+}
+
+/// Renders a value the way it would be written in a DC file, such as
+/// `"Bob"` for a string or `{7, "glazed"}` for a struct. Blobs are
+/// rendered as their bytes in hexadecimal, such as `<01 02 ff>`.
+impl std::fmt::Display for FieldValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Number(DCNumber::Integer(x)) => x.fmt(f),
+            Self::Number(DCNumber::UnsignedInteger(x)) => x.fmt(f),
+            Self::Number(DCNumber::FloatingPoint(x)) => x.fmt(f),
+            Self::String(string) => write!(f, "{:?}", string),
+            Self::Blob(bytes) => {
+                write!(f, "<")?;
+
+                for (i, byte) in bytes.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{:02x}", byte)?;
+                }
+                write!(f, ">")
+            }
+            Self::Struct(members) => {
+                write!(f, "{{")?;
+
+                for (i, member) in members.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    member.fmt(f)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+/// Handle to a field of a Distributed Class, which packs and unpacks
+/// the values of the field's parameters, in declaration order.
+///
+/// Returned by [`crate::dclass::DClass::field`].
+#[derive(Debug, Clone, Copy)]
+pub struct FieldAccessor<'dc> {
+    field: &'dc ClassField<'dc>,
+}
+
+impl<'dc> From<&'dc ClassField<'dc>> for FieldAccessor<'dc> {
+    fn from(value: &'dc ClassField<'dc>) -> Self {
+        Self { field: value }
+    }
+}
+
+impl<'dc> FieldAccessor<'dc> {
+    #[inline(always)]
+    pub fn get_field(&self) -> &'dc ClassField<'dc> {
+        self.field
+    }
+
+    #[inline(always)]
+    pub fn get_field_id(&self) -> globals::FieldId {
+        self.field.get_base_field().get_field_id()
+    }
+
+    /// Packs the given values as the payload of this field, which
+    /// is one value for each of its parameters, in order.
+    ///
+    /// The field ID is not included, so the payload can follow
+    /// any message header that carries it.
+    pub fn pack(&self, values: &[FieldValue]) -> Result<Datagram, String> {
+        let types: Vec<&DCTypeDefinition> = self.parameter_types()?;
+
+        if values.len() != types.len() {
+            return Err(format!(
+                "Field `{}` has {} parameters, but {} values were given.",
+                self.field.get_base_field().get_field_name(),
+                types.len(),
+                values.len()
+            ));
+        }
+
+        let mut dg: Datagram = Datagram::default();
+
+        for (dtype, value) in types.into_iter().zip(values) {
+            pack_value(&mut dg, dtype, value)?;
+        }
+        Ok(dg)
+    }
+
+    /// Unpacks a payload of this field into the values of its
+    /// parameters. The payload must not have any bytes left over.
+    pub fn unpack(&self, data: &[u8]) -> Result<Vec<FieldValue>, String> {
+        let types: Vec<&DCTypeDefinition> = self.parameter_types()?;
+
+        let mut dg: Datagram = Datagram::default();
+        dg.add_data(data.to_vec()).map_err(|err| err.to_string())?;
+
+        let mut dgi: DatagramIterator = dg.into();
+        let mut values: Vec<FieldValue> = vec![];
+
+        for dtype in types {
+            values.push(unpack_value(&mut dgi, dtype).map_err(|err| err.to_string())?);
+        }
+
+        if dgi.get_remaining() != 0 {
+            return Err(format!(
+                "{} bytes left over after unpacking field `{}`.",
+                dgi.get_remaining(),
+                self.field.get_base_field().get_field_name()
+            ));
+        }
+        Ok(values)
+    }
+
+    /// Returns the type of each parameter of this field, in order.
+    ///
+    /// The parameters of a molecular field are the parameters of
+    /// each of its atomic fields, in the order they were declared.
+    pub(crate) fn parameter_types(&self) -> Result<Vec<&'dc DCTypeDefinition>, String> {
+        let plain_type = |field: &'dc DCField<'dc>| {
+            field
+                .get_field_type()
+                .ok_or_else(|| format!("Field `{}` has no type.", field.get_field_name()))
+        };
+
+        match self.field {
+            ClassField::Field(field) => Ok(vec![plain_type(field)?]),
+            ClassField::Atomic(atomic) => Ok((0..atomic.get_num_elements())
+                .filter_map(|i| atomic.get_element(i))
+                .map(|param| param.get_type())
+                .collect()),
+            ClassField::Molecular(molecular) => Ok((0..molecular.get_num_atomics())
+                .filter_map(|i| molecular.get_atomic_field(i))
+                .flat_map(|atomic| (0..atomic.get_num_elements()).filter_map(|i| atomic.get_element(i)))
+                .map(|param| param.get_type())
+                .collect()),
+        }
+    }
+}
+
+/// Returns an error, unless the given length matches the fixed
+/// length of the type, if the type is not of variable length.
+fn check_fixed_length(dtype: &DCTypeDefinition, length: usize) -> Result<(), String> {
+    if !dtype.is_variable_length() && length != usize::from(dtype.get_size()) {
+        return Err(format!(
+            "{} value must be {} bytes long, but is {} bytes long.",
+            dtype.get_dc_type(),
+            dtype.get_size(),
+            length
+        ));
+    }
+    Ok(())
+}
+
+pub(crate) fn pack_value(
+    dg: &mut Datagram,
+    dtype: &DCTypeDefinition,
+    value: &FieldValue,
+) -> Result<(), String> {
+    let mismatch = || format!("{:?} is not a valid {} value.", value, dtype.get_dc_type());
+    let out_of_range = |_| format!("{:?} is out of range for {}.", value, dtype.get_dc_type());
+
+    let result = match (dtype.get_dc_type(), value) {
+        (DCTypeEnum::TInt8, FieldValue::Number(DCNumber::Integer(v))) => {
+            dg.add_i8(i8::try_from(*v).map_err(out_of_range)?)
+        }
+        (DCTypeEnum::TInt16, FieldValue::Number(DCNumber::Integer(v))) => {
+            dg.add_i16(i16::try_from(*v).map_err(out_of_range)?)
+        }
+        (DCTypeEnum::TInt32, FieldValue::Number(DCNumber::Integer(v))) => {
+            dg.add_i32(i32::try_from(*v).map_err(out_of_range)?)
+        }
+        (DCTypeEnum::TInt64, FieldValue::Number(DCNumber::Integer(v))) => dg.add_i64(*v),
+        (DCTypeEnum::TUInt8 | DCTypeEnum::TChar, FieldValue::Number(DCNumber::UnsignedInteger(v))) => {
+            dg.add_u8(u8::try_from(*v).map_err(out_of_range)?)
+        }
+        (DCTypeEnum::TUInt16, FieldValue::Number(DCNumber::UnsignedInteger(v))) => {
+            dg.add_u16(u16::try_from(*v).map_err(out_of_range)?)
+        }
+        (DCTypeEnum::TUInt32, FieldValue::Number(DCNumber::UnsignedInteger(v))) => {
+            dg.add_u32(u32::try_from(*v).map_err(out_of_range)?)
+        }
+        (DCTypeEnum::TUInt64, FieldValue::Number(DCNumber::UnsignedInteger(v))) => dg.add_u64(*v),
+        (DCTypeEnum::TFloat32, FieldValue::Number(DCNumber::FloatingPoint(v))) => {
+            if v.abs() > f64::from(f32::MAX) {
+                return Err(format!("{:?} is out of range for float32.", value));
+            }
+            dg.add_f32(*v as f32)
+        }
+        (DCTypeEnum::TFloat64, FieldValue::Number(DCNumber::FloatingPoint(v))) => dg.add_f64(*v),
+        (DCTypeEnum::TString | DCTypeEnum::TVarString, FieldValue::String(v)) => {
+            if dtype.is_variable_length() {
+                dg.add_string(v)
+            } else {
+                check_fixed_length(dtype, v.len())?;
+                dg.add_data(v.as_bytes().to_vec())
+            }
+        }
+        (DCTypeEnum::TBlob | DCTypeEnum::TVarBlob, FieldValue::Blob(v)) => {
+            if dtype.is_variable_length() {
+                dg.add_blob(v.clone())
+            } else {
+                check_fixed_length(dtype, v.len())?;
+                dg.add_data(v.clone())
+            }
+        }
+        (DCTypeEnum::TBlob32 | DCTypeEnum::TVarBlob32, FieldValue::Blob(v)) => {
+            let length: u32 = u32::try_from(v.len()).map_err(out_of_range)?;
+
+            dg.add_u32(length).and_then(|_| dg.add_data(v.clone()))
+        }
+        (DCTypeEnum::TStruct, FieldValue::Struct(members)) => {
+            let types: &[DCTypeDefinition] = dtype.get_struct_members();
+
+            if members.len() != types.len() {
+                return Err(format!(
+                    "Struct `{}` has {} members, but {} values were given.",
+                    dtype.get_alias().unwrap_or_default(),
+                    types.len(),
+                    members.len()
+                ));
+            }
+            for (member_type, member) in types.iter().zip(members) {
+                pack_value(dg, member_type, member)?;
+            }
+            Ok(())
+        }
+        (DCTypeEnum::TArray | DCTypeEnum::TVarArray | DCTypeEnum::TMethod, _) => {
+            return Err(format!(
+                "{} parameters are not supported by field accessors.",
+                dtype.get_dc_type()
+            ));
+        }
+        _ => return Err(mismatch()),
+    };
+    result.map_err(|err| err.to_string())
+}
+
+/// Returns the number of bytes that the value would pack to as the
+/// given type, including the length prefix of variable length types,
+/// without packing it. Fails for the same type mismatches and fixed
+/// lengths as [`pack_value`], but does not check integer ranges.
+pub(crate) fn packed_size(dtype: &DCTypeDefinition, value: &FieldValue) -> Result<usize, String> {
+    let mismatch = || format!("{:?} is not a valid {} value.", value, dtype.get_dc_type());
+
+    // Variable length values are prefixed with their length.
+    let tagged = |length: usize, tag: usize| -> Result<usize, String> {
+        let max: usize = match tag {
+            2 => usize::from(u16::MAX),
+            _ => usize::try_from(u32::MAX).unwrap_or(usize::MAX),
+        };
+        if length > max {
+            return Err(format!("{:?} is too long for {}.", value, dtype.get_dc_type()));
+        }
+        Ok(tag + length)
+    };
+
+    match (dtype.get_dc_type(), value) {
+        (DCTypeEnum::TInt8, FieldValue::Number(DCNumber::Integer(_))) => Ok(1),
+        (DCTypeEnum::TInt16, FieldValue::Number(DCNumber::Integer(_))) => Ok(2),
+        (DCTypeEnum::TInt32, FieldValue::Number(DCNumber::Integer(_))) => Ok(4),
+        (DCTypeEnum::TInt64, FieldValue::Number(DCNumber::Integer(_))) => Ok(8),
+        (DCTypeEnum::TUInt8 | DCTypeEnum::TChar, FieldValue::Number(DCNumber::UnsignedInteger(_))) => Ok(1),
+        (DCTypeEnum::TUInt16, FieldValue::Number(DCNumber::UnsignedInteger(_))) => Ok(2),
+        (DCTypeEnum::TUInt32, FieldValue::Number(DCNumber::UnsignedInteger(_))) => Ok(4),
+        (DCTypeEnum::TUInt64, FieldValue::Number(DCNumber::UnsignedInteger(_))) => Ok(8),
+        (DCTypeEnum::TFloat32, FieldValue::Number(DCNumber::FloatingPoint(_))) => Ok(4),
+        (DCTypeEnum::TFloat64, FieldValue::Number(DCNumber::FloatingPoint(_))) => Ok(8),
+        (DCTypeEnum::TString | DCTypeEnum::TVarString, FieldValue::String(v)) => {
+            match dtype.is_variable_length() {
+                true => tagged(v.len(), 2),
+                false => check_fixed_length(dtype, v.len()).map(|_| v.len()),
+            }
+        }
+        (DCTypeEnum::TBlob | DCTypeEnum::TVarBlob, FieldValue::Blob(v)) => match dtype.is_variable_length() {
+            true => tagged(v.len(), 2),
+            false => check_fixed_length(dtype, v.len()).map(|_| v.len()),
+        },
+        (DCTypeEnum::TBlob32 | DCTypeEnum::TVarBlob32, FieldValue::Blob(v)) => tagged(v.len(), 4),
+        (DCTypeEnum::TStruct, FieldValue::Struct(members)) => {
+            let types: &[DCTypeDefinition] = dtype.get_struct_members();
+
+            if members.len() != types.len() {
+                return Err(format!(
+                    "Struct `{}` has {} members, but {} values were given.",
+                    dtype.get_alias().unwrap_or_default(),
+                    types.len(),
+                    members.len()
+                ));
+            }
+            types
+                .iter()
+                .zip(members)
+                .map(|(member_type, member)| packed_size(member_type, member))
+                .sum()
+        }
+        (DCTypeEnum::TArray | DCTypeEnum::TVarArray | DCTypeEnum::TMethod, _) => Err(format!(
+            "{} parameters are not supported by field accessors.",
+            dtype.get_dc_type()
+        )),
+        _ => Err(mismatch()),
+    }
+}
+
+/// Returns true if the value can be packed as the given type, which
+/// is when [`pack_value`] would not fail.
+///
+/// Integers are implicitly widened to, or narrowed to, any integer
+/// type of the same signedness that can hold their value, so `300`
+/// is accepted by an `int16` or `int64`, but not by an `int8`. Signed
+/// and unsigned integers and floating point numbers are never mixed.
+pub(crate) fn accepts_value(dtype: &DCTypeDefinition, value: &FieldValue) -> bool {
+    match (dtype.get_dc_type(), value) {
+        (DCTypeEnum::TInt8, FieldValue::Number(DCNumber::Integer(v))) => i8::try_from(*v).is_ok(),
+        (DCTypeEnum::TInt16, FieldValue::Number(DCNumber::Integer(v))) => i16::try_from(*v).is_ok(),
+        (DCTypeEnum::TInt32, FieldValue::Number(DCNumber::Integer(v))) => i32::try_from(*v).is_ok(),
+        (DCTypeEnum::TUInt8 | DCTypeEnum::TChar, FieldValue::Number(DCNumber::UnsignedInteger(v))) => {
+            u8::try_from(*v).is_ok()
+        }
+        (DCTypeEnum::TUInt16, FieldValue::Number(DCNumber::UnsignedInteger(v))) => u16::try_from(*v).is_ok(),
+        (DCTypeEnum::TUInt32, FieldValue::Number(DCNumber::UnsignedInteger(v))) => u32::try_from(*v).is_ok(),
+        (DCTypeEnum::TFloat32, FieldValue::Number(DCNumber::FloatingPoint(v))) => {
+            v.abs() <= f64::from(f32::MAX)
+        }
+        (DCTypeEnum::TStruct, FieldValue::Struct(members)) => {
+            let types: &[DCTypeDefinition] = dtype.get_struct_members();
+
+            members.len() == types.len()
+                && types
+                    .iter()
+                    .zip(members)
+                    .all(|(member_type, member)| accepts_value(member_type, member))
+        }
+        // the remaining types have no range, only a kind and a length
+        _ => packed_size(dtype, value).is_ok(),
+    }
+}
+
+pub(crate) fn unpack_value(
+    dgi: &mut DatagramIterator,
+    dtype: &DCTypeDefinition,
+) -> Result<FieldValue, Box<dyn std::error::Error>> {
+    let integer = |v: i64| FieldValue::Number(DCNumber::Integer(v));
+    let unsigned = |v: u64| FieldValue::Number(DCNumber::UnsignedInteger(v));
+    let float = |v: f64| FieldValue::Number(DCNumber::FloatingPoint(v));
+
+    let fixed_length: usize = usize::from(dtype.get_size());
+
+    Ok(match dtype.get_dc_type() {
+        DCTypeEnum::TInt8 => integer(dgi.read_i8()?.into()),
+        DCTypeEnum::TInt16 => integer(dgi.read_i16()?.into()),
+        DCTypeEnum::TInt32 => integer(dgi.read_i32()?.into()),
+        DCTypeEnum::TInt64 => integer(dgi.read_i64()?),
+        DCTypeEnum::TUInt8 | DCTypeEnum::TChar => unsigned(dgi.read_u8()?.into()),
+        DCTypeEnum::TUInt16 => unsigned(dgi.read_u16()?.into()),
+        DCTypeEnum::TUInt32 => unsigned(dgi.read_u32()?.into()),
+        DCTypeEnum::TUInt64 => unsigned(dgi.read_u64()?),
+        DCTypeEnum::TFloat32 => float(dgi.read_f32()?.into()),
+        DCTypeEnum::TFloat64 => float(dgi.read_f64()?),
+        DCTypeEnum::TString | DCTypeEnum::TVarString => match dtype.is_variable_length() {
+            true => FieldValue::String(dgi.read_string()?),
+            false => FieldValue::String(String::from_utf8(dgi.read_data(fixed_length)?)?),
+        },
+        DCTypeEnum::TBlob | DCTypeEnum::TVarBlob => match dtype.is_variable_length() {
+            true => {
+                let length: usize = dgi.read_size()?.into();
+                FieldValue::Blob(dgi.read_data(length)?)
+            }
+            false => FieldValue::Blob(dgi.read_data(fixed_length)?),
+        },
+        DCTypeEnum::TBlob32 | DCTypeEnum::TVarBlob32 => {
+            let length: usize = dgi.read_u32()?.try_into()?;
+            FieldValue::Blob(dgi.read_data(length)?)
+        }
+        DCTypeEnum::TStruct => {
+            let mut members: Vec<FieldValue> = vec![];
+
+            for member_type in dtype.get_struct_members() {
+                members.push(unpack_value(dgi, member_type)?);
+            }
+            FieldValue::Struct(members)
+        }
+        DCTypeEnum::TArray | DCTypeEnum::TVarArray | DCTypeEnum::TMethod => {
+            return Err(format!(
+                "{} parameters are not supported by field accessors.",
+                dtype.get_dc_type()
+            )
+            .into());
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dcfield::FieldParent;
+    use crate::dcfile::DCFile;
+    use crate::dclass::DClass;
+    use crate::dconfig::DCFileConfig;
+    use crate::read_dc;
+
+    #[test]
+    fn pack_type_mismatches() {
+        let dcf: DCFile = read_dc(DCFileConfig::default(), String::default()).unwrap();
+        let dclass: DClass = DClass::new(&dcf, "DistributedDonut");
+
+        let mut radius: DCField = DCField::new("setRadius", FieldParent::DClass(&dclass));
+        radius.set_field_type(DCTypeEnum::TUInt8.into());
+
+        let mut label: DCField = DCField::new("setLabel", FieldParent::DClass(&dclass));
+        let mut fixed_string: DCTypeDefinition = DCTypeEnum::TString.into();
+        fixed_string.size = 4;
+        label.set_field_type(fixed_string);
+
+        let (radius, label) = (ClassField::Field(radius), ClassField::Field(label));
+        let (radius, label) = (FieldAccessor::from(&radius), FieldAccessor::from(&label));
+
+        let number = |v: u64| FieldValue::Number(DCNumber::UnsignedInteger(v));
+
+        assert_eq!(radius.pack(&[number(200)]).unwrap().get_data(), vec![200]);
+
+        assert!(radius.pack(&[number(256)]).is_err());
+        assert!(radius.pack(&[FieldValue::String("200".into())]).is_err());
+        assert!(radius.pack(&[number(1), number(2)]).is_err());
+        assert!(radius.unpack(&[]).is_err());
+        assert!(radius.unpack(&[1, 2]).is_err());
+
+        // fixed length strings are not prefixed with their length
+        let packed: Datagram = label.pack(&[FieldValue::String("ring".into())]).unwrap();
+        assert_eq!(packed.get_data(), b"ring".to_vec());
+
+        assert!(label.pack(&[FieldValue::String("donut".into())]).is_err());
+        assert_eq!(
+            label.unpack(b"ring").unwrap(),
+            vec![FieldValue::String("ring".into())]
+        );
+    }
+
+    #[test]
+    fn pack_empty_values() {
+        let dcf: DCFile = read_dc(DCFileConfig::default(), String::default()).unwrap();
+        let dclass: DClass = DClass::new(&dcf, "DistributedDonut");
+
+        let field = |name: &str, dc_type: DCTypeEnum| {
+            let mut field: DCField = DCField::new(name, FieldParent::DClass(&dclass));
+            field.set_field_type(dc_type.into());
+            ClassField::Field(field)
+        };
+        let name: ClassField = field("setName", DCTypeEnum::TVarString);
+        let icing: ClassField = field("setIcing", DCTypeEnum::TVarBlob);
+        let recipe: ClassField = field("setRecipe", DCTypeEnum::TVarBlob32);
+
+        let (name, icing, recipe) = (
+            FieldAccessor::from(&name),
+            FieldAccessor::from(&icing),
+            FieldAccessor::from(&recipe),
+        );
+
+        // empty values are packed as a zero length prefix alone
+        let empty_string: FieldValue = FieldValue::String(String::default());
+        let empty_blob: FieldValue = FieldValue::Blob(vec![]);
+
+        assert_eq!(
+            name.pack(std::slice::from_ref(&empty_string)).unwrap().get_data(),
+            vec![0, 0]
+        );
+        assert_eq!(
+            icing.pack(std::slice::from_ref(&empty_blob)).unwrap().get_data(),
+            vec![0, 0]
+        );
+        assert_eq!(
+            recipe.pack(std::slice::from_ref(&empty_blob)).unwrap().get_data(),
+            vec![0, 0, 0, 0]
+        );
+
+        assert_eq!(name.unpack(&[0, 0]).unwrap(), vec![empty_string]);
+        assert_eq!(icing.unpack(&[0, 0]).unwrap(), vec![empty_blob.clone()]);
+        assert_eq!(recipe.unpack(&[0, 0, 0, 0]).unwrap(), vec![empty_blob]);
+
+        // a missing or truncated prefix is an error, not a panic
+        assert!(name.unpack(&[]).is_err());
+        assert!(icing.unpack(&[0]).is_err());
+        assert!(recipe.unpack(&[0, 0]).is_err());
+    }
+}
@@ -20,13 +20,18 @@
 //! Data model of the DC Array element, which is a parameter
 //! type that stores a list of values of the same data type.
 
-use crate::dcnumeric::DCNumericRange;
-use crate::dctype::{DCNumber, DCTypeDefinition, DCTypeEnum};
+use crate::datagram::datagram::Datagram;
+use crate::datagram::iterator::{DatagramIterator, IteratorError};
+use crate::dcnumeric::{DCNumericRange, DCNumericType};
+use crate::dctype::{same_optional_type, DCNumber, DCTypeDefinition, DCTypeEnum};
 use crate::hashgen::*;
 
 pub struct DCArrayType {
     base_type: Option<DCTypeDefinition>,
     element_type: Option<DCTypeDefinition>,
+    /// Set if the element type is numeric. The element's divisor,
+    /// modulus, and range are applied to every element of the array.
+    numeric_element: Option<DCNumericType>,
     array_size: u16,
     array_range: Option<DCNumericRange>,
 }
@@ -49,21 +54,15 @@ impl LegacyDCHash for DCArrayType {
 impl DCArrayType {
     pub fn new(element_type: Option<DCTypeDefinition>, size: Option<DCNumericRange>) -> Self {
         let mut new_array_type: Self = Self {
-            base_type: None,
+            base_type: Some(DCTypeDefinition::from(DCTypeEnum::TVarArray)),
             element_type,
+            numeric_element: None,
             array_size: 0_u16,
             array_range: size,
         };
 
-        if new_array_type.array_range.is_none() {
-            new_array_type.array_range = None;
-            let range: &mut DCNumericRange = new_array_type.array_range.as_mut().unwrap();
-
-            range.min = DCNumber::UnsignedInteger(0_u64);
-            range.max = DCNumber::UnsignedInteger(u64::MAX);
-        } else {
-            let range: &mut DCNumericRange = new_array_type.array_range.as_mut().unwrap();
-
+        // Without a size range, the array is of variable length with no limits.
+        if let Some(range) = &new_array_type.array_range {
             if range.min == range.max {
                 new_array_type.array_size = u64::from(range.min) as u16;
             }
@@ -71,10 +70,11 @@ impl DCArrayType {
 
         if new_array_type.element_type.is_some() {
             let e_type: DCTypeDefinition = new_array_type.element_type.clone().unwrap();
+            let array_size: u16 = new_array_type.array_size;
 
             let new_base_type: &mut DCTypeDefinition = new_array_type.base_type.as_mut().unwrap();
 
-            if !e_type.is_variable_length() && new_base_type.size > 0 {
+            if !e_type.is_variable_length() && array_size > 0 {
                 new_base_type.data_type = DCTypeEnum::TArray;
                 new_base_type.size = new_array_type.array_size * e_type.get_size();
             } else {
@@ -103,6 +103,30 @@ impl DCArrayType {
         new_array_type
     }
 
+    /// Creates a new array type of numeric elements, such as `int16[] / 100`.
+    pub fn from_numeric(element: DCNumericType, size: Option<DCNumericRange>) -> Self {
+        let mut new_array_type: Self = Self::new(Some(element.get_base_type().clone()), size);
+
+        new_array_type.numeric_element = Some(element);
+        new_array_type
+    }
+
+    /// Returns true if both array types have the same element type,
+    /// including the transforms of numeric elements, and the same
+    /// size bounds. The aliases of the types are not compared.
+    pub fn same_type_as(&self, other: &DCArrayType) -> bool {
+        let same_numeric_element = match (&self.numeric_element, &other.numeric_element) {
+            (Some(a), Some(b)) => a.same_type_as(b),
+            (None, None) => true,
+            _ => false,
+        };
+        same_optional_type(self.base_type.as_ref(), other.base_type.as_ref())
+            && same_optional_type(self.element_type.as_ref(), other.element_type.as_ref())
+            && same_numeric_element
+            && self.array_size == other.array_size
+            && self.array_range == other.array_range
+    }
+
     #[inline(always)]
     pub fn get_array_size(&self) -> u16 {
         self.base_type.clone().unwrap().size
@@ -122,4 +146,155 @@ impl DCArrayType {
     pub fn has_range(&self) -> bool {
         self.array_range.is_some()
     }
+
+    /// Packs the given values into the datagram as this numeric array.
+    ///
+    /// The element type's transforms are applied to each element.
+    /// Variable length arrays are prefixed with their size in bytes.
+    pub fn pack_numeric(&self, dg: &mut Datagram, values: &[DCNumber]) -> Result<(), String> {
+        let element: &DCNumericType = self
+            .numeric_element
+            .as_ref()
+            .ok_or("Array element type is not numeric.")?;
+
+        let fixed_size: bool = self.array_size > 0;
+
+        if fixed_size && values.len() != usize::from(self.array_size) {
+            return Err("Number of elements does not match the array size.".into());
+        }
+
+        let mut elements: Datagram = Datagram::default();
+
+        for value in values {
+            element.pack_value(&mut elements, *value)?;
+        }
+
+        if fixed_size {
+            dg.add_data(elements.get_data())
+        } else {
+            dg.add_blob(elements.get_data())
+        }
+        .map_err(|err| err.to_string())
+    }
+
+    /// Unpacks the elements of this numeric array from the datagram.
+    pub fn unpack_numeric(&self, dgi: &mut DatagramIterator) -> Result<Vec<DCNumber>, IteratorError> {
+        let element: &DCNumericType = self
+            .numeric_element
+            .as_ref()
+            .ok_or(IteratorError::InvalidRead("Array element type is not numeric."))?;
+
+        let byte_size: usize = if self.array_size > 0 {
+            usize::from(self.array_size) * usize::from(element.get_base_type().get_size())
+        } else {
+            usize::from(dgi.read_size()?)
+        };
+
+        let mut elements: Datagram = Datagram::default();
+        elements.add_data(dgi.read_data(byte_size)?).unwrap();
+
+        let mut elements: DatagramIterator = elements.into();
+        let mut values: Vec<DCNumber> = vec![];
+
+        while elements.get_remaining() > 0 {
+            values.push(element.unpack_value(&mut elements)?);
+        }
+        Ok(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn int16_scaled_array() -> DCArrayType {
+        let mut element: DCNumericType = DCNumericType::from(DCTypeEnum::TInt16);
+        element.set_divisor(100).unwrap();
+
+        DCArrayType::from_numeric(element, None)
+    }
+
+    #[test]
+    fn pack_scaled_int_array() {
+        let array: DCArrayType = int16_scaled_array();
+        let values: Vec<DCNumber> = vec![
+            DCNumber::FloatingPoint(1.25),
+            DCNumber::FloatingPoint(-3.5),
+            DCNumber::FloatingPoint(0.07),
+            DCNumber::FloatingPoint(327.67),
+            DCNumber::Integer(2),
+        ];
+        let mut dg: Datagram = Datagram::default();
+
+        array.pack_numeric(&mut dg, &values).unwrap();
+
+        // every element is scaled by the divisor before packing
+        let mut expected: Datagram = Datagram::default();
+        expected.add_size(10).unwrap();
+
+        for raw in [125_i16, -350, 7, 32767, 200] {
+            expected.add_i16(raw).unwrap();
+        }
+        assert_eq!(dg.get_data(), expected.get_data());
+
+        let mut dgi: DatagramIterator = dg.into();
+        let unpacked: Vec<DCNumber> = array.unpack_numeric(&mut dgi).unwrap();
+
+        assert_eq!(
+            unpacked,
+            vec![
+                DCNumber::FloatingPoint(1.25),
+                DCNumber::FloatingPoint(-3.5),
+                DCNumber::FloatingPoint(0.07),
+                DCNumber::FloatingPoint(327.67),
+                DCNumber::FloatingPoint(2.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn pack_scaled_int_array_overflow() {
+        let array: DCArrayType = int16_scaled_array();
+        let mut dg: Datagram = Datagram::default();
+
+        // 400.00 is packed as 40000, which does not fit in an int16
+        let values: Vec<DCNumber> = vec![DCNumber::FloatingPoint(1.0), DCNumber::FloatingPoint(400.0)];
+
+        assert!(array.pack_numeric(&mut dg, &values).is_err());
+    }
+
+    #[test]
+    fn pack_empty_var_array() {
+        let array: DCArrayType = int16_scaled_array();
+        let mut dg: Datagram = Datagram::default();
+
+        array.pack_numeric(&mut dg, &[]).unwrap();
+
+        // only the zero byte size prefix is packed
+        assert_eq!(dg.get_data(), vec![0, 0]);
+
+        let mut dgi: DatagramIterator = dg.into();
+
+        assert_eq!(array.unpack_numeric(&mut dgi).unwrap(), vec![]);
+        assert_eq!(dgi.get_remaining(), 0);
+    }
+
+    #[test]
+    fn same_array_type_ignores_alias() {
+        let mut coins: DCTypeDefinition = DCTypeEnum::TUInt32.into();
+        coins.size = 4;
+        coins.set_alias("coins".into());
+
+        let mut gems: DCTypeDefinition = coins.clone();
+        gems.set_alias("gems".into());
+
+        let bounded = |element: &DCTypeDefinition, max: u64| {
+            DCArrayType::new(Some(element.clone()), Some(DCNumericRange::from(0_u64..max)))
+        };
+
+        assert!(bounded(&coins, 10).same_type_as(&bounded(&gems, 10)));
+        assert!(!bounded(&coins, 10).same_type_as(&bounded(&gems, 20)));
+        assert!(int16_scaled_array().same_type_as(&int16_scaled_array()));
+        assert!(!int16_scaled_array().same_type_as(&DCArrayType::new(Some(DCTypeEnum::TInt16.into()), None)));
+    }
 }
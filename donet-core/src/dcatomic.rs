@@ -20,15 +20,19 @@
 //! Data model for a DC Atomic Field, which represents a remote
 //! procedure call method of a Distributed Class.
 
-use crate::dcfield::DCField;
+use crate::datagram::datagram::Datagram;
+use crate::datagram::iterator::DatagramIterator;
+use crate::dcaccessor::{self, FieldValue};
+use crate::dcfield::{DCField, FieldParent};
 use crate::dckeyword::DCKeywordList;
 use crate::dcparameter::DCParameter;
+use crate::globals;
 use crate::hashgen::*;
 
 /// Represents an atomic field of a Distributed Class.
 /// This defines the interface to a DClass object, and is
 /// always implemented as a remote procedure call (RPC).
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct DCAtomicField<'dc> {
     base_field: DCField<'dc>,
     elements: Vec<&'dc DCParameter<'dc>>,
@@ -53,6 +57,19 @@ impl LegacyDCHash for DCAtomicField<'_> {
 }
 
 impl<'dc> DCAtomicField<'dc> {
+    /// Creates a new atomic field with no elements.
+    pub fn new(name: &str, parent: FieldParent<'dc>) -> Self {
+        Self {
+            base_field: DCField::new(name, parent),
+            elements: vec![],
+        }
+    }
+
+    #[inline(always)]
+    pub fn get_base_field(&self) -> &DCField<'dc> {
+        &self.base_field
+    }
+
     #[inline(always)]
     pub fn get_num_elements(&self) -> usize {
         self.elements.len()
@@ -63,7 +80,233 @@ impl<'dc> DCAtomicField<'dc> {
         self.elements.get(index).copied()
     }
 
+    /// Appends a parameter to the signature of this atomic field.
+    pub fn add_element(&mut self, param: &'dc DCParameter<'dc>) {
+        self.elements.push(param);
+    }
+
     pub fn set_keyword_list(&mut self, kw_list: DCKeywordList<'dc>) {
         self.base_field.set_field_keyword_list(kw_list)
     }
+
+    #[inline(always)]
+    pub(crate) fn set_field_id(&mut self, id: globals::FieldId) {
+        self.base_field.set_field_id(id)
+    }
+
+    /// Returns the number of bytes that the given arguments would pack
+    /// to, one for each element, including the length prefixes of
+    /// variable length elements, without packing them.
+    ///
+    /// Useful for sizing a buffer, or rejecting an oversized update,
+    /// before packing the arguments.
+    pub fn packed_size(&self, values: &[FieldValue]) -> Result<usize, String> {
+        if values.len() != self.elements.len() {
+            return Err(format!(
+                "Field `{}` has {} parameters, but {} values were given.",
+                self.base_field.get_field_name(),
+                self.elements.len(),
+                values.len()
+            ));
+        }
+
+        self.elements
+            .iter()
+            .zip(values)
+            .map(|(param, value)| dcaccessor::packed_size(param.get_type(), value))
+            .sum()
+    }
+
+    /// Renders a packed payload of this field as a call with its
+    /// arguments, such as `setName("Bob")`, for logging field updates.
+    ///
+    /// Never panics on bad data. If the payload is truncated, has bytes
+    /// left over, or holds an invalid value, the arguments are rendered
+    /// as `<malformed>` instead, such as `setName(<malformed>)`.
+    pub fn format_packed(&self, data: &[u8]) -> String {
+        let name: String = self.base_field.get_field_name();
+
+        match self.unpack_elements(data) {
+            Some(values) => {
+                let args: Vec<String> = values.iter().map(FieldValue::to_string).collect();
+                format!("{}({})", name, args.join(", "))
+            }
+            None => format!("{}(<malformed>)", name),
+        }
+    }
+
+    /// Unpacks a value for each element from the payload, or returns
+    /// `None` unless the payload is exactly one value for each element.
+    fn unpack_elements(&self, data: &[u8]) -> Option<Vec<FieldValue>> {
+        let mut dg: Datagram = Datagram::default();
+        dg.add_data(data.to_vec()).ok()?;
+
+        let mut dgi: DatagramIterator = dg.into();
+        let mut values: Vec<FieldValue> = vec![];
+
+        for param in &self.elements {
+            values.push(dcaccessor::unpack_value(&mut dgi, param.get_type()).ok()?);
+        }
+
+        match dgi.get_remaining() {
+            0 => Some(values),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dcaccessor::FieldAccessor;
+    use crate::dcfield::ClassField;
+    use crate::dcfile::DCFile;
+    use crate::dclass::DClass;
+    use crate::dconfig::DCFileConfig;
+    use crate::dcparameter::DCParameterBuilder;
+    use crate::dctype::{DCNumber, DCTypeDefinition, DCTypeEnum};
+    use crate::read_dc;
+
+    #[test]
+    fn packed_size_matches_packed_length() {
+        let dcf: DCFile = read_dc(DCFileConfig::default(), String::default()).unwrap();
+        let dclass: DClass = DClass::new(&dcf, "DistributedAvatar");
+        let parent = || FieldParent::DClass(&dclass);
+
+        let owner: DCAtomicField = DCAtomicField::new("owner", parent());
+        let param = |dtype: DCTypeDefinition| DCParameterBuilder::new(&owner, dtype).build().unwrap();
+
+        let mut code: DCTypeDefinition = DCTypeEnum::TString.into();
+        code.size = 4;
+
+        let fixed_params = [
+            param(DCTypeEnum::TInt16.into()),
+            param(DCTypeEnum::TUInt32.into()),
+            param(DCTypeEnum::TFloat64.into()),
+            param(code),
+        ];
+        let variable_params = [
+            param(DCTypeEnum::TVarString.into()),
+            param(DCTypeEnum::TVarBlob.into()),
+            param(DCTypeEnum::TVarBlob32.into()),
+            param(DCTypeDefinition::new_struct(
+                "Tag",
+                vec![DCTypeEnum::TUInt8.into(), DCTypeEnum::TVarString.into()],
+            )),
+        ];
+
+        // setState(int16, uint32, float64, string(4))
+        let mut set_state: DCAtomicField = DCAtomicField::new("setState", parent());
+        fixed_params.iter().for_each(|p| set_state.add_element(p));
+
+        // setProfile(string, blob, blob32, Tag)
+        let mut set_profile: DCAtomicField = DCAtomicField::new("setProfile", parent());
+        variable_params.iter().for_each(|p| set_profile.add_element(p));
+
+        let state: Vec<FieldValue> = vec![
+            FieldValue::Number(DCNumber::Integer(-20)),
+            FieldValue::Number(DCNumber::UnsignedInteger(70_000)),
+            FieldValue::Number(DCNumber::FloatingPoint(0.5)),
+            FieldValue::String("DNUT".into()),
+        ];
+        let profile: Vec<FieldValue> = vec![
+            FieldValue::String("Donut".into()),
+            FieldValue::Blob(vec![1, 2, 3]),
+            FieldValue::Blob(vec![]),
+            FieldValue::Struct(vec![
+                FieldValue::Number(DCNumber::UnsignedInteger(7)),
+                FieldValue::String("glazed".into()),
+            ]),
+        ];
+
+        assert_eq!(set_state.packed_size(&state), Ok(2 + 4 + 8 + 4));
+        assert_eq!(
+            set_profile.packed_size(&profile),
+            Ok((2 + 5) + (2 + 3) + 4 + (1 + 2 + 6))
+        );
+
+        for (field, values) in [(set_state, state), (set_profile, profile)] {
+            let size: usize = field.packed_size(&values).unwrap();
+            let field: ClassField = ClassField::Atomic(field);
+
+            let packed = FieldAccessor::from(&field).pack(&values).unwrap();
+            assert_eq!(size, packed.size());
+        }
+
+        // the same errors as packing, without packing
+        let mut set_code: DCAtomicField = DCAtomicField::new("setCode", parent());
+        set_code.add_element(&fixed_params[3]);
+
+        assert!(set_code
+            .packed_size(&[FieldValue::String("DONUT".into())])
+            .is_err());
+        assert!(set_code.packed_size(&[FieldValue::Blob(vec![0; 4])]).is_err());
+        assert!(set_code.packed_size(&[]).is_err());
+    }
+
+    #[test]
+    fn format_packed_payloads() {
+        let dcf: DCFile = read_dc(DCFileConfig::default(), String::default()).unwrap();
+        let dclass: DClass = DClass::new(&dcf, "DistributedToon");
+        let parent = || FieldParent::DClass(&dclass);
+
+        let owner: DCAtomicField = DCAtomicField::new("owner", parent());
+        let param = |dtype: DCTypeDefinition| DCParameterBuilder::new(&owner, dtype).build().unwrap();
+
+        let params = [
+            param(DCTypeEnum::TVarString.into()),
+            param(DCTypeEnum::TInt16.into()),
+            param(DCTypeEnum::TFloat64.into()),
+            param(DCTypeEnum::TVarBlob.into()),
+            param(DCTypeDefinition::new_struct(
+                "Tag",
+                vec![DCTypeEnum::TUInt8.into(), DCTypeEnum::TVarString.into()],
+            )),
+        ];
+
+        // setName(string)
+        let mut set_name: DCAtomicField = DCAtomicField::new("setName", parent());
+        set_name.add_element(&params[0]);
+
+        // setState(int16, float64, blob, Tag)
+        let mut set_state: DCAtomicField = DCAtomicField::new("setState", parent());
+        params[1..].iter().for_each(|p| set_state.add_element(p));
+
+        // ping()
+        let ping: DCAtomicField = DCAtomicField::new("ping", parent());
+
+        let name: Vec<u8> = vec![3, 0, b'B', b'o', b'b'];
+        let state: Vec<u8> = vec![
+            0xec, 0xff, // -20
+            0, 0, 0, 0, 0, 0, 0xe0, 0x3f, // 0.5
+            2, 0, 0x01, 0xff, // <01 ff>
+            7, 2, 0, b'h', b'i', // {7, "hi"}
+        ];
+
+        assert_eq!(set_name.format_packed(&name), "setName(\"Bob\")");
+        assert_eq!(
+            set_state.format_packed(&state),
+            "setState(-20, 0.5, <01 ff>, {7, \"hi\"})"
+        );
+        assert_eq!(ping.format_packed(&[]), "ping()");
+
+        // truncated, too long, and an oversized length tag
+        assert_eq!(set_name.format_packed(&name[..4]), "setName(<malformed>)");
+        assert_eq!(
+            set_name.format_packed(&[name.clone(), vec![0]].concat()),
+            "setName(<malformed>)"
+        );
+        assert_eq!(
+            set_name.format_packed(&[0xff, 0xff, b'B']),
+            "setName(<malformed>)"
+        );
+        assert_eq!(set_state.format_packed(&state[..12]), "setState(<malformed>)");
+        assert_eq!(ping.format_packed(&[0]), "ping(<malformed>)");
+
+        // strings that are not UTF-8
+        assert_eq!(
+            set_name.format_packed(&[2, 0, 0xc3, 0x28]),
+            "setName(<malformed>)"
+        );
+    }
 }
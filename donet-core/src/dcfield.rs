@@ -46,13 +46,33 @@ use crate::hashgen::*;
 /// of a molecular field are the parameters of all the fields it
 /// represents, joined together in the order in which they were declared
 /// when the molecular field was declared.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ClassField<'dc> {
     Field(DCField<'dc>),
     Atomic(DCAtomicField<'dc>),
     Molecular(DCMolecularField<'dc>),
 }
 
+impl<'dc> ClassField<'dc> {
+    /// Returns a reference to the base [`DCField`] of this class field.
+    pub fn get_base_field(&self) -> &DCField<'dc> {
+        match self {
+            Self::Field(field) => field,
+            Self::Atomic(atomic) => atomic.get_base_field(),
+            Self::Molecular(molecular) => molecular.get_base_field(),
+        }
+    }
+
+    /// Sets the field ID of the base [`DCField`] of this class field.
+    pub(crate) fn set_field_id(&mut self, id: globals::FieldId) {
+        match self {
+            Self::Field(field) => field.set_field_id(id),
+            Self::Atomic(atomic) => atomic.set_field_id(id),
+            Self::Molecular(molecular) => molecular.set_field_id(id),
+        }
+    }
+}
+
 /// A different enumerator representing DC Field types used
 /// for DC Structs, since they cannot contain DC Atomic Fields.
 #[derive(Debug)]
@@ -64,7 +84,7 @@ pub enum StructField<'dc> {
 /// A DC field element can be declared within a dclass or a
 /// struct declaration. The DC field element must have a
 /// reference to its parent, which is stored in this enum type.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum FieldParent<'dc> {
     DClass(&'dc DClass<'dc>),
     Strukt(&'dc DCStruct<'dc>), // 'strukt' due to reserved keyword
@@ -82,7 +102,7 @@ macro_rules! has_keyword {
 /// A field of a Distributed Class. The DCField struct is a base for
 /// struct and dclass fields. In the DC language, there are three types
 /// of field declarations, which are: plain fields, atomic, and molecular.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct DCField<'dc> {
     keyword_list: DCKeywordList<'dc>,
     parent_element: FieldParent<'dc>,
@@ -112,7 +132,10 @@ impl DCFileConfigAccessor for DCField<'_> {
 
 impl LegacyDCHash for DCField<'_> {
     fn generate_hash(&self, hashgen: &mut DCHashGenerator) {
-        self.keyword_list.generate_hash(hashgen);
+        match self.get_dc_config().dc_hash_compatibility {
+            HashCompatibility::Panda3D => self.keyword_list.generate_hash(hashgen),
+            HashCompatibility::Astron => self.keyword_list.generate_sorted_hash(hashgen),
+        }
         self.field_type.clone().unwrap().generate_hash(hashgen);
 
         // It shouldn't be necessary to explicitly add the field ID
@@ -132,6 +155,22 @@ impl LegacyDCHash for DCField<'_> {
 }
 
 impl<'dc> DCField<'dc> {
+    /// Creates a new field with the given identifier and parent element.
+    /// The field is created with no type, keywords, or default value.
+    pub fn new(name: &str, parent: FieldParent<'dc>) -> Self {
+        Self {
+            keyword_list: DCKeywordList::default(),
+            parent_element: parent,
+            field_name: name.to_owned(),
+            field_id: 0,
+            field_type: None,
+            default_value_stale: true,
+            has_default_value: false,
+            default_value: vec![],
+            bogus_field: false,
+        }
+    }
+
     #[inline(always)]
     pub fn get_field_id(&self) -> globals::FieldId {
         self.field_id
@@ -142,6 +181,16 @@ impl<'dc> DCField<'dc> {
         self.field_name.clone()
     }
 
+    #[inline(always)]
+    pub fn get_field_type(&self) -> Option<&DCTypeDefinition> {
+        self.field_type.as_ref()
+    }
+
+    #[inline(always)]
+    pub fn get_keyword_list(&self) -> &DCKeywordList<'dc> {
+        &self.keyword_list
+    }
+
     /// Gets the parent DClass element reference.
     ///
     /// Panics if this field's parent element is not a DClass.
@@ -188,6 +237,13 @@ impl<'dc> DCField<'dc> {
         self.has_default_value
     }
 
+    /// Returns the packed default value of this field, which
+    /// is empty if [`Self::has_default_value`] is false.
+    #[inline(always)]
+    pub fn get_default_value(&self) -> &[u8] {
+        &self.default_value
+    }
+
     pub fn validate_ranges(&self, _packed_data: &Datagram) -> bool {
         todo!()
     }
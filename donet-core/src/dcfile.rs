@@ -20,15 +20,19 @@
 //! Root structure that stores the collection of DC elements
 //! in memory. Provides functions for manipulating the tree.
 
-use crate::dcfield::DCField;
+use crate::dcfield::{ClassField, DCField};
 use crate::dckeyword::DCKeyword;
-use crate::dclass::DClass;
+use crate::dclass::{DClass, FieldCopies};
+use crate::dclisting::DCListing;
 use crate::dconfig::*;
 use crate::dcstruct::DCStruct;
 use crate::dctype::DCTypeDefinition;
 use crate::globals;
 use crate::hashgen::*;
 use crate::parser::ast;
+use crate::parser::error::DCReadError;
+use codespan_reporting::diagnostic::Diagnostic;
+use thiserror::Error;
 
 /// Represents a Python-style import statement in the DC file.
 #[derive(Debug, Clone)]
@@ -68,6 +72,15 @@ impl std::fmt::Display for DCPythonImport {
     }
 }
 
+/// Returned by [`DCFile::check_hash`] if a remote DC file hash, such as
+/// the one sent by a client in its hello, differs from the local one.
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
+#[error("DC file hash mismatch: local hash is 0x{local:08x}, remote hash is 0x{remote:08x}")]
+pub struct HashMismatch {
+    pub local: globals::DCFileHash,
+    pub remote: globals::DCFileHash,
+}
+
 /// Data model that provides a high level representation of a single,
 /// or collection, of DC files and their elements such as class imports,
 /// type definitions, structures, and Distributed Classes.
@@ -82,8 +95,11 @@ pub struct DCFile<'dc> {
     type_defs: Vec<DCTypeDefinition>,
     field_id_2_field: Vec<&'dc DCField<'dc>>,
     // TODO: type_id_2_type, type_name_2_type
+    /// Offset that every class and field ID was shifted up by; see [`DCFile::with_id_offset`].
+    id_offset: u16,
     all_object_valid: bool,
     inherited_fields_stale: bool,
+    warnings: Vec<Diagnostic<usize>>,
 }
 
 impl From<interim::DCFile> for DCFile<'_> {
@@ -108,8 +124,10 @@ impl From<interim::DCFile> for DCFile<'_> {
             keywords,
             type_defs: vec![],
             field_id_2_field: vec![],
+            id_offset: 0,
             all_object_valid: true,
             inherited_fields_stale: false,
+            warnings: value.warnings,
         }
     }
 }
@@ -167,19 +185,53 @@ impl LegacyDCHash for DCFile<'_> {
                 hashgen.add_int(2);
             }
         }
-        hashgen.add_int(self.get_num_dclasses().try_into().unwrap());
+        hashgen.add_int(self.get_num_types().try_into().unwrap());
 
-        for strukt in &self.structs {
-            strukt.generate_hash(hashgen);
+        // Schemas that are only different by their ID offset are on
+        // different wire formats, so they must not have the same hash.
+        if self.id_offset != 0 {
+            hashgen.add_int(i32::from(self.id_offset));
         }
 
+        // Structs and classes are hashed in ID order, as they are declared.
+        let mut types: Vec<(globals::DClassId, &dyn LegacyDCHash)> = vec![];
+
+        for strukt in &self.structs {
+            types.push((strukt.get_struct_id(), strukt));
+        }
         for dclass in &self.dclasses {
-            dclass.generate_hash(hashgen);
+            types.push((dclass.get_dclass_id(), dclass));
+        }
+        types.sort_by_key(|(id, _)| *id);
+
+        for (_, dc_type) in types {
+            dc_type.generate_hash(hashgen);
         }
     }
 }
 
 impl<'dc> DCFile<'dc> {
+    /// Reads a DC file from its source, instead of from a path, so that
+    /// the DC file can be bundled into a binary with [`include_str!`].
+    ///
+    /// The DC file read is identical to reading the same source from a
+    /// file with [`crate::read_dc_files`], including its hash.
+    pub fn from_str(config: DCFileConfig, source: &str) -> Result<DCFile<'dc>, DCReadError> {
+        crate::read_dc(config, source.to_owned())
+    }
+
+    /// Reads a DC file from a buffer holding its source in UTF-8,
+    /// such as one bundled into a binary with [`include_bytes!`].
+    pub fn from_bytes(config: DCFileConfig, source: &[u8]) -> Result<DCFile<'dc>, DCReadError> {
+        match std::str::from_utf8(source) {
+            Ok(source) => Self::from_str(config, source),
+            Err(err) => Err(DCReadError::IO(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                err,
+            ))),
+        }
+    }
+
     /// Returns a 32-bit hash index associated with this file.  This number is
     /// guaranteed to be consistent if the contents of the file have not changed,
     /// and it is very likely to be different if the contents of the file do change.
@@ -197,11 +249,123 @@ impl<'dc> DCFile<'dc> {
         }
     }
 
+    /// Returns a copy of this file, with every class ID and field ID
+    /// shifted up by `offset`, so that the schemas of multiple games
+    /// can be combined without their IDs colliding. The structure of
+    /// the classes is unchanged, and the hash of the copy is computed
+    /// again, including the offset.
+    ///
+    /// The classes and fields of the copy are copies, which only know
+    /// the shifted IDs, and the parents of each class are the copies of
+    /// its parents. See [`DClass::with_id_offset`].
+    ///
+    /// Returns an error if an ID would no longer fit in 16 bits, or if
+    /// a class inherits from a class that is not in this file.
+    pub fn with_id_offset(&self, offset: u16) -> Result<DCFile<'dc>, String> {
+        let out_of_range = || {
+            format!(
+                "IDs of the DC file do not fit in 16 bits with an offset of {}.",
+                offset
+            )
+        };
+        let id_offset: u16 = self.id_offset.checked_add(offset).ok_or_else(out_of_range)?;
+
+        // IDs of this file that are the highest once shifted.
+        let last_class: Option<usize> =
+            (self.get_num_types().checked_sub(1)).map(|last| last + usize::from(self.id_offset));
+        let last_field: Option<usize> = self.fields().map(|(id, _, _)| usize::from(id)).max();
+
+        if let Some(highest) = last_class.max(last_field) {
+            if highest + usize::from(offset) > usize::from(u16::MAX) {
+                return Err(out_of_range());
+            }
+        }
+
+        let mut structs: Vec<DCStruct<'dc>> = self.structs.clone();
+
+        for strukt in &mut structs {
+            strukt.set_struct_id(strukt.get_struct_id() + offset);
+        }
+
+        // Classes are copied in declaration order, so the parents of a
+        // class are copied before it. The copies are leaked, as the
+        // classes declared after them refer to them as parents.
+        let mut copies: Vec<&'dc DClass<'dc>> = vec![];
+        let mut copied: FieldCopies<'dc> = FieldCopies::new();
+
+        for dclass in &self.dclasses {
+            let copy: DClass<'dc> = dclass.with_id_offset(offset, &copies, &mut copied)?;
+            copies.push(Box::leak(Box::new(copy)));
+        }
+        let dclasses: Vec<DClass<'dc>> = copies.into_iter().cloned().collect();
+
+        let field_id_2_field: Vec<&'dc DCField<'dc>> = self
+            .field_id_2_field
+            .iter()
+            .map(|field| match copied.get(&(*field as *const DCField)) {
+                Some(copy) => copy.get_base_field(),
+                None => *field,
+            })
+            .collect();
+
+        Ok(DCFile {
+            config: self.config.clone(),
+            baked_legacy_hash: 0_u32, // computed again for the copy
+            structs,
+            dclasses,
+            imports: self.imports.clone(),
+            keywords: self.keywords.clone(),
+            type_defs: self.type_defs.clone(),
+            field_id_2_field,
+            id_offset,
+            all_object_valid: self.all_object_valid,
+            inherited_fields_stale: self.inherited_fields_stale,
+            warnings: self.warnings.clone(),
+        })
+    }
+
     /// Returns a string with the hash as a pretty format hexadecimal.
     pub fn get_pretty_hash(&self) -> String {
         format!("0x{:0width$x}", self.get_legacy_hash(), width = 8) // 2 hex / byte = 8 hex
     }
 
+    /// Compares the hash of this file against a remote hash, such as
+    /// the one a client sends to the Client Agent in `ClientHello`.
+    ///
+    /// On a mismatch, both hashes are returned, so they can be logged.
+    pub fn check_hash(&self, remote: globals::DCFileHash) -> Result<(), HashMismatch> {
+        let local: globals::DCFileHash = self.get_legacy_hash();
+
+        if local == remote {
+            Ok(())
+        } else {
+            Err(HashMismatch { local, remote })
+        }
+    }
+
+    /// Returns the warnings found by the consistency checks of the
+    /// DC parser, such as unused typedefs, fields that shadow an
+    /// inherited field, and duplicate keywords on a field.
+    ///
+    /// These do not stop the DC file from being read, but each one
+    /// is labeled with the file ID and span it was found at, so they
+    /// can be rendered against the DC files that were read.
+    pub fn validate(&self) -> Vec<Diagnostic<usize>> {
+        self.warnings.clone()
+    }
+
+    /// Lists the keywords, typedefs, and distributed classes of
+    /// this file, with the IDs assigned to each class and field.
+    pub fn listing(&self) -> DCListing {
+        // TODO: list structs, once DC structs are modeled.
+        DCListing {
+            hash: self.get_legacy_hash(),
+            keywords: self.keywords.iter().map(|kw| kw.get_name()).collect(),
+            typedefs: self.type_defs.iter().map(|t| t.into()).collect(),
+            dclasses: self.dclasses.iter().map(|dclass| dclass.into()).collect(),
+        }
+    }
+
     // ---------- Python Imports ---------- //
 
     pub fn get_num_imports(&self) -> usize {
@@ -215,15 +379,28 @@ impl<'dc> DCFile<'dc> {
     // ---------- DC Keyword ---------- //
 
     pub fn get_num_keywords(&self) -> usize {
-        todo!();
+        self.keywords.len()
     }
 
-    pub fn get_keyword(&self, _index: usize) -> &'dc DCKeyword {
-        todo!();
+    pub fn get_keyword(&self, index: usize) -> Option<&DCKeyword> {
+        self.keywords.get(index)
     }
 
-    pub fn has_keyword(&self, _keyword: String) -> bool {
-        todo!();
+    pub fn has_keyword(&self, keyword: &str) -> bool {
+        self.keywords.iter().any(|kw| kw.get_name() == keyword)
+    }
+
+    /// Returns the number of structs and distributed classes of this
+    /// file combined, as they are numbered in the same space of IDs.
+    pub fn get_num_types(&self) -> usize {
+        self.structs.len() + self.dclasses.len()
+    }
+
+    /// Returns the ID to assign to the next struct or class declared.
+    fn next_type_id(&self) -> globals::DClassId {
+        (self.get_num_types() + usize::from(self.id_offset))
+            .try_into()
+            .expect("Ran out of DClass IDs.")
     }
 
     // ---------- Distributed Class ---------- //
@@ -232,32 +409,94 @@ impl<'dc> DCFile<'dc> {
         self.dclasses.len()
     }
 
-    pub fn get_dclass(&self, _index: usize) -> &'dc DClass {
-        todo!();
+    pub fn get_dclass(&self, index: usize) -> &'dc DClass {
+        self.dclasses.get(index).unwrap()
     }
 
     pub fn get_dclass_by_id(&self, id: globals::DClassId) -> &'dc DClass {
-        self.dclasses.get(usize::from(id)).unwrap()
+        self.get_class_by_id(id).unwrap()
     }
 
-    pub fn get_dclass_by_name(&self, _name: &str) -> &'dc DClass {
-        todo!();
+    pub fn get_dclass_by_name(&self, name: &str) -> &'dc DClass {
+        self.get_class_by_name(name).unwrap()
+    }
+
+    /// Returns the distributed class with the given class ID, if any.
+    ///
+    /// Class IDs are assigned in declaration order, starting at 0, and
+    /// are shared with structs, which is the same numbering used by
+    /// Astron and Panda3D. An ID that belongs to a struct returns `None`.
+    pub fn get_class_by_id(&self, id: globals::DClassId) -> Option<&DClass<'dc>> {
+        self.dclasses.iter().find(|dclass| dclass.get_dclass_id() == id)
+    }
+
+    /// Returns the distributed class declared with the given name, if any.
+    pub fn get_class_by_name(&self, name: &str) -> Option<&DClass<'dc>> {
+        self.dclasses.iter().find(|dclass| dclass.get_name() == name)
+    }
+
+    /// Appends a distributed class to this file, assigning it the
+    /// next class ID in declaration order, and builds the list of
+    /// fields it inherits from its parents.
+    pub fn add_dclass(&mut self, mut dclass: DClass<'dc>) {
+        dclass.set_dclass_id(self.next_type_id());
+        dclass.rebuild_inherited_fields();
+        self.dclasses.push(dclass);
+    }
+
+    /// Returns the number of fields declared across all the distributed
+    /// classes of this file. Inherited fields are only counted once,
+    /// in the class that declares them.
+    pub fn get_num_fields(&self) -> usize {
+        self.dclasses.iter().map(DClass::get_num_fields).sum()
+    }
+
+    /// Returns an iterator over the fields declared across all the
+    /// distributed classes of this file, with their global field ID
+    /// and the class that declares them. Classes are visited in class
+    /// ID order, and their fields in declaration order.
+    pub fn fields(
+        &self,
+    ) -> impl Iterator<Item = (globals::FieldId, &DClass<'dc>, &'dc ClassField<'dc>)> + '_ {
+        self.dclasses.iter().flat_map(|dclass| {
+            (0..dclass.get_num_fields())
+                .filter_map(|index| dclass.get_field(index))
+                .map(move |field| (field.get_base_field().get_field_id(), dclass, field))
+        })
+    }
+
+    /// Returns the field with the given global field ID, and the
+    /// distributed class that declares it, if any.
+    pub fn get_field_by_id(&self, id: globals::FieldId) -> Option<(&DClass<'dc>, &'dc ClassField<'dc>)> {
+        self.fields()
+            .find(|(field_id, _, _)| *field_id == id)
+            .map(|(_, dclass, field)| (dclass, field))
     }
 
     // ---------- DC Struct ---------- //
 
     pub fn get_num_structs(&self) -> usize {
-        todo!();
+        self.structs.len()
     }
 
     pub fn get_struct(&self, _index: usize) -> &'dc DCStruct {
         todo!();
     }
+
+    /// Appends a struct to this file, assigning it the next ID in
+    /// declaration order. Structs take IDs from the same space as
+    /// distributed classes, so a class declared after a struct is
+    /// numbered after it, as in Astron and Panda3D.
+    pub fn add_struct(&mut self, mut strukt: DCStruct<'dc>) {
+        strukt.set_struct_id(self.next_type_id());
+        self.structs.push(strukt);
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::read_dc;
 
     #[test]
     fn write_dc_python_import() {
@@ -295,8 +534,10 @@ mod tests {
             keywords: vec![],
             type_defs: vec![],
             field_id_2_field: vec![],
+            id_offset: 0,
             all_object_valid: false,
             inherited_fields_stale: false,
+            warnings: vec![],
         };
 
         assert_eq!(
@@ -314,6 +555,153 @@ mod tests {
             ",
         );
     }
+
+    #[test]
+    fn dclass_ids_in_declaration_order() {
+        let owner: DCFile = read_dc(DCFileConfig::default(), String::default()).unwrap();
+        let names: [&str; 4] = [
+            "DistributedNode", "DistributedAvatar", "DistributedToon", "LoginManager",
+        ];
+
+        let mut dcf: DCFile<'_> = DCFile {
+            config: DCFileConfig::default(),
+            baked_legacy_hash: 0_u32,
+            structs: vec![],
+            dclasses: vec![],
+            imports: vec![],
+            keywords: vec![],
+            type_defs: vec![],
+            field_id_2_field: vec![],
+            id_offset: 0,
+            all_object_valid: false,
+            inherited_fields_stale: false,
+            warnings: vec![],
+        };
+
+        for name in names {
+            dcf.add_dclass(DClass::new(&owner, name));
+        }
+
+        assert_eq!(dcf.get_num_dclasses(), names.len());
+
+        for (id, name) in names.iter().enumerate() {
+            let id: globals::DClassId = id.try_into().unwrap();
+
+            let by_id: &DClass = dcf.get_class_by_id(id).unwrap();
+            let by_name: &DClass = dcf.get_class_by_name(name).unwrap();
+
+            assert_eq!(by_id.get_dclass_id(), id);
+            assert_eq!(by_id.get_name(), *name);
+            assert!(std::ptr::eq(by_id, by_name));
+        }
+
+        assert!(dcf.get_class_by_id(4).is_none());
+        assert!(dcf.get_class_by_name("DistributedDonut").is_none());
+    }
+
+    #[test]
+    fn struct_declared_before_class() {
+        let owner: DCFile = read_dc(DCFileConfig::default(), String::default()).unwrap();
+
+        let mut dcf: DCFile<'_> = DCFile {
+            config: DCFileConfig::default(),
+            baked_legacy_hash: 0_u32,
+            structs: vec![],
+            dclasses: vec![],
+            imports: vec![],
+            keywords: vec![],
+            type_defs: vec![],
+            field_id_2_field: vec![],
+            id_offset: 0,
+            all_object_valid: false,
+            inherited_fields_stale: false,
+            warnings: vec![],
+        };
+
+        // struct BarrierData { ... };
+        // dclass DistributedNode { ... };
+        // struct Vec3 { ... };
+        // dclass DistributedAvatar { ... };
+        dcf.add_struct(DCStruct::new(&owner, "BarrierData"));
+        dcf.add_dclass(DClass::new(&owner, "DistributedNode"));
+        dcf.add_struct(DCStruct::new(&owner, "Vec3"));
+        dcf.add_dclass(DClass::new(&owner, "DistributedAvatar"));
+
+        assert_eq!(dcf.get_num_structs(), 2);
+        assert_eq!(dcf.get_num_dclasses(), 2);
+        assert_eq!(dcf.get_num_types(), 4);
+
+        let node: &DClass = dcf.get_class_by_name("DistributedNode").unwrap();
+        let avatar: &DClass = dcf.get_class_by_name("DistributedAvatar").unwrap();
+
+        assert_eq!(node.get_dclass_id(), 1);
+        assert_eq!(avatar.get_dclass_id(), 3);
+
+        assert!(std::ptr::eq(dcf.get_class_by_id(1).unwrap(), node));
+        assert!(std::ptr::eq(dcf.get_class_by_id(3).unwrap(), avatar));
+
+        // IDs that belong to structs are not classes
+        assert!(dcf.get_class_by_id(0).is_none());
+        assert!(dcf.get_class_by_id(2).is_none());
+
+        let shifted: DCFile = dcf.with_id_offset(10).unwrap();
+        let shifted_avatar: &DClass = shifted.get_class_by_id(13).unwrap();
+
+        assert_eq!(shifted_avatar.get_name(), "DistributedAvatar");
+        assert!(shifted.get_class_by_id(12).is_none());
+    }
+
+    #[test]
+    fn check_hash_against_remote() {
+        let dcf: DCFile = DCFile::from_str(DCFileConfig::default(), "keyword required;\n").unwrap();
+        let local: globals::DCFileHash = dcf.get_legacy_hash();
+        let remote: globals::DCFileHash = local.wrapping_add(1);
+
+        assert_eq!(dcf.check_hash(local), Ok(()));
+
+        let mismatch: HashMismatch = dcf.check_hash(remote).unwrap_err();
+
+        assert_eq!(mismatch, HashMismatch { local, remote });
+        assert_eq!(
+            mismatch.to_string(),
+            format!(
+                "DC file hash mismatch: local hash is {}, remote hash is 0x{:08x}",
+                dcf.get_pretty_hash(),
+                remote
+            )
+        );
+    }
+
+    #[test]
+    fn embedded_source_hash_matches_file() {
+        let source: &str = "\
+            from game.avatar import DistributedAvatar/AI/OV\n\
+            typedef uint32 doId;\n\
+            dclass DistributedAvatar {\n\
+                setName(string name) required broadcast ram;\n\
+                setParent(doId parent) broadcast;\n\
+            };\n\
+        ";
+
+        let path = std::env::temp_dir().join("donet_embedded_source_test.dc");
+        std::fs::write(&path, source).unwrap();
+
+        let paths: Vec<String> = vec![path.to_string_lossy().into_owned()];
+        let from_file: DCFile = crate::read_dc_files(DCFileConfig::default(), paths).unwrap();
+
+        std::fs::remove_file(path).unwrap();
+
+        let from_str: DCFile = DCFile::from_str(DCFileConfig::default(), source).unwrap();
+        let from_bytes: DCFile = DCFile::from_bytes(DCFileConfig::default(), source.as_bytes()).unwrap();
+
+        assert_eq!(from_str.get_legacy_hash(), from_file.get_legacy_hash());
+        assert_eq!(from_bytes.get_legacy_hash(), from_file.get_legacy_hash());
+
+        assert!(matches!(
+            DCFile::from_bytes(DCFileConfig::default(), &[0xff, 0xfe]),
+            Err(DCReadError::IO(_))
+        ));
+    }
 }
 
 /// Contains intermediate DC file structure and logic
@@ -346,6 +734,7 @@ pub(crate) mod interim {
         // TODO: type_id_2_type, type_name_2_type
         pub all_object_valid: bool,
         pub inherited_fields_stale: bool,
+        pub warnings: Vec<codespan_reporting::diagnostic::Diagnostic<usize>>,
     }
 
     impl From<DCFileConfig> for DCFile {
@@ -359,6 +748,7 @@ pub(crate) mod interim {
                 //field_id_2_field: vec![],
                 all_object_valid: true,
                 inherited_fields_stale: false,
+                warnings: vec![],
             }
         }
     }
@@ -369,6 +759,18 @@ pub(crate) mod interim {
             todo!();
         }
 
+        /// Emits a warning found by the consistency checks, and keeps it
+        /// so that it can be returned by [`super::DCFile::validate`].
+        pub fn add_warning(&mut self, pipeline: &mut PipelineData, diag: Diagnostic) {
+            let diag: codespan_reporting::diagnostic::Diagnostic<usize> = diag.into();
+
+            self.warnings.push(diag.clone());
+
+            pipeline
+                .emit_diagnostic(diag)
+                .expect("Failed to emit diagnostic.");
+        }
+
         /// Redundancy check for an array of strings that represent view suffixes.
         /// The lexer already generates a specific token type for view suffixes,
         /// and the parser grammar expects this token type, so we already are
@@ -501,7 +903,7 @@ pub(crate) mod interim {
 
                 return Err(anyhow!("Ran out of 16-bit DClass IDs!"));
             }
-            Ok(dc_num - 1_u16)
+            Ok(dc_num)
         }
     }
 }
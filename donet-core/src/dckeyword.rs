@@ -28,6 +28,30 @@ use multimap::MultiMap;
 /// as 4 bytes in modern 32-bit and 64-bit C/C++ compilers.
 pub type HistoricalFlag = i32;
 
+/// Bits assigned to each of the historical keywords in the flags
+/// bitmask, matching the values Panda sets up by default so that
+/// DC files which only use historical keywords keep their hash.
+const HISTORICAL_FLAGS: [(&str, HistoricalFlag); 9] = [
+    ("required", 0x0001),
+    ("broadcast", 0x0002),
+    ("ownrecv", 0x0004),
+    ("ram", 0x0008),
+    ("db", 0x0010),
+    ("clsend", 0x0020),
+    ("clrecv", 0x0040),
+    ("ownsend", 0x0080),
+    ("airecv", 0x0100),
+];
+
+/// Returns the historical flag bit for the given keyword name, or
+/// the bitwise complement of 0 if it is a custom keyword.
+pub(crate) fn historical_flag(name: &str) -> HistoricalFlag {
+    HISTORICAL_FLAGS
+        .iter()
+        .find(|(kw, _)| *kw == name)
+        .map_or(!0, |(_, flag)| *flag)
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DCKeyword {
     name: String,
@@ -60,6 +84,16 @@ impl LegacyDCHash for DCKeyword {
 }
 
 impl DCKeyword {
+    /// Creates a new keyword with the given identifier. Custom
+    /// keywords have no historical flag, so using them on a field
+    /// makes its keyword list hash by keyword names instead.
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            historical_flag: historical_flag(name),
+        }
+    }
+
     #[inline]
     pub fn get_name(&self) -> String {
         self.name.clone()
@@ -84,13 +118,23 @@ pub enum IdentifyKeyword {
 
 /// This is a list of [`DCKeyword`] structures, which represent
 /// communication keywords that may be set on a particular field.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct DCKeywordList<'dc> {
     keywords: Vec<&'dc DCKeyword>,
     kw_name_2_keyword: KeywordName2Keyword<'dc>,
     flags: HistoricalFlag,
 }
 
+impl Default for DCKeywordList<'_> {
+    fn default() -> Self {
+        Self {
+            keywords: vec![],
+            kw_name_2_keyword: MultiMap::new(),
+            flags: 0_i32,
+        }
+    }
+}
+
 impl std::cmp::PartialEq for DCKeywordList<'_> {
     fn eq(&self, other: &Self) -> bool {
         let target_kw_map: KeywordName2Keyword = other._get_keywords_by_name_map();
@@ -144,6 +188,36 @@ impl LegacyDCHash for DCKeywordList<'_> {
 }
 
 impl<'dc> DCKeywordList<'dc> {
+    /// Same as [`LegacyDCHash::generate_hash`], except that keyword
+    /// names are hashed sorted by name, as Astron does.
+    pub(crate) fn generate_sorted_hash(&self, hashgen: &mut DCHashGenerator) {
+        if self.flags != !0 {
+            hashgen.add_int(self.flags);
+        } else {
+            let mut keywords: Vec<&DCKeyword> = self.keywords.clone();
+            keywords.sort_by(|a, b| a.name.cmp(&b.name));
+
+            hashgen.add_int(keywords.len().try_into().unwrap());
+
+            for keyword in keywords {
+                keyword.generate_hash(hashgen);
+            }
+        }
+    }
+
+    /// Adds the given keyword to this list, mixing its historical flag
+    /// into the list's bitmask. Returns `Err` if it is already present.
+    pub fn add_keyword(&mut self, keyword: &'dc DCKeyword) -> Result<(), String> {
+        if self.kw_name_2_keyword.get(&keyword.name).is_some() {
+            return Err(format!("Keyword `{}` is already in this list.", keyword.name));
+        }
+        self.flags |= keyword.historical_flag;
+
+        self.keywords.push(keyword);
+        self.kw_name_2_keyword.insert(keyword.name.clone(), keyword);
+        Ok(())
+    }
+
     /// Returns the number of keywords in this keyword list.
     pub fn get_num_keywords(&self) -> usize {
         self.keywords.len()
@@ -181,10 +255,115 @@ impl<'dc> DCKeywordList<'dc> {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dcfield::{DCField, FieldParent};
+    use crate::dcfile::DCFile;
+    use crate::dclass::DClass;
+    use crate::dconfig::{DCFileConfig, HashCompatibility};
+    use crate::dctype::{DCTypeDefinition, DCTypeEnum};
+    use crate::read_dc;
+
+    fn field_hash(dclass: &DClass, keywords: &[&DCKeyword]) -> u32 {
+        let mut field: DCField = DCField::new("setColor", FieldParent::DClass(dclass));
+        let mut kw_list: DCKeywordList = DCKeywordList::default();
+
+        for keyword in keywords {
+            kw_list.add_keyword(keyword).unwrap();
+        }
+        field.set_field_type(DCTypeDefinition::from(DCTypeEnum::TUInt8));
+        field.set_field_keyword_list(kw_list);
+
+        let mut hashgen: DCHashGenerator = DCHashGenerator::default();
+
+        field.generate_hash(&mut hashgen);
+        hashgen.get_hash()
+    }
+
+    #[test]
+    fn custom_keyword_affects_hash() {
+        let dc_string: String = String::from("keyword p2p;\nkeyword unreliable;\n");
+        let dcf: DCFile = read_dc(DCFileConfig::default(), dc_string).unwrap();
+
+        assert_eq!(dcf.get_num_keywords(), 2);
+        assert!(dcf.has_keyword("p2p"));
+        assert!(dcf.has_keyword("unreliable"));
+        assert!(!dcf.has_keyword("broadcast"));
+
+        let p2p: &DCKeyword = dcf.get_keyword(0).unwrap();
+        assert_eq!(p2p.get_historical_flag(), !0);
+
+        let broadcast: DCKeyword = DCKeyword::new("broadcast");
+        assert_eq!(broadcast.get_historical_flag(), 0x0002);
+
+        let dclass: DClass = DClass::new(&dcf, "DistributedDonut");
+
+        let historical_only: u32 = field_hash(&dclass, &[&broadcast]);
+        let with_custom: u32 = field_hash(&dclass, &[&broadcast, p2p]);
+
+        assert_ne!(historical_only, with_custom);
+        assert_eq!(with_custom, field_hash(&dclass, &[&broadcast, p2p]));
+
+        let mut kw_list: DCKeywordList = DCKeywordList::default();
+
+        kw_list.add_keyword(p2p).unwrap();
+        assert!(kw_list.add_keyword(p2p).is_err());
+        assert!(kw_list.has_keyword(IdentifyKeyword::ByName("p2p".into())));
+    }
+
+    #[test]
+    fn keyword_order_hash_compatibility() {
+        let dc_string: String = String::from("keyword p2p;\nkeyword unreliable;\n");
+        let astron: DCFileConfig = DCFileConfig {
+            dc_hash_compatibility: HashCompatibility::Astron,
+            ..DCFileConfig::default()
+        };
+        let panda_dcf: DCFile = read_dc(DCFileConfig::default(), dc_string.clone()).unwrap();
+        let astron_dcf: DCFile = read_dc(astron, dc_string).unwrap();
+
+        let (p2p, unreliable) = (
+            panda_dcf.get_keyword(0).unwrap(),
+            panda_dcf.get_keyword(1).unwrap(),
+        );
+
+        let panda: DClass = DClass::new(&panda_dcf, "DistributedDonut");
+        let astron: DClass = DClass::new(&astron_dcf, "DistributedDonut");
+
+        // Panda3D hashes keywords in the order they are on the field.
+        assert_ne!(
+            field_hash(&panda, &[p2p, unreliable]),
+            field_hash(&panda, &[unreliable, p2p])
+        );
+        // Astron hashes them sorted by name.
+        assert_eq!(
+            field_hash(&astron, &[p2p, unreliable]),
+            field_hash(&astron, &[unreliable, p2p])
+        );
+        assert_eq!(
+            field_hash(&panda, &[p2p, unreliable]),
+            field_hash(&astron, &[unreliable, p2p])
+        );
+        assert_ne!(
+            field_hash(&panda, &[unreliable, p2p]),
+            field_hash(&astron, &[unreliable, p2p])
+        );
+
+        // with historical keywords only, both hash the same
+        let broadcast: DCKeyword = DCKeyword::new("broadcast");
+        let ram: DCKeyword = DCKeyword::new("ram");
+
+        assert_eq!(
+            field_hash(&panda, &[&ram, &broadcast]),
+            field_hash(&astron, &[&broadcast, &ram])
+        );
+    }
+}
+
 /// Contains intermediate keyword structures and logic
 /// for semantic analysis as the keyword/lists is being built.
 pub(crate) mod interim {
-    use super::HistoricalFlag;
+    use super::{historical_flag, HistoricalFlag};
     use crate::parser::ast;
     use crate::parser::lexer::Span;
     use multimap::MultiMap;
@@ -201,17 +380,14 @@ pub(crate) mod interim {
         fn from(value: ast::KeywordDefinition) -> Self {
             Self {
                 span: value.span,
-                name: value.identifier,
-                historical_flag: {
-                    if value.historical {
-                        // Sets the historical flag bitmask to the bitwise complement of 0
-                        // (!0 in Rust, or ~0 in C/C++), as if the keyword were not one
-                        // of the historically defined keywords.
-                        !0
-                    } else {
-                        0
-                    }
+                // Custom keywords get the bitwise complement of 0 (!0 in Rust,
+                // or ~0 in C/C++), so that any keyword list they are mixed
+                // into will include every keyword name in its hash.
+                historical_flag: match value.historical {
+                    true => historical_flag(&value.identifier),
+                    false => !0,
                 },
+                name: value.identifier,
             }
         }
     }
@@ -20,17 +20,39 @@
 //! Data model for Distributed Class definitions in the DC file.
 //! Stores DC Fields and tracks class hierarchy.
 
+use crate::datagram::datagram::Datagram;
+use crate::dcaccessor::FieldAccessor;
 use crate::dcatomic::DCAtomicField;
-use crate::dcfield::ClassField;
+use crate::dcfield::{ClassField, DCField};
 use crate::dcfile::DCFile;
 use crate::dconfig::*;
+use crate::dcstruct::zero_value;
+use crate::dctype::DCTypeDefinition;
 use crate::globals;
 use crate::hashgen::*;
 use multimap::MultiMap;
+use std::collections::{HashMap, HashSet};
 
+// These maps are only used for looking up fields. Their iteration order
+// is not deterministic, so fields are always iterated using the vectors.
 pub type FieldName2Field<'dc> = MultiMap<String, &'dc ClassField<'dc>>;
 pub type FieldId2Field<'dc> = MultiMap<globals::FieldId, &'dc ClassField<'dc>>;
 
+/// Maps the base of each field copied by [`DClass::with_id_offset`] to its copy.
+pub(crate) type FieldCopies<'dc> = HashMap<*const DCField<'dc>, &'dc ClassField<'dc>>;
+
+/// What [`DClass::pack_required_fields`] does with a required field
+/// that is not given a value, and has no default value in the DC file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MissingDefault {
+    /// Fail to pack the fields.
+    #[default]
+    Reject,
+    /// Pack the zero value of the field's type, such as zero for
+    /// numeric types, or an empty string.
+    Zero,
+}
+
 /// Represents a Distributed Class defined in the DC file.
 /// Contains a map of DC Fields, as well as atomic and
 /// molecular fields that are declared within the class.
@@ -117,6 +139,149 @@ impl LegacyDCHash for DClass<'_> {
 }
 
 impl<'dc> DClass<'dc> {
+    /// Creates a new, empty Distributed Class with no parents or fields.
+    pub fn new(dcf: &'dc DCFile<'dc>, name: &str) -> Self {
+        Self {
+            dcfile: dcf,
+            class_name: name.to_owned(),
+            class_id: 0,
+            is_bogus_class: false,
+            class_parents: vec![],
+            constructor: None,
+            fields: vec![],
+            inherited_fields: vec![],
+            field_name_2_field: MultiMap::new(),
+            field_id_2_field: MultiMap::new(),
+        }
+    }
+
+    /// Adds a parent class, after any parents added before it. The
+    /// fields of the parent are inherited once this class is added
+    /// to its DC file, with [`DCFile::add_dclass`].
+    pub fn add_parent(&mut self, parent: &'dc DClass<'dc>) {
+        self.class_parents.push(parent);
+    }
+
+    /// Adds a field declared in this class, after any fields added
+    /// before it, which is the order the fields are iterated in.
+    pub fn add_field(&mut self, field: &'dc ClassField<'dc>) {
+        let base = field.get_base_field();
+
+        self.field_name_2_field.insert(base.get_field_name(), field);
+        self.field_id_2_field.insert(base.get_field_id(), field);
+        self.fields.push(field);
+    }
+
+    /// Iterates over the fields of this class, including inherited
+    /// fields, that a client may send updates for. Those are `clsend`
+    /// fields, and `ownsend` fields if the client owns the object.
+    pub fn sendable_fields(&self, owner: bool) -> impl Iterator<Item = &'dc ClassField<'dc>> + '_ {
+        self.inherited_fields().filter(move |field| {
+            let base = field.get_base_field();
+            base.is_clsend() || (owner && base.is_ownsend())
+        })
+    }
+
+    /// Iterates over the fields of this class, including inherited
+    /// fields, that a client receives, such as when the object enters
+    /// its view. Those are `broadcast` and `clrecv` fields, and `ownrecv`
+    /// fields if the client owns the object.
+    pub fn receivable_fields(&self, owner: bool) -> impl Iterator<Item = &'dc ClassField<'dc>> + '_ {
+        self.inherited_fields().filter(move |field| {
+            let base = field.get_base_field();
+            base.is_broadcast() || base.is_clrecv() || (owner && base.is_ownrecv())
+        })
+    }
+
+    /// Packs a value for each `required` field of this class, in
+    /// order, as they are sent when creating an object of the class.
+    ///
+    /// Fields are packed with their value in `values`, keyed by field
+    /// ID, if one is given. Otherwise, they are packed with their default
+    /// value from the DC file. Fields with neither are handled by the
+    /// given policy. Types without a zero value, such as fixed length
+    /// arrays, are always rejected.
+    pub fn pack_required_fields(
+        &self,
+        values: &HashMap<globals::FieldId, Vec<u8>>,
+        policy: MissingDefault,
+    ) -> Result<Datagram, String> {
+        let mut dg: Datagram = Datagram::default();
+
+        // molecular fields are packed as their atomic fields
+        let required = self
+            .inherited_fields()
+            .filter(|field| !matches!(field, ClassField::Molecular(_)))
+            .filter(|field| field.get_base_field().is_required());
+
+        for field in required {
+            let base = field.get_base_field();
+
+            let value: Vec<u8> = if let Some(value) = values.get(&base.get_field_id()) {
+                value.clone()
+            } else if base.has_default_value() {
+                base.get_default_value().to_vec()
+            } else if policy == MissingDefault::Zero {
+                let types: Vec<&DCTypeDefinition> = FieldAccessor::from(field).parameter_types()?;
+                let zeros: Option<Vec<Vec<u8>>> = types.into_iter().map(zero_value).collect();
+
+                zeros
+                    .ok_or_else(|| {
+                        format!(
+                            "Required field `{}` of `{}` has no default, and its type has no zero value.",
+                            base.get_field_name(),
+                            self.class_name
+                        )
+                    })?
+                    .concat()
+            } else {
+                return Err(format!(
+                    "Required field `{}` of `{}` was not given a value, and has no default.",
+                    base.get_field_name(),
+                    self.class_name
+                ));
+            };
+            dg.add_data(value).map_err(|err| err.to_string())?;
+        }
+        Ok(dg)
+    }
+
+    /// Packs the `<OTHER>` section of an enter message for a client,
+    /// which is a field count, followed by the ID and value of each
+    /// field that is not `required`, that the client may receive, and
+    /// that has a current value in `values`, keyed by field ID.
+    ///
+    /// If `owner` is true, as when a client is granted ownership of the
+    /// object, this includes every `ownrecv` field with a value, not
+    /// just `ram` fields. Fields are packed in declaration order.
+    pub fn pack_other_fields(
+        &self,
+        values: &HashMap<globals::FieldId, Vec<u8>>,
+        owner: bool,
+    ) -> Result<Datagram, String> {
+        let other: Vec<(globals::FieldId, &Vec<u8>)> = self
+            .receivable_fields(owner)
+            .filter(|field| !matches!(field, ClassField::Molecular(_)))
+            .map(|field| field.get_base_field())
+            .filter(|base| !base.is_required())
+            .filter_map(|base| values.get(&base.get_field_id()).map(|v| (base.get_field_id(), v)))
+            .collect();
+
+        let count: u16 = other
+            .len()
+            .try_into()
+            .map_err(|_| format!("`{}` has too many fields to pack.", self.class_name))?;
+
+        let mut dg: Datagram = Datagram::default();
+        dg.add_u16(count).map_err(|err| err.to_string())?;
+
+        for (id, value) in other {
+            dg.add_u16(id).map_err(|err| err.to_string())?;
+            dg.add_data(value.clone()).map_err(|err| err.to_string())?;
+        }
+        Ok(dg)
+    }
+
     pub fn get_field_by_name(&self, name: &str) -> Option<&'dc ClassField> {
         match self.field_name_2_field.get(name) {
             Some(pointer) => Some(pointer),
@@ -124,6 +289,15 @@ impl<'dc> DClass<'dc> {
         }
     }
 
+    /// Returns an accessor for packing and unpacking the field with
+    /// the given name, among the fields of this class, including
+    /// inherited fields, where a redeclared field replaces the parent's.
+    pub fn field(&self, name: &str) -> Option<FieldAccessor<'dc>> {
+        self.inherited_fields()
+            .find(|field| field.get_base_field().get_field_name() == name)
+            .map(FieldAccessor::from)
+    }
+
     #[inline(always)]
     pub fn get_name(&self) -> String {
         self.class_name.clone()
@@ -134,13 +308,18 @@ impl<'dc> DClass<'dc> {
         self.class_id
     }
 
+    #[inline(always)]
+    pub(crate) fn set_dclass_id(&mut self, id: globals::DClassId) {
+        self.class_id = id;
+    }
+
     #[inline(always)]
     pub fn get_num_parents(&self) -> usize {
         self.class_parents.len()
     }
 
     #[inline(always)]
-    pub fn get_parent(&self, index: usize) -> Option<&'static DClass> {
+    pub fn get_parent(&self, index: usize) -> Option<&'dc DClass<'dc>> {
         // copy the reference inside the option instead of a reference to the reference
         self.class_parents.get(index).cloned()
     }
@@ -158,6 +337,806 @@ impl<'dc> DClass<'dc> {
             None
         }
     }
+
+    /// Returns the number of fields declared directly in this class,
+    /// not including fields inherited from parent classes.
+    #[inline(always)]
+    pub fn get_num_fields(&self) -> usize {
+        self.fields.len()
+    }
+
+    /// Returns the nth field declared in this class, in declaration order.
+    #[inline(always)]
+    pub fn get_field(&self, index: usize) -> Option<&'dc ClassField<'dc>> {
+        self.fields.get(index).copied()
+    }
+
+    /// Returns the number of fields of this class, including
+    /// fields inherited from parent classes.
+    #[inline(always)]
+    pub fn get_num_inherited_fields(&self) -> usize {
+        self.inherited_fields.len()
+    }
+
+    /// Returns the nth field of this class, including inherited fields.
+    #[inline(always)]
+    pub fn get_inherited_field(&self, index: usize) -> Option<&'dc ClassField<'dc>> {
+        self.inherited_fields.get(index).copied()
+    }
+
+    /// Returns an iterator over all the fields of this class, including
+    /// inherited fields. Inherited fields come first, in the order of the
+    /// parents, followed by the fields declared in this class.
+    pub fn inherited_fields(&self) -> impl Iterator<Item = &'dc ClassField<'dc>> + '_ {
+        self.inherited_fields.iter().copied()
+    }
+
+    /// Rebuilds the list of inherited fields from the class parents.
+    /// This is done by [`DCFile::add_dclass`], once the parents and
+    /// fields of the class are known.
+    ///
+    /// If more than one parent declares a field with the same name,
+    /// the field from the earlier parent is the one inherited.
+    ///
+    /// A field declared in this class with the same name as a field
+    /// of a parent overrides (shadows) it, following Astron. The
+    /// parent's field is not inherited, and the overriding field is
+    /// listed with the fields of this class. Both fields keep their
+    /// own field IDs, so the parent's field ID is never used to
+    /// reach the field of this class.
+    pub fn rebuild_inherited_fields(&mut self) {
+        let mut names: HashSet<String> = self
+            .fields
+            .iter()
+            .map(|field| field.get_base_field().get_field_name())
+            .collect();
+
+        self.inherited_fields.clear();
+
+        for parent in &self.class_parents {
+            for field in parent.inherited_fields() {
+                if names.insert(field.get_base_field().get_field_name()) {
+                    self.inherited_fields.push(field);
+                }
+            }
+        }
+        self.inherited_fields.extend(self.fields.iter().copied());
+    }
+
+    /// Returns a copy of this class for a copy of its DC file with every
+    /// ID shifted up by `offset`, as made by [`DCFile::with_id_offset`].
+    ///
+    /// The fields declared in this class are copied with their field IDs
+    /// shifted. Parents are replaced with the class of the same name in
+    /// `copies`, the classes of the copy made so far, so a parent must be
+    /// copied before its children. `copied` maps the base of each field
+    /// copied so far to its copy, so that molecular fields and constructors
+    /// refer to the copies of their atomic fields.
+    ///
+    /// The copied fields are leaked, as the DC file of the copy refers
+    /// to them for as long as it lives, which is usually the program.
+    pub(crate) fn with_id_offset(
+        &self,
+        offset: u16,
+        copies: &[&'dc DClass<'dc>],
+        copied: &mut FieldCopies<'dc>,
+    ) -> Result<DClass<'dc>, String> {
+        let mut parents: Vec<&'dc DClass<'dc>> = vec![];
+
+        for parent in &self.class_parents {
+            let copy = copies
+                .iter()
+                .find(|dclass| dclass.get_name() == parent.get_name());
+
+            parents.push(copy.copied().ok_or_else(|| {
+                format!(
+                    "Parent `{}` of `{}` is not a class of the DC file.",
+                    parent.get_name(),
+                    self.class_name
+                )
+            })?);
+        }
+
+        let mut copy: DClass<'dc> = DClass::new(self.dcfile, &self.class_name);
+        copy.class_id = self.class_id + offset;
+        copy.is_bogus_class = self.is_bogus_class;
+        copy.class_parents = parents;
+
+        // returns the copy of an atomic field, if it was copied
+        let relink = |copied: &FieldCopies<'dc>, atomic: &'dc DCAtomicField<'dc>| {
+            let base: *const DCField = atomic.get_base_field();
+
+            match copied.get(&base) {
+                Some(ClassField::Atomic(copy)) => copy,
+                _ => atomic,
+            }
+        };
+
+        for field in &self.fields {
+            let mut field_copy: ClassField<'dc> = (*field).clone();
+            field_copy.set_field_id(field.get_base_field().get_field_id() + offset);
+
+            if let ClassField::Molecular(molecular) = &mut field_copy {
+                molecular.relink_atomic_fields(|atomic| relink(copied, atomic));
+            }
+            let field_copy: &'dc ClassField<'dc> = Box::leak(Box::new(field_copy));
+
+            copied.insert(field.get_base_field(), field_copy);
+            copy.add_field(field_copy);
+        }
+
+        if let Some(constructor) = self.constructor {
+            let copy_of: &'dc DCAtomicField<'dc> = relink(copied, constructor);
+
+            copy.constructor = Some(if std::ptr::eq(copy_of, constructor) {
+                let mut constructor_copy: DCAtomicField<'dc> = constructor.clone();
+                constructor_copy.set_field_id(constructor.get_base_field().get_field_id() + offset);
+
+                Box::leak(Box::new(constructor_copy))
+            } else {
+                copy_of
+            });
+        }
+        copy.rebuild_inherited_fields();
+        Ok(copy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dcaccessor::FieldValue;
+    use crate::dcfield::{DCField, FieldParent};
+    use crate::dckeyword::{DCKeyword, DCKeywordList};
+    use crate::dclisting::DCListing;
+    use crate::dcmolecular::DCMolecularField;
+    use crate::dctype::DCTypeEnum;
+    use crate::read_dc;
+
+    fn new_dclass<'dc>(
+        dcf: &'dc DCFile<'dc>,
+        name: &str,
+        parents: Vec<&'dc DClass<'dc>>,
+        fields: Vec<&'dc ClassField<'dc>>,
+    ) -> DClass<'dc> {
+        DClass {
+            dcfile: dcf,
+            class_name: name.to_owned(),
+            class_id: 0,
+            is_bogus_class: false,
+            class_parents: parents,
+            constructor: None,
+            fields,
+            inherited_fields: vec![],
+            field_name_2_field: MultiMap::new(),
+            field_id_2_field: MultiMap::new(),
+        }
+    }
+
+    /// Builds fields declared by an `Owner` class, that lives as
+    /// long as the DC file, with the given names, IDs, and keywords.
+    fn owner_with_fields<'dc>(
+        dcf: &'dc DCFile<'dc>,
+        fields: &[(&str, globals::FieldId, &[&str])],
+    ) -> Vec<DCField<'dc>> {
+        let owner: &'dc DClass<'dc> = Box::leak(Box::new(new_dclass(dcf, "Owner", vec![], vec![])));
+
+        fields
+            .iter()
+            .map(|(name, id, keywords)| {
+                let mut field: DCField = DCField::new(name, FieldParent::DClass(owner));
+                let mut list: DCKeywordList = DCKeywordList::default();
+
+                for keyword in keywords.iter() {
+                    list.add_keyword(Box::leak(Box::new(DCKeyword::new(keyword))))
+                        .unwrap();
+                }
+                field.set_field_id(*id);
+                field.set_field_keyword_list(list);
+                field
+            })
+            .collect()
+    }
+
+    fn field_names(dclass: &DClass) -> Vec<String> {
+        dclass
+            .inherited_fields()
+            .map(|field| field.get_base_field().get_field_name())
+            .collect()
+    }
+
+    #[test]
+    fn inherited_field_order() {
+        let dcf: DCFile = read_dc(DCFileConfig::default(), String::default()).unwrap();
+
+        let fields: Vec<ClassField> = owner_with_fields(
+            &dcf,
+            &[
+                ("a", 0, &[]),
+                ("b", 0, &[]),
+                ("c", 0, &[]),
+                ("d", 0, &[]),
+                ("a", 0, &[]),
+            ],
+        )
+        .into_iter()
+        .map(ClassField::Field)
+        .collect();
+        let (a, b, c, d, duplicate_a) = (&fields[0], &fields[1], &fields[2], &fields[3], &fields[4]);
+
+        let mut first: DClass = new_dclass(&dcf, "First", vec![], vec![a, b]);
+        first.rebuild_inherited_fields();
+
+        let mut second: DClass = new_dclass(&dcf, "Second", vec![], vec![duplicate_a, c]);
+        second.rebuild_inherited_fields();
+
+        let mut child: DClass = new_dclass(&dcf, "Child", vec![&first, &second], vec![d]);
+        child.rebuild_inherited_fields();
+
+        assert_eq!(child.get_num_fields(), 1);
+        assert_eq!(child.get_num_inherited_fields(), 4);
+        assert_eq!(field_names(&child), vec!["a", "b", "c", "d"]);
+
+        // 'a' is inherited from the first parent, not the second.
+        assert!(std::ptr::eq(child.get_inherited_field(0).unwrap(), a));
+
+        // Rebuilding must always produce the same order.
+        let before: Vec<String> = field_names(&child);
+
+        child.rebuild_inherited_fields();
+        assert_eq!(field_names(&child), before);
+    }
+
+    #[test]
+    fn same_file_built_twice_is_identical() {
+        // Builds the classes the way they are built into a DC file, so
+        // the order they are read back in is the one the file gives.
+        let build = || -> (Vec<Vec<String>>, globals::DCFileHash) {
+            let owner: DCFile = read_dc(DCFileConfig::default(), String::default()).unwrap();
+
+            let fields: Vec<ClassField> = owner_with_fields(
+                &owner,
+                &[
+                    ("setX", 0, &[]),
+                    ("setY", 1, &[]),
+                    ("setZ", 2, &[]),
+                    ("setName", 3, &[]),
+                    ("setChat", 4, &[]),
+                ],
+            )
+            .into_iter()
+            .map(|mut field| {
+                field.set_field_type(DCTypeEnum::TInt32.into()); // hashed
+                ClassField::Field(field)
+            })
+            .collect();
+
+            let mut node: DClass = DClass::new(&owner, "DistributedNode");
+            node.add_field(&fields[0]);
+            node.add_field(&fields[1]);
+            node.add_field(&fields[2]);
+
+            let mut parents: DCFile = DCFile::from_str(DCFileConfig::default(), "").unwrap();
+            parents.add_dclass(node);
+            let node: &DClass = parents.get_class_by_name("DistributedNode").unwrap();
+
+            let mut avatar: DClass = DClass::new(&owner, "DistributedAvatar");
+            avatar.add_parent(node);
+            avatar.add_field(&fields[3]);
+            avatar.add_field(&fields[4]);
+
+            let mut dcf: DCFile = DCFile::from_str(DCFileConfig::default(), "").unwrap();
+            dcf.add_dclass(node.clone());
+            dcf.add_dclass(avatar);
+
+            let order: Vec<Vec<String>> = (0..dcf.get_num_dclasses())
+                .map(|index| field_names(dcf.get_dclass(index)))
+                .collect();
+
+            (order, dcf.get_legacy_hash())
+        };
+
+        let (first_order, first_hash) = build();
+        let (second_order, second_hash) = build();
+
+        assert_eq!(
+            first_order,
+            vec![
+                vec!["setX", "setY", "setZ"],
+                vec!["setX", "setY", "setZ", "setName", "setChat"],
+            ]
+        );
+        assert_eq!(first_order, second_order);
+        assert_eq!(first_hash, second_hash);
+    }
+
+    #[test]
+    fn redeclared_field_overrides_parent() {
+        let dcf: DCFile = read_dc(DCFileConfig::default(), String::default()).unwrap();
+
+        let fields: Vec<ClassField> = owner_with_fields(
+            &dcf,
+            &[
+                ("setX", 0, &[]),
+                ("setY", 1, &[]),
+                ("setZ", 2, &[]),
+                ("setX", 3, &[]),
+            ],
+        )
+        .into_iter()
+        .map(ClassField::Field)
+        .collect();
+        let (set_x, set_y, set_z, override_x) = (&fields[0], &fields[1], &fields[2], &fields[3]);
+
+        let mut parent: DClass = new_dclass(&dcf, "DistributedNode", vec![], vec![set_x, set_y]);
+        parent.rebuild_inherited_fields();
+
+        let mut child: DClass = new_dclass(&dcf, "DistributedAvatar", vec![&parent], vec![set_z, override_x]);
+        child.rebuild_inherited_fields();
+
+        // the parent's `setX` is replaced, and not inherited twice
+        assert_eq!(field_names(&child), vec!["setY", "setZ", "setX"]);
+        assert!(std::ptr::eq(child.get_inherited_field(2).unwrap(), override_x));
+        assert_eq!(child.field("setX").unwrap().get_field_id(), 3);
+
+        let ids: Vec<globals::FieldId> = child
+            .inherited_fields()
+            .map(|field| field.get_base_field().get_field_id())
+            .collect();
+
+        assert_eq!(ids, vec![1, 2, 3]);
+        assert_eq!(field_names(&parent), vec!["setX", "setY"]);
+    }
+
+    #[test]
+    fn pack_unpack_field_by_name() {
+        let dcf: DCFile = read_dc(DCFileConfig::default(), String::default()).unwrap();
+
+        let [mut set_name]: [DCField; 1] = owner_with_fields(&dcf, &[("setName", 0, &[])])
+            .try_into()
+            .unwrap();
+        set_name.set_field_type(DCTypeEnum::TVarString.into());
+
+        let set_name = ClassField::Field(set_name);
+        let mut parent: DClass = new_dclass(&dcf, "DistributedAvatar", vec![], vec![&set_name]);
+        parent.rebuild_inherited_fields();
+
+        let mut child: DClass = new_dclass(&dcf, "DistributedToon", vec![&parent], vec![]);
+        child.rebuild_inherited_fields();
+
+        let values: Vec<FieldValue> = vec![FieldValue::String("Donut".into())];
+
+        for dclass in [&parent, &child] {
+            let accessor: FieldAccessor = dclass.field("setName").unwrap();
+            let packed: Vec<u8> = accessor.pack(&values).unwrap().get_data();
+
+            assert_eq!(packed, vec![5, 0, b'D', b'o', b'n', b'u', b't']);
+            assert_eq!(accessor.unpack(&packed).unwrap(), values);
+        }
+        assert!(child.field("setRadius").is_none());
+    }
+
+    #[test]
+    fn client_visible_fields() {
+        let dcf: DCFile = read_dc(DCFileConfig::default(), String::default()).unwrap();
+
+        let fields: Vec<ClassField> = owner_with_fields(
+            &dcf,
+            &[
+                ("setName", 0, &["broadcast", "ram"]),
+                ("setChat", 1, &["broadcast", "clsend"]),
+                ("setPing", 2, &["clrecv"]),
+                ("setPosition", 3, &["ownsend", "broadcast"]),
+                ("setMoney", 4, &["ownrecv", "db"]),
+                ("setSecret", 5, &["ram", "db"]),
+            ],
+        )
+        .into_iter()
+        .map(ClassField::Field)
+        .collect();
+        let mut dclass: DClass = new_dclass(&dcf, "DistributedToon", vec![], fields.iter().collect());
+        dclass.rebuild_inherited_fields();
+
+        let names = |fields: Vec<&ClassField>| -> Vec<String> {
+            fields
+                .iter()
+                .map(|field| field.get_base_field().get_field_name())
+                .collect()
+        };
+
+        assert_eq!(names(dclass.sendable_fields(false).collect()), vec!["setChat"]);
+        assert_eq!(
+            names(dclass.sendable_fields(true).collect()),
+            vec!["setChat", "setPosition"]
+        );
+        assert_eq!(
+            names(dclass.receivable_fields(false).collect()),
+            vec!["setName", "setChat", "setPing", "setPosition"]
+        );
+        assert_eq!(
+            names(dclass.receivable_fields(true).collect()),
+            vec!["setName", "setChat", "setPing", "setPosition", "setMoney"]
+        );
+    }
+
+    #[test]
+    fn global_field_iteration() {
+        let owner: DCFile = read_dc(DCFileConfig::default(), String::default()).unwrap();
+
+        let fields: Vec<ClassField> = owner_with_fields(
+            &owner,
+            &[
+                ("setX", 0, &[]),
+                ("setY", 1, &[]),
+                ("setName", 2, &[]),
+                ("setColor", 3, &[]),
+                ("setFlavor", 4, &[]),
+                ("setSize", 5, &[]),
+            ],
+        )
+        .into_iter()
+        .map(ClassField::Field)
+        .collect();
+
+        let node: DClass = new_dclass(&owner, "DistributedNode", vec![], fields[..2].iter().collect());
+        let toon: DClass = new_dclass(
+            &owner,
+            "DistributedToon",
+            vec![&node],
+            fields[2..3].iter().collect(),
+        );
+        let donut: DClass = new_dclass(&owner, "DistributedDonut", vec![], fields[3..].iter().collect());
+
+        let mut dcf: DCFile = DCFile::from_str(DCFileConfig::default(), "").unwrap();
+        dcf.add_dclass(node.clone());
+        dcf.add_dclass(toon.clone());
+        dcf.add_dclass(donut.clone());
+
+        let per_class: usize = (0..dcf.get_num_dclasses())
+            .map(|index| {
+                dcf.get_class_by_id(index.try_into().unwrap())
+                    .unwrap()
+                    .get_num_fields()
+            })
+            .sum();
+
+        assert_eq!(dcf.get_num_fields(), 6);
+        assert_eq!(dcf.get_num_fields(), per_class);
+        assert_eq!(dcf.fields().count(), per_class);
+
+        let fields: Vec<(globals::FieldId, String, String)> = dcf
+            .fields()
+            .map(|(id, dclass, field)| (id, dclass.get_name(), field.get_base_field().get_field_name()))
+            .collect();
+
+        assert_eq!(
+            fields,
+            vec![
+                (0, "DistributedNode".into(), "setX".into()),
+                (1, "DistributedNode".into(), "setY".into()),
+                (2, "DistributedToon".into(), "setName".into()),
+                (3, "DistributedDonut".into(), "setColor".into()),
+                (4, "DistributedDonut".into(), "setFlavor".into()),
+                (5, "DistributedDonut".into(), "setSize".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn remapped_id_space() {
+        let owner: DCFile = read_dc(DCFileConfig::default(), String::default()).unwrap();
+
+        let fields: Vec<ClassField> =
+            owner_with_fields(&owner, &[("setX", 0, &[]), ("setY", 1, &[]), ("setName", 2, &[])])
+                .into_iter()
+                .map(|mut field| {
+                    field.set_field_type(DCTypeEnum::TInt32.into()); // hashed
+                    ClassField::Field(field)
+                })
+                .collect();
+
+        let node: DClass = new_dclass(&owner, "DistributedNode", vec![], fields[..2].iter().collect());
+        let toon: DClass = new_dclass(&owner, "DistributedToon", vec![], fields[2..].iter().collect());
+
+        let mut dcf: DCFile = DCFile::from_str(DCFileConfig::default(), "").unwrap();
+        dcf.add_dclass(node);
+        dcf.add_dclass(toon);
+
+        let remapped: DCFile = dcf.with_id_offset(1000).unwrap();
+
+        let class_ids: Vec<globals::DClassId> = (0..remapped.get_num_dclasses())
+            .map(|index| remapped.get_dclass(index).get_dclass_id())
+            .collect();
+
+        let field_ids: Vec<globals::FieldId> = remapped.fields().map(|(id, _, _)| id).collect();
+
+        assert_eq!(class_ids, vec![1000, 1001]);
+        assert_eq!(field_ids, vec![1000, 1001, 1002]);
+
+        // lookups by the offset IDs
+        assert_eq!(
+            remapped.get_class_by_id(1001).unwrap().get_name(),
+            "DistributedToon"
+        );
+        assert_eq!(
+            remapped
+                .get_class_by_name("DistributedNode")
+                .unwrap()
+                .get_dclass_id(),
+            1000
+        );
+        assert!(remapped.get_class_by_id(0).is_none());
+
+        let (dclass, field) = remapped.get_field_by_id(1002).unwrap();
+        assert_eq!(dclass.get_name(), "DistributedToon");
+        assert_eq!(field.get_base_field().get_field_name(), "setName");
+        assert!(remapped.get_field_by_id(2).is_none());
+
+        // the original is left as it was
+        assert_eq!(dcf.get_class_by_id(0).unwrap().get_name(), "DistributedNode");
+        assert_ne!(remapped.get_legacy_hash(), dcf.get_legacy_hash());
+
+        // offsets add up, and must keep every ID in 16 bits
+        assert_eq!(
+            remapped.with_id_offset(5).unwrap().get_dclass(0).get_dclass_id(),
+            1005
+        );
+        assert!(dcf.with_id_offset(u16::MAX - 1).is_err());
+    }
+
+    #[test]
+    fn remapped_fields_and_parents() {
+        let owner: DCFile = read_dc(DCFileConfig::default(), String::default()).unwrap();
+        let base: DClass = new_dclass(&owner, "Owner", vec![], vec![]);
+
+        let new_atomic = |name: &str, id: globals::FieldId| {
+            let mut atomic: DCAtomicField = DCAtomicField::new(name, FieldParent::DClass(&base));
+            atomic.set_field_id(id);
+            ClassField::Atomic(atomic)
+        };
+
+        let (set_x, set_y, set_name) = (
+            new_atomic("setX", 0),
+            new_atomic("setY", 1),
+            new_atomic("setName", 3),
+        );
+
+        let mut set_xy: DCMolecularField = DCMolecularField::new("setXY", FieldParent::DClass(&base));
+        set_xy.set_field_id(2);
+
+        for atomic in [&set_x, &set_y] {
+            let ClassField::Atomic(atomic) = atomic else {
+                unreachable!()
+            };
+            set_xy.add_atomic_field(atomic);
+        }
+        let set_xy: ClassField = ClassField::Molecular(set_xy);
+
+        let mut node: DClass = DClass::new(&owner, "DistributedNode");
+        node.add_field(&set_x);
+        node.add_field(&set_y);
+        node.add_field(&set_xy);
+
+        let mut parents: DCFile = DCFile::from_str(DCFileConfig::default(), "").unwrap();
+        parents.add_dclass(node);
+        let node: &DClass = parents.get_class_by_name("DistributedNode").unwrap();
+
+        let mut avatar: DClass = DClass::new(&owner, "DistributedAvatar");
+        avatar.add_parent(node);
+        avatar.add_field(&set_name);
+
+        let mut dcf: DCFile = DCFile::from_str(DCFileConfig::default(), "").unwrap();
+        dcf.add_dclass(node.clone());
+        dcf.add_dclass(avatar);
+
+        let remapped: DCFile = dcf.with_id_offset(100).unwrap();
+        let (node_copy, avatar_copy) = (remapped.get_dclass(0), remapped.get_dclass(1));
+
+        // every field of the copy only knows its shifted ID
+        let field_ids: Vec<globals::FieldId> = avatar_copy
+            .inherited_fields()
+            .map(|field| field.get_base_field().get_field_id())
+            .collect();
+
+        assert_eq!(field_ids, vec![100, 101, 102, 103]);
+        assert!(std::ptr::eq(
+            *avatar_copy.field_id_2_field.get(&103).unwrap(),
+            avatar_copy.get_field(0).unwrap()
+        ));
+        assert!(avatar_copy.field_id_2_field.get(&3).is_none());
+
+        // the molecular field is made of the copies of its atomic fields
+        let Some(ClassField::Molecular(xy_copy)) = node_copy.get_field(2) else {
+            panic!("Molecular field was not copied.");
+        };
+        let atomic_ids: Vec<globals::FieldId> = (0..xy_copy.get_num_atomics())
+            .map(|index| {
+                xy_copy
+                    .get_atomic_field(index)
+                    .unwrap()
+                    .get_base_field()
+                    .get_field_id()
+            })
+            .collect();
+
+        assert_eq!(atomic_ids, vec![100, 101]);
+
+        // the parent is the copy of the parent, with the shifted ID
+        let parent: &DClass = avatar_copy.get_parent(0).unwrap();
+
+        assert_eq!(parent.get_dclass_id(), 100);
+        assert!(std::ptr::eq(
+            parent.get_field(0).unwrap(),
+            node_copy.get_field(0).unwrap()
+        ));
+
+        // the original is left as it was
+        let avatar: &DClass = dcf.get_dclass(1);
+
+        assert_eq!(avatar.get_field(0).unwrap().get_base_field().get_field_id(), 3);
+        assert_eq!(avatar.get_parent(0).unwrap().get_dclass_id(), 0);
+
+        // a class whose parent is not in the file has no parent to copy
+        let mut orphan: DClass = DClass::new(&owner, "DistributedOrphan");
+        orphan.add_parent(node);
+
+        let mut orphans: DCFile = DCFile::from_str(DCFileConfig::default(), "").unwrap();
+        orphans.add_dclass(orphan);
+
+        assert_eq!(
+            orphans.with_id_offset(1).unwrap_err(),
+            "Parent `DistributedNode` of `DistributedOrphan` is not a class of the DC file."
+        );
+    }
+
+    #[test]
+    fn owner_view_other_fields() {
+        let dcf: DCFile = read_dc(DCFileConfig::default(), String::default()).unwrap();
+
+        let fields: Vec<ClassField> = owner_with_fields(
+            &dcf,
+            &[
+                ("setName", 0, &["required", "broadcast", "ram"]),
+                ("setHp", 1, &["broadcast", "ram"]),
+                ("setMoney", 2, &["ownrecv", "db"]),
+                ("setSecret", 3, &["ram", "db"]),
+            ],
+        )
+        .into_iter()
+        .map(ClassField::Field)
+        .collect();
+        let mut dclass: DClass = new_dclass(&dcf, "DistributedToon", vec![], fields.iter().collect());
+        dclass.rebuild_inherited_fields();
+
+        // setMoney is set after the object was created
+        let values: HashMap<globals::FieldId, Vec<u8>> = HashMap::from([
+            (0, vec![0x01, 0x00, b'A']),
+            (2, vec![0xe8, 0x03, 0x00, 0x00]),
+            (3, vec![0x2a]),
+        ]);
+
+        // the owner gets the current value of its ownrecv field
+        let packed: Datagram = dclass.pack_other_fields(&values, true).unwrap();
+        assert_eq!(packed.get_data(), vec![1, 0, 2, 0, 0xe8, 0x03, 0x00, 0x00]);
+
+        // an interest view does not
+        let packed: Datagram = dclass.pack_other_fields(&values, false).unwrap();
+        assert_eq!(packed.get_data(), vec![0, 0]);
+
+        // set fields are included, in declaration order
+        let values: HashMap<globals::FieldId, Vec<u8>> = HashMap::from([(2, vec![0; 4]), (1, vec![9, 0])]);
+
+        let packed: Datagram = dclass.pack_other_fields(&values, true).unwrap();
+        assert_eq!(packed.get_data(), vec![2, 0, 1, 0, 9, 0, 2, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn required_field_without_default() {
+        let dcf: DCFile = read_dc(DCFileConfig::default(), String::default()).unwrap();
+
+        let [mut set_score, mut set_level, mut set_x]: [DCField; 3] = owner_with_fields(
+            &dcf,
+            &[
+                ("setScore", 0, &["required"]),
+                ("setLevel", 1, &["required"]),
+                ("setX", 2, &[]),
+            ],
+        )
+        .try_into()
+        .unwrap();
+
+        // setScore(int32) required;
+        set_score.set_field_type(DCTypeEnum::TInt32.into());
+
+        // setLevel(uint8 = 1) required;
+        set_level.set_field_type(DCTypeEnum::TUInt8.into());
+        set_level.set_default_value(vec![1]);
+
+        // setX(int16), which is not required
+        set_x.set_field_type(DCTypeEnum::TInt16.into());
+
+        let fields = (
+            ClassField::Field(set_score),
+            ClassField::Field(set_level),
+            ClassField::Field(set_x),
+        );
+        let mut dclass: DClass = new_dclass(
+            &dcf,
+            "DistributedToon",
+            vec![],
+            vec![&fields.0, &fields.1, &fields.2],
+        );
+        dclass.rebuild_inherited_fields();
+
+        let none: HashMap<globals::FieldId, Vec<u8>> = HashMap::new();
+
+        let err: String = dclass
+            .pack_required_fields(&none, MissingDefault::Reject)
+            .unwrap_err();
+        assert!(err.contains("`setScore`"), "{}", err);
+
+        let packed: Datagram = dclass.pack_required_fields(&none, MissingDefault::Zero).unwrap();
+        assert_eq!(packed.get_data(), vec![0, 0, 0, 0, 1]);
+
+        // a given value is always used, with either policy
+        let given: HashMap<globals::FieldId, Vec<u8>> = HashMap::from([(0, vec![7, 0, 0, 0])]);
+
+        for policy in [MissingDefault::Reject, MissingDefault::Zero] {
+            let packed: Datagram = dclass.pack_required_fields(&given, policy).unwrap();
+            assert_eq!(packed.get_data(), vec![7, 0, 0, 0, 1]);
+        }
+    }
+
+    #[test]
+    fn listing_ids_match_computed_ids() {
+        let dcf: DCFile = read_dc(DCFileConfig::default(), String::default()).unwrap();
+
+        let [mut set_name, mut set_x]: [DCField; 2] = owner_with_fields(
+            &dcf,
+            &[("setName", 0, &["required", "broadcast"]), ("setX", 1, &[])],
+        )
+        .try_into()
+        .unwrap();
+
+        set_name.set_field_type(DCTypeEnum::TVarString.into());
+        set_x.set_field_type(DCTypeEnum::TInt16.into());
+
+        let (set_name, set_x) = (ClassField::Field(set_name), ClassField::Field(set_x));
+
+        let mut listed: DCFile = read_dc(DCFileConfig::default(), String::default()).unwrap();
+        listed.add_dclass(new_dclass(&dcf, "DistributedAvatar", vec![], vec![&set_name]));
+        listed.add_dclass(new_dclass(&dcf, "DistributedNode", vec![], vec![&set_x]));
+
+        let listing: DCListing = listed.listing();
+
+        for listed_class in &listing.dclasses {
+            let dclass: &DClass = listed.get_class_by_name(&listed_class.name).unwrap();
+            assert_eq!(listed_class.id, dclass.get_dclass_id());
+
+            for (i, listed_field) in listed_class.fields.iter().enumerate() {
+                let field: &DCField = dclass.get_field(i).unwrap().get_base_field();
+
+                assert_eq!(listed_field.name, field.get_field_name());
+                assert_eq!(listed_field.id, field.get_field_id());
+            }
+        }
+
+        assert_eq!(
+            listing.to_string(),
+            format!(
+                "hash\t{}\n\
+                dclass\t0\tDistributedAvatar\n\
+                field\t0\t0\tfield\tsetName\tvar string\trequired,broadcast\n\
+                dclass\t1\tDistributedNode\n\
+                field\t1\t1\tfield\tsetX\tint16\t\n",
+                listing.hash
+            )
+        );
+        assert!(listing.to_json().ends_with(
+            "{\"id\":1,\"name\":\"DistributedNode\",\"fields\":[\
+            {\"id\":1,\"kind\":\"field\",\"name\":\"setX\",\"type\":\"int16\",\"keywords\":[]}]}]}"
+        ));
+    }
 }
 
 /// Contains intermediate DClass structure and logic
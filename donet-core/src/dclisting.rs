@@ -0,0 +1,241 @@
+/*
+    This file is part of Donet.
+
+    Copyright © 2024 Max Rodriguez <me@maxrdz.com>
+
+    Donet is free software; you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License,
+    as published by the Free Software Foundation, either version 3
+    of the License, or (at your option) any later version.
+
+    Donet is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public
+    License along with Donet. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Machine-parseable listing of the elements of a DC file and
+//! the IDs assigned to them, as printed by `donetd --list-dc`.
+
+use crate::dcaccessor::FieldAccessor;
+use crate::dcfield::ClassField;
+use crate::dckeyword::DCKeywordList;
+use crate::dclass::DClass;
+use crate::dctype::DCTypeDefinition;
+use crate::globals;
+
+/// Listing of a DC file, returned by [`crate::dcfile::DCFile::listing`].
+///
+/// Displayed as one element per line, with tab separated columns:
+///
+/// ```txt
+/// hash     <legacy hash>
+/// keyword  <name>
+/// typedef  <name>  <type>
+/// dclass   <class id>  <name>
+/// field    <class id>  <field id>  <kind>  <name>  <type>  <keywords>
+/// ```
+///
+/// Keywords of a field are separated by commas. Fields are listed
+/// in declaration order under the class that declares them, so
+/// inherited fields are only listed under the parent class.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DCListing {
+    pub hash: globals::DCFileHash,
+    pub keywords: Vec<String>,
+    pub typedefs: Vec<ListedTypedef>,
+    pub dclasses: Vec<ListedClass>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListedTypedef {
+    pub name: String,
+    pub dc_type: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListedClass {
+    pub id: globals::DClassId,
+    pub name: String,
+    pub fields: Vec<ListedField>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListedField {
+    pub id: globals::FieldId,
+    /// One of `field`, `atomic`, or `molecular`.
+    pub kind: &'static str,
+    pub name: String,
+    /// The field's type, or the types of its parameters in
+    /// parentheses, such as `(int32,string)`. `-` if untyped.
+    pub dc_type: String,
+    pub keywords: Vec<String>,
+}
+
+impl From<&DCTypeDefinition> for ListedTypedef {
+    fn from(value: &DCTypeDefinition) -> Self {
+        Self {
+            name: value.get_alias().unwrap_or_default(),
+            dc_type: value.get_dc_type().to_string(),
+        }
+    }
+}
+
+impl From<&DClass<'_>> for ListedClass {
+    fn from(value: &DClass<'_>) -> Self {
+        Self {
+            id: value.get_dclass_id(),
+            name: value.get_name(),
+            fields: (0..value.get_num_fields())
+                .filter_map(|i| value.get_field(i))
+                .map(ListedField::from)
+                .collect(),
+        }
+    }
+}
+
+impl<'dc> From<&'dc ClassField<'dc>> for ListedField {
+    fn from(value: &'dc ClassField<'dc>) -> Self {
+        let kind: &'static str = match value {
+            ClassField::Field(_) => "field",
+            ClassField::Atomic(_) => "atomic",
+            ClassField::Molecular(_) => "molecular",
+        };
+
+        let dc_type: String = match FieldAccessor::from(value).parameter_types() {
+            Ok(types) if matches!(value, ClassField::Field(_)) => types[0].get_dc_type().to_string(),
+            Ok(types) => {
+                let types: Vec<String> = types.iter().map(|t| t.get_dc_type().to_string()).collect();
+                format!("({})", types.join(","))
+            }
+            Err(_) => "-".to_owned(),
+        };
+
+        let base = value.get_base_field();
+
+        Self {
+            id: base.get_field_id(),
+            kind,
+            name: base.get_field_name(),
+            dc_type,
+            keywords: keyword_names(base.get_keyword_list()),
+        }
+    }
+}
+
+fn keyword_names(list: &DCKeywordList<'_>) -> Vec<String> {
+    (0..list.get_num_keywords())
+        .filter_map(|i| list.get_keyword(i))
+        .map(|kw| kw.get_name())
+        .collect()
+}
+
+impl std::fmt::Display for DCListing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "hash\t{}", self.hash)?;
+
+        for keyword in &self.keywords {
+            writeln!(f, "keyword\t{}", keyword)?;
+        }
+        for typedef in &self.typedefs {
+            writeln!(f, "typedef\t{}\t{}", typedef.name, typedef.dc_type)?;
+        }
+        for dclass in &self.dclasses {
+            writeln!(f, "dclass\t{}\t{}", dclass.id, dclass.name)?;
+
+            for field in &dclass.fields {
+                writeln!(
+                    f,
+                    "field\t{}\t{}\t{}\t{}\t{}\t{}",
+                    dclass.id,
+                    field.id,
+                    field.kind,
+                    field.name,
+                    field.dc_type,
+                    field.keywords.join(",")
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl DCListing {
+    /// Formats this listing as a single JSON object, with the
+    /// same elements, in the same order, as the text listing.
+    pub fn to_json(&self) -> String {
+        let typedefs: Vec<String> = self
+            .typedefs
+            .iter()
+            .map(|t| {
+                format!(
+                    "{{\"name\":{},\"type\":{}}}",
+                    json_string(&t.name),
+                    json_string(&t.dc_type)
+                )
+            })
+            .collect();
+
+        let dclasses: Vec<String> = self
+            .dclasses
+            .iter()
+            .map(|dclass| {
+                let fields: Vec<String> = dclass
+                    .fields
+                    .iter()
+                    .map(|field| {
+                        format!(
+                            "{{\"id\":{},\"kind\":{},\"name\":{},\"type\":{},\"keywords\":{}}}",
+                            field.id,
+                            json_string(field.kind),
+                            json_string(&field.name),
+                            json_string(&field.dc_type),
+                            json_strings(&field.keywords)
+                        )
+                    })
+                    .collect();
+
+                format!(
+                    "{{\"id\":{},\"name\":{},\"fields\":[{}]}}",
+                    dclass.id,
+                    json_string(&dclass.name),
+                    fields.join(",")
+                )
+            })
+            .collect();
+
+        format!(
+            "{{\"hash\":{},\"keywords\":{},\"typedefs\":[{}],\"dclasses\":[{}]}}",
+            self.hash,
+            json_strings(&self.keywords),
+            typedefs.join(","),
+            dclasses.join(",")
+        )
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut escaped: String = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+fn json_strings(values: &[String]) -> String {
+    let values: Vec<String> = values.iter().map(|v| json_string(v)).collect();
+    format!("[{}]", values.join(","))
+}
@@ -20,13 +20,17 @@
 //! Data model for a DC Molecular field, which represents
 //! a form of a field 'alias' for a collection of fields.
 
+use crate::datagram::datagram::Datagram;
+use crate::datagram::iterator::DatagramIterator;
+use crate::dcaccessor::{self, FieldValue};
 use crate::dcatomic::DCAtomicField;
-use crate::dcfield::DCField;
+use crate::dcfield::{DCField, FieldParent};
+use crate::globals;
 use crate::hashgen::*;
 
 /// An abstract field which provides an interface to access
 /// multiple atomic fields under one field and one identifier.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct DCMolecularField<'dc> {
     base_field: DCField<'dc>,
     atomic_fields: Vec<&'dc DCAtomicField<'dc>>,
@@ -51,6 +55,19 @@ impl LegacyDCHash for DCMolecularField<'_> {
 }
 
 impl<'dc> DCMolecularField<'dc> {
+    /// Creates a new molecular field with no atomic fields.
+    pub fn new(name: &str, parent: FieldParent<'dc>) -> Self {
+        Self {
+            base_field: DCField::new(name, parent),
+            atomic_fields: vec![],
+        }
+    }
+
+    #[inline(always)]
+    pub fn get_base_field(&self) -> &DCField<'dc> {
+        &self.base_field
+    }
+
     #[inline(always)]
     pub fn get_num_atomics(&self) -> usize {
         self.atomic_fields.len()
@@ -60,4 +77,134 @@ impl<'dc> DCMolecularField<'dc> {
     pub fn get_atomic_field(&self, index: usize) -> Option<&'dc DCAtomicField> {
         self.atomic_fields.get(index).copied()
     }
+
+    /// Appends an atomic field to the fields that this molecular
+    /// field is an alias for.
+    pub fn add_atomic_field(&mut self, atomic: &'dc DCAtomicField<'dc>) {
+        self.atomic_fields.push(atomic);
+    }
+
+    /// Replaces each atomic field of this molecular field with the
+    /// one returned by `relink`, such as a copy of the atomic field.
+    pub(crate) fn relink_atomic_fields(
+        &mut self,
+        relink: impl Fn(&'dc DCAtomicField<'dc>) -> &'dc DCAtomicField<'dc>,
+    ) {
+        for atomic in &mut self.atomic_fields {
+            *atomic = relink(atomic);
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn set_field_id(&mut self, id: globals::FieldId) {
+        self.base_field.set_field_id(id)
+    }
+
+    /// Unpacks a payload of this molecular field, splitting it across
+    /// its atomic fields in order, into the values of each atomic
+    /// field's parameters.
+    ///
+    /// Returns an error if the payload is too short to hold every
+    /// atomic field, or if any bytes are left over.
+    pub fn unpack(&self, data: &[u8]) -> Result<Vec<(&'dc DCAtomicField<'dc>, Vec<FieldValue>)>, String> {
+        let mut dg: Datagram = Datagram::default();
+        dg.add_data(data.to_vec()).map_err(|err| err.to_string())?;
+
+        let mut dgi: DatagramIterator = dg.into();
+        let mut components: Vec<(&'dc DCAtomicField<'dc>, Vec<FieldValue>)> = vec![];
+
+        for atomic in &self.atomic_fields {
+            let mut values: Vec<FieldValue> = vec![];
+
+            for param in (0..atomic.get_num_elements()).filter_map(|i| atomic.get_element(i)) {
+                let value = dcaccessor::unpack_value(&mut dgi, param.get_type()).map_err(|err| {
+                    format!(
+                        "Failed to unpack `{}` of molecular field `{}`: {}",
+                        atomic.get_base_field().get_field_name(),
+                        self.base_field.get_field_name(),
+                        err
+                    )
+                })?;
+                values.push(value);
+            }
+            components.push((atomic, values));
+        }
+
+        if dgi.get_remaining() != 0 {
+            return Err(format!(
+                "{} bytes left over after unpacking molecular field `{}`.",
+                dgi.get_remaining(),
+                self.base_field.get_field_name()
+            ));
+        }
+        Ok(components)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dcaccessor::FieldAccessor;
+    use crate::dcfield::ClassField;
+    use crate::dcfile::DCFile;
+    use crate::dclass::DClass;
+    use crate::dconfig::DCFileConfig;
+    use crate::dcparameter::{DCParameter, DCParameterBuilder};
+    use crate::dctype::{DCNumber, DCTypeEnum};
+    use crate::read_dc;
+
+    #[test]
+    fn unpack_into_components() {
+        let dcf: DCFile = read_dc(DCFileConfig::default(), String::default()).unwrap();
+        let dclass: DClass = DClass::new(&dcf, "DistributedAvatar");
+        let parent = || FieldParent::DClass(&dclass);
+
+        // parameters only refer back to their atomic field, which
+        // does not need to be the field they are added to here.
+        let owner: DCAtomicField = DCAtomicField::new("owner", parent());
+        let param = |dtype: DCTypeEnum| -> DCParameter {
+            DCParameterBuilder::new(&owner, dtype.into()).build().unwrap()
+        };
+        let (x, y, name) = (
+            param(DCTypeEnum::TInt16),
+            param(DCTypeEnum::TInt16),
+            param(DCTypeEnum::TVarString),
+        );
+
+        let mut set_xy: DCAtomicField = DCAtomicField::new("setXY", parent());
+        set_xy.add_element(&x);
+        set_xy.add_element(&y);
+
+        let mut set_name: DCAtomicField = DCAtomicField::new("setName", parent());
+        set_name.add_element(&name);
+
+        let mut molecular: DCMolecularField = DCMolecularField::new("setXYName", parent());
+        molecular.add_atomic_field(&set_xy);
+        molecular.add_atomic_field(&set_name);
+
+        let xy: Vec<FieldValue> = vec![
+            FieldValue::Number(DCNumber::Integer(-20)),
+            FieldValue::Number(DCNumber::Integer(45)),
+        ];
+        let names: Vec<FieldValue> = vec![FieldValue::String("Donut".into())];
+
+        let field = ClassField::Molecular(molecular);
+        let values: Vec<FieldValue> = [xy.clone(), names.clone()].concat();
+        let packed: Vec<u8> = FieldAccessor::from(&field).pack(&values).unwrap().get_data();
+
+        let ClassField::Molecular(molecular) = &field else {
+            unreachable!()
+        };
+        let components = molecular.unpack(&packed).unwrap();
+
+        assert_eq!(components.len(), 2);
+        assert!(std::ptr::eq(components[0].0, &set_xy));
+        assert_eq!(components[0].1, xy);
+        assert!(std::ptr::eq(components[1].0, &set_name));
+        assert_eq!(components[1].1, names);
+
+        // too short for the last component, and one byte too long
+        assert!(molecular.unpack(&packed[..packed.len() - 1]).is_err());
+        assert!(molecular.unpack(&[packed, vec![0]].concat()).is_err());
+    }
 }
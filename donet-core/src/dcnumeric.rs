@@ -29,7 +29,7 @@ use std::mem::{discriminant, size_of};
 /// Numeric Range structs are used to represent a range of signed/unsigned
 /// integers or floating point numbers. Used for enforcing numeric limits
 /// within constraints of array, string, or blob sized types.
-#[derive(Clone)]
+#[derive(Clone, PartialEq)]
 pub struct DCNumericRange {
     pub min: DCNumber,
     pub max: DCNumber,
@@ -65,7 +65,7 @@ impl From<std::ops::Range<f64>> for DCNumericRange {
 impl DCNumericRange {
     pub fn contains(&self, num: DCNumber) -> bool {
         // Check that `num` is of the same data type as this numeric range.
-        if discriminant(&self.min) == discriminant(&num) {
+        if discriminant(&self.min) != discriminant(&num) {
             return false;
         }
 
@@ -113,6 +113,46 @@ impl DCNumericRange {
     }
 }
 
+/// Converts any [`DCNumber`] variant to an `f64`, for applying
+/// numeric transforms regardless of the number's variant.
+fn number_to_f64(num: DCNumber) -> f64 {
+    match num {
+        DCNumber::Integer(x) => x as f64,
+        DCNumber::UnsignedInteger(x) => x as f64,
+        DCNumber::FloatingPoint(x) => x,
+    }
+}
+
+/// Returns the number of decimal places needed to print
+/// any value that has been scaled by the given divisor.
+fn decimal_places(divisor: u16) -> usize {
+    (divisor - 1)
+        .checked_ilog10()
+        .map_or(0, |digits| digits as usize + 1)
+}
+
+/// Multiplies a [`DCNumber`] by the given divisor, keeping its variant.
+fn scale_number(num: DCNumber, divisor: u16) -> Result<DCNumber, String> {
+    let overflow = || "Range overflows type when scaled by the divisor.".to_owned();
+
+    Ok(match num {
+        DCNumber::Integer(x) => DCNumber::Integer(x.checked_mul(i64::from(divisor)).ok_or_else(overflow)?),
+        DCNumber::UnsignedInteger(x) => {
+            DCNumber::UnsignedInteger(x.checked_mul(u64::from(divisor)).ok_or_else(overflow)?)
+        }
+        DCNumber::FloatingPoint(x) => DCNumber::FloatingPoint(x * f64::from(divisor)),
+    })
+}
+
+/// Formats a [`DCNumber`] as it would be written in a DC file.
+fn format_number(num: DCNumber) -> String {
+    match num {
+        DCNumber::Integer(x) => x.to_string(),
+        DCNumber::UnsignedInteger(x) => x.to_string(),
+        DCNumber::FloatingPoint(x) => x.to_string(),
+    }
+}
+
 pub struct DCNumericType {
     base_type: DCTypeDefinition,
     divisor: u16,
@@ -171,6 +211,25 @@ impl From<DCTypeEnum> for DCNumericType {
     }
 }
 
+/// Formats the numeric type as it would be written in a DC file,
+/// with its transforms as declared, unscaled by the divisor.
+impl std::fmt::Display for DCNumericType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.base_type.data_type.fmt(f)?;
+
+        if self.has_modulus() {
+            write!(f, " %{}", self.orig_modulus)?;
+        }
+        if self.divisor != 1 {
+            write!(f, " /{}", self.divisor)?;
+        }
+        if let Some(range) = &self.orig_range {
+            write!(f, " ({}-{})", format_number(range.min), format_number(range.max))?;
+        }
+        Ok(())
+    }
+}
+
 impl LegacyDCHash for DCNumericType {
     fn generate_hash(&self, hashgen: &mut DCHashGenerator) {
         self.base_type.generate_hash(hashgen);
@@ -213,11 +272,27 @@ impl DCNumericType {
         self.orig_range.clone()
     }
 
+    #[inline]
+    pub fn get_base_type(&self) -> &DCTypeDefinition {
+        &self.base_type
+    }
+
     #[inline]
     pub fn get_explicit_cast(&self) -> Option<DCTypeDefinition> {
         self.explicit_cast.clone()
     }
 
+    /// Returns true if both numeric types are of the same primitive
+    /// type, with the same divisor, modulus, range, and explicit cast.
+    /// The aliases of the types are not compared.
+    pub fn same_type_as(&self, other: &DCNumericType) -> bool {
+        self.base_type.same_type_as(&other.base_type)
+            && self.divisor == other.divisor
+            && self.orig_modulus == other.orig_modulus
+            && self.orig_range == other.orig_range
+            && same_optional_type(self.explicit_cast.as_ref(), other.explicit_cast.as_ref())
+    }
+
     pub fn set_divisor(&mut self, divisor: u16) -> Result<(), String> {
         if divisor == 0 {
             return Err("Cannot set the divisor to 0.".into());
@@ -244,8 +319,25 @@ impl DCNumericType {
         Ok(()) // TODO: properly validate modulus range
     }
 
+    /// Sets the range of this numeric type.
+    ///
+    /// A float32 cannot hold values past [`f32::MAX`], so a range
+    /// of a float32 must be within the range of an [`f32`].
     pub fn set_range(&mut self, range: DCNumericRange) -> Result<(), String> {
-        self.range = Some(range); // TODO: validate
+        if self.base_type.data_type == DCTypeEnum::TFloat32 {
+            let max: f64 = f64::from(f32::MAX);
+
+            for bound in [range.min, range.max] {
+                if number_to_f64(bound).abs() > max {
+                    return Err("Range is out of bounds of float32.".into());
+                }
+            }
+        }
+        self.range = Some(DCNumericRange {
+            min: scale_number(range.min, self.divisor)?,
+            max: scale_number(range.max, self.divisor)?,
+        });
+        self.orig_range = Some(range);
         Ok(())
     }
 
@@ -254,8 +346,197 @@ impl DCNumericType {
         Ok(()) // TODO: do some sort of type check
     }
 
-    pub fn within_range(&self, _data: Vec<u8>, _length: u64) -> Result<(), String> {
-        todo!();
+    /// Checks that a packed value of this numeric type, such as one
+    /// in a field update sent by a client, is within its range.
+    ///
+    /// The value is the first `length` bytes of `data`, which must be
+    /// the size of the base type. The packed value is compared to the
+    /// range scaled by the divisor, like [`Self::pack_value`] does.
+    pub fn within_range(&self, data: Vec<u8>, length: u64) -> Result<(), String> {
+        let length: usize = usize::try_from(length).map_err(|_| "Length overflows usize.".to_owned())?;
+
+        if length > data.len() {
+            return Err(format!(
+                "Expected {} bytes of data, but only {} were given.",
+                length,
+                data.len()
+            ));
+        }
+
+        let (valid, raw) = self
+            .data_to_number(data[..length].to_vec())
+            .map_err(|err| err.to_string())?;
+
+        if !valid {
+            return Err(format!(
+                "{} value must be {} bytes long, but is {} bytes long.",
+                self.base_type.data_type, self.base_type.size, length
+            ));
+        }
+
+        if let Some(range) = &self.range {
+            let bounds = number_to_f64(range.min)..=number_to_f64(range.max);
+
+            // NaN is never within a range
+            if !bounds.contains(&number_to_f64(raw)) {
+                return Err("Value is out of range.".into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Packs the given value into the datagram as this numeric type.
+    ///
+    /// The value is scaled by the divisor, wrapped by the modulus,
+    /// and checked against the range, in that order, before it is
+    /// written as the base type.
+    pub fn pack_value(&self, dg: &mut Datagram, value: DCNumber) -> Result<(), String> {
+        let divisor: f64 = f64::from(self.divisor);
+        let overflow = || "Value overflows type when scaled by the divisor.".to_owned();
+
+        // Integers without a modulus are scaled with integer
+        // arithmetic and packed exactly, for precision.
+        let exact: Option<i128> = match value {
+            _ if self.has_modulus() => None,
+            DCNumber::Integer(x) => Some(i128::from(
+                x.checked_mul(i64::from(self.divisor)).ok_or_else(overflow)?,
+            )),
+            DCNumber::UnsignedInteger(x) => Some(i128::from(
+                x.checked_mul(u64::from(self.divisor)).ok_or_else(overflow)?,
+            )),
+            DCNumber::FloatingPoint(_) => None,
+        };
+
+        let mut scaled: f64 = number_to_f64(value) * divisor;
+
+        if !scaled.is_finite() {
+            return Err(overflow());
+        }
+
+        if self.has_modulus() {
+            scaled = scaled.rem_euclid(self.modulus);
+        }
+
+        if let Some(range) = &self.range {
+            let min: f64 = number_to_f64(range.min);
+            let max: f64 = number_to_f64(range.max);
+
+            if scaled < min || scaled > max {
+                return Err("Value is out of range.".into());
+            }
+        }
+
+        macro_rules! add_int {
+            ($t:ty, $add:ident) => {{
+                let int: i128 = exact.unwrap_or(scaled.round() as i128);
+                let v: $t = <$t>::try_from(int).map_err(|_| "Value overflows type.".to_owned())?;
+                dg.$add(v)
+            }};
+        }
+
+        let res: Result<(), DatagramError> = match self.base_type.data_type {
+            DCTypeEnum::TInt8 => add_int!(i8, add_i8),
+            DCTypeEnum::TInt16 => add_int!(i16, add_i16),
+            DCTypeEnum::TInt32 => add_int!(i32, add_i32),
+            DCTypeEnum::TInt64 => add_int!(i64, add_i64),
+            DCTypeEnum::TChar | DCTypeEnum::TUInt8 => add_int!(u8, add_u8),
+            DCTypeEnum::TUInt16 => add_int!(u16, add_u16),
+            DCTypeEnum::TUInt32 => add_int!(u32, add_u32),
+            DCTypeEnum::TUInt64 => add_int!(u64, add_u64),
+            DCTypeEnum::TFloat32 => {
+                if scaled.abs() > f64::from(f32::MAX) {
+                    return Err("Value overflows type.".into());
+                }
+                dg.add_f32(scaled as f32)
+            }
+            DCTypeEnum::TFloat64 => dg.add_f64(scaled),
+            _ => return Err("Invalid data type!".into()),
+        };
+        res.map_err(|err| err.to_string())
+    }
+
+    /// Formats a value of this numeric type as packed, scaled by the
+    /// divisor, as its human value. The number of decimal places is
+    /// chosen from the divisor, so `1234` with a divisor of 1000 is
+    /// formatted as `1.234`, and a divisor of 1 formats an integer.
+    pub fn format_value(&self, raw: DCNumber) -> String {
+        let places: usize = decimal_places(self.divisor);
+
+        let int: i128 = match raw {
+            _ if self.divisor == 1 => return format_number(raw),
+            DCNumber::Integer(x) => i128::from(x),
+            DCNumber::UnsignedInteger(x) => i128::from(x),
+            DCNumber::FloatingPoint(x) => {
+                return format!("{:.*}", places, x / f64::from(self.divisor));
+            }
+        };
+
+        // Divisors that are a power of 10 can be formatted exactly.
+        if 10_i128.pow(places as u32) == i128::from(self.divisor) {
+            let sign: &str = if int < 0 { "-" } else { "" };
+            let divisor: u128 = u128::from(self.divisor);
+
+            format!(
+                "{}{}.{:0places$}",
+                sign,
+                int.unsigned_abs() / divisor,
+                int.unsigned_abs() % divisor,
+            )
+        } else {
+            format!("{:.*}", places, int as f64 / f64::from(self.divisor))
+        }
+    }
+
+    /// Unpacks a value of this numeric type from the datagram,
+    /// and formats it as its human value with [`Self::format_value`].
+    pub fn unpack_string(&self, dgi: &mut DatagramIterator) -> Result<String, IteratorError> {
+        Ok(self.format_value(self.unpack_raw(dgi)?))
+    }
+
+    /// Unpacks a value of this numeric type from the datagram, as packed.
+    fn unpack_raw(&self, dgi: &mut DatagramIterator) -> Result<DCNumber, IteratorError> {
+        Ok(match self.base_type.data_type {
+            DCTypeEnum::TInt8 => DCNumber::Integer(i64::from(dgi.read_i8()?)),
+            DCTypeEnum::TInt16 => DCNumber::Integer(i64::from(dgi.read_i16()?)),
+            DCTypeEnum::TInt32 => DCNumber::Integer(i64::from(dgi.read_i32()?)),
+            DCTypeEnum::TInt64 => DCNumber::Integer(dgi.read_i64()?),
+            DCTypeEnum::TChar | DCTypeEnum::TUInt8 => DCNumber::UnsignedInteger(u64::from(dgi.read_u8()?)),
+            DCTypeEnum::TUInt16 => DCNumber::UnsignedInteger(u64::from(dgi.read_u16()?)),
+            DCTypeEnum::TUInt32 => DCNumber::UnsignedInteger(u64::from(dgi.read_u32()?)),
+            DCTypeEnum::TUInt64 => DCNumber::UnsignedInteger(dgi.read_u64()?),
+            DCTypeEnum::TFloat32 => DCNumber::FloatingPoint(f64::from(dgi.read_f32()?)),
+            DCTypeEnum::TFloat64 => DCNumber::FloatingPoint(dgi.read_f64()?),
+            _ => return Err(IteratorError::InvalidRead("Not a numeric type.")),
+        })
+    }
+
+    /// Unpacks a value of this numeric type from the datagram.
+    ///
+    /// If this type has a divisor, the packed value is divided by
+    /// it, and returned as a floating point number.
+    pub fn unpack_value(&self, dgi: &mut DatagramIterator) -> Result<DCNumber, IteratorError> {
+        let raw: DCNumber = self.unpack_raw(dgi)?;
+
+        if self.divisor == 1 {
+            return Ok(raw);
+        }
+
+        // Integers past 2^53 cannot be converted to floating
+        // point for the division without losing precision.
+        let lossless: bool = match raw {
+            DCNumber::Integer(x) => (x as f64) as i128 == i128::from(x),
+            DCNumber::UnsignedInteger(x) => (x as f64) as i128 == i128::from(x),
+            DCNumber::FloatingPoint(x) => x.is_finite(),
+        };
+
+        if !lossless {
+            return Err(IteratorError::InvalidRead(
+                "Value overflows the precision of its divisor.",
+            ));
+        }
+        Ok(DCNumber::FloatingPoint(
+            number_to_f64(raw) / f64::from(self.divisor),
+        ))
     }
 
     fn data_to_number(&self, data: Vec<u8>) -> Result<(bool, DCNumber), IteratorError> {
@@ -285,3 +566,364 @@ impl DCNumericType {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn int64_with_divisor(divisor: u16) -> DCNumericType {
+        let mut numeric: DCNumericType = DCNumericType::from(DCTypeEnum::TInt64);
+        numeric.set_divisor(divisor).unwrap();
+        numeric
+    }
+
+    #[test]
+    fn pack_overflows_at_i64_boundaries() {
+        let numeric: DCNumericType = int64_with_divisor(10);
+        let mut dg: Datagram = Datagram::default();
+
+        assert!(numeric
+            .pack_value(&mut dg, DCNumber::Integer(i64::MAX / 10))
+            .is_ok());
+        assert!(numeric
+            .pack_value(&mut dg, DCNumber::Integer(i64::MIN / 10))
+            .is_ok());
+
+        assert!(numeric.pack_value(&mut dg, DCNumber::Integer(i64::MAX)).is_err());
+        assert!(numeric.pack_value(&mut dg, DCNumber::Integer(i64::MIN)).is_err());
+        assert!(numeric
+            .pack_value(&mut dg, DCNumber::FloatingPoint(f64::MAX))
+            .is_err());
+
+        let mut dgi: DatagramIterator = dg.into();
+
+        assert_eq!(dgi.read_i64().unwrap(), i64::MAX / 10 * 10);
+        assert_eq!(dgi.read_i64().unwrap(), i64::MIN / 10 * 10);
+        assert_eq!(dgi.get_remaining(), 0);
+    }
+
+    #[test]
+    fn unpack_overflows_divisor_precision() {
+        let numeric: DCNumericType = int64_with_divisor(10);
+
+        let mut dg: Datagram = Datagram::default();
+        dg.add_i64(125).unwrap();
+        dg.add_i64(i64::MAX).unwrap();
+
+        let mut dgi: DatagramIterator = dg.into();
+
+        match numeric.unpack_value(&mut dgi).unwrap() {
+            DCNumber::FloatingPoint(x) => assert_eq!(x, 12.5),
+            _ => panic!("Expected a floating point number."),
+        }
+        assert!(numeric.unpack_value(&mut dgi).is_err());
+    }
+
+    #[test]
+    fn divisor_of_zero() {
+        let mut numeric: DCNumericType = DCNumericType::from(DCTypeEnum::TInt64);
+
+        assert!(numeric.set_divisor(0).is_err());
+    }
+
+    #[test]
+    fn format_divisor_scaled_values() {
+        let mut numeric: DCNumericType = DCNumericType::from(DCTypeEnum::TInt32);
+        numeric.set_divisor(1000).unwrap();
+
+        assert_eq!(numeric.format_value(DCNumber::Integer(1234)), "1.234");
+        assert_eq!(numeric.format_value(DCNumber::Integer(-1234)), "-1.234");
+        assert_eq!(numeric.format_value(DCNumber::Integer(-5)), "-0.005");
+        assert_eq!(numeric.format_value(DCNumber::Integer(2000)), "2.000");
+
+        // the human value packs back into the same scaled integer
+        let mut dg: Datagram = Datagram::default();
+        let human: f64 = numeric.format_value(DCNumber::Integer(1234)).parse().unwrap();
+
+        numeric
+            .pack_value(&mut dg, DCNumber::FloatingPoint(human))
+            .unwrap();
+
+        let mut dgi: DatagramIterator = dg.clone().into();
+        assert_eq!(dgi.read_i32().unwrap(), 1234);
+
+        let mut dgi: DatagramIterator = dg.into();
+        assert_eq!(numeric.unpack_string(&mut dgi).unwrap(), "1.234");
+
+        let integer: DCNumericType = DCNumericType::from(DCTypeEnum::TUInt16);
+        assert_eq!(integer.format_value(DCNumber::UnsignedInteger(1234)), "1234");
+
+        let mut thirds: DCNumericType = DCNumericType::from(DCTypeEnum::TInt8);
+        thirds.set_divisor(3).unwrap();
+        assert_eq!(thirds.format_value(DCNumber::Integer(-4)), "-1.3");
+    }
+
+    #[test]
+    fn packed_values_within_range() {
+        // uint8 /10 (0-12.5), such as a client's speed
+        let mut speed: DCNumericType = DCNumericType::from(DCTypeEnum::TUInt8);
+        speed.set_divisor(10).unwrap();
+        speed.set_range(DCNumericRange::from(0.0..12.5)).unwrap();
+
+        assert_eq!(speed.within_range(vec![0], 1), Ok(()));
+        assert_eq!(speed.within_range(vec![125], 1), Ok(()));
+        assert_eq!(
+            speed.within_range(vec![126], 1),
+            Err("Value is out of range.".into())
+        );
+
+        // trailing bytes belong to the next value
+        assert_eq!(speed.within_range(vec![100, 255], 1), Ok(()));
+        assert!(speed.within_range(vec![100], 2).is_err());
+        assert!(speed.within_range(vec![100, 0], 2).is_err());
+
+        // int16 (-5-5)
+        let mut offset: DCNumericType = DCNumericType::from(DCTypeEnum::TInt16);
+        offset.set_range(DCNumericRange::from(-5_i64..5)).unwrap();
+
+        assert_eq!(offset.within_range((-5_i16).to_le_bytes().to_vec(), 2), Ok(()));
+        assert!(offset.within_range((-6_i16).to_le_bytes().to_vec(), 2).is_err());
+
+        // float64 (0-1)
+        let mut ratio: DCNumericType = DCNumericType::from(DCTypeEnum::TFloat64);
+        ratio.set_range(DCNumericRange::from(0.0..1.0)).unwrap();
+
+        assert_eq!(ratio.within_range(0.5_f64.to_le_bytes().to_vec(), 8), Ok(()));
+        assert!(ratio.within_range(f64::NAN.to_le_bytes().to_vec(), 8).is_err());
+
+        // any value is within a type without a range
+        let unbounded: DCNumericType = DCNumericType::from(DCTypeEnum::TUInt8);
+        assert_eq!(unbounded.within_range(vec![u8::MAX], 1), Ok(()));
+    }
+
+    #[test]
+    fn range_scaled_by_divisor() {
+        // uint16 /10 (0-5), hashed with its range as packed, (0-50)
+        let mut numeric: DCNumericType = DCNumericType::from(DCTypeEnum::TUInt16);
+        numeric.set_range(DCNumericRange::from(0_u64..5)).unwrap();
+        numeric.set_divisor(10).unwrap();
+
+        let mut expected: DCHashGenerator = DCHashGenerator::default();
+        numeric.base_type.generate_hash(&mut expected);
+        expected.add_int(10);
+        expected.add_int(0);
+        expected.add_int(50);
+
+        let mut hashgen: DCHashGenerator = DCHashGenerator::default();
+        numeric.generate_hash(&mut hashgen);
+
+        assert_eq!(hashgen.get_hash(), expected.get_hash());
+
+        // the range as declared is unscaled
+        assert!(numeric.get_range() == Some(DCNumericRange::from(0_u64..5)));
+
+        // the same type, with its divisor set before its range
+        let mut reordered: DCNumericType = DCNumericType::from(DCTypeEnum::TUInt16);
+        reordered.set_divisor(10).unwrap();
+        reordered.set_range(DCNumericRange::from(0_u64..5)).unwrap();
+
+        let mut hashgen: DCHashGenerator = DCHashGenerator::default();
+        reordered.generate_hash(&mut hashgen);
+
+        assert_eq!(hashgen.get_hash(), expected.get_hash());
+
+        let mut dg: Datagram = Datagram::default();
+        assert!(numeric.pack_value(&mut dg, DCNumber::UnsignedInteger(5)).is_ok());
+        assert!(numeric.pack_value(&mut dg, DCNumber::UnsignedInteger(6)).is_err());
+
+        // a range that overflows once scaled
+        let mut wide: DCNumericType = int64_with_divisor(1000);
+        assert!(wide.set_range(DCNumericRange::from(0_i64..i64::MAX)).is_err());
+    }
+
+    #[test]
+    fn write_numeric_type() {
+        let mut numeric: DCNumericType = DCNumericType::from(DCTypeEnum::TInt16);
+        assert_eq!(numeric.to_string(), "int16");
+
+        numeric.set_modulus(360.0).unwrap();
+        numeric.set_divisor(100).unwrap();
+        numeric.set_range(DCNumericRange::from(0.0..1.5)).unwrap();
+
+        assert_eq!(numeric.to_string(), "int16 %360 /100 (0-1.5)");
+    }
+
+    #[test]
+    fn same_type_ignores_alias() {
+        let mut health: DCNumericType = int64_with_divisor(10);
+        health.base_type.set_alias("health".into());
+        health.set_range(DCNumericRange::from(0_i64..100)).unwrap();
+
+        let mut score: DCNumericType = int64_with_divisor(10);
+        score.base_type.set_alias("score".into());
+        score.set_range(DCNumericRange::from(0_i64..100)).unwrap();
+
+        assert!(health.same_type_as(&score));
+
+        let mut wide: DCNumericType = int64_with_divisor(10);
+        wide.set_range(DCNumericRange::from(0_i64..1000)).unwrap();
+
+        assert!(!health.same_type_as(&wide));
+        assert!(!health.same_type_as(&int64_with_divisor(100)));
+        assert!(!int64_with_divisor(10).same_type_as(&DCNumericType::from(DCTypeEnum::TInt32)));
+    }
+
+    #[test]
+    fn integer_round_trips() {
+        use DCNumber::{Integer as I, UnsignedInteger as U};
+
+        // type, size, min, max, below min, above max
+        type Bounds = (
+            DCTypeEnum,
+            u16,
+            DCNumber,
+            DCNumber,
+            Option<DCNumber>,
+            Option<DCNumber>,
+        );
+
+        #[rustfmt::skip]
+        let types: [Bounds; 8] = [
+            (DCTypeEnum::TInt8, 1, I(i8::MIN.into()), I(i8::MAX.into()), Some(I(-129)), Some(I(128))),
+            (DCTypeEnum::TInt16, 2, I(i16::MIN.into()), I(i16::MAX.into()), Some(I(-32769)), Some(I(32768))),
+            (DCTypeEnum::TInt32, 4, I(i32::MIN.into()), I(i32::MAX.into()), Some(I(-(1 << 31) - 1)), Some(I(1 << 31))),
+            (DCTypeEnum::TInt64, 8, I(i64::MIN), I(i64::MAX), None, Some(U(1 << 63))),
+            (DCTypeEnum::TUInt8, 1, U(0), U(u8::MAX.into()), Some(I(-1)), Some(U(256))),
+            (DCTypeEnum::TUInt16, 2, U(0), U(u16::MAX.into()), Some(I(-1)), Some(U(65536))),
+            (DCTypeEnum::TUInt32, 4, U(0), U(u32::MAX.into()), Some(I(-1)), Some(U(1 << 32))),
+            (DCTypeEnum::TUInt64, 8, U(0), U(u64::MAX), Some(I(-1)), None),
+        ];
+
+        for (dtype, size, min, max, below, above) in types {
+            let numeric: DCNumericType = DCNumericType::from(dtype.clone());
+            assert_eq!(numeric.get_base_type().get_size(), size, "{}", dtype);
+
+            let mut dg: Datagram = Datagram::default();
+            numeric.pack_value(&mut dg, min).unwrap();
+            numeric.pack_value(&mut dg, max).unwrap();
+
+            // a negative value of a signed type packs as its two's complement
+            if let I(min) = min {
+                let bytes: Vec<u8> = min.to_le_bytes()[..usize::from(size)].to_vec();
+                assert_eq!(dg.get_data()[..usize::from(size)], bytes, "{}", dtype);
+            }
+
+            // each is read back with the signedness of its type
+            let mut dgi: DatagramIterator = dg.into();
+
+            assert_eq!(numeric.unpack_value(&mut dgi).unwrap(), min, "{}", dtype);
+            assert_eq!(numeric.unpack_value(&mut dgi).unwrap(), max, "{}", dtype);
+            assert_eq!(dgi.get_remaining(), 0);
+
+            for out_of_range in [below, above].into_iter().flatten() {
+                let mut dg: Datagram = Datagram::default();
+
+                assert!(numeric.pack_value(&mut dg, out_of_range).is_err(), "{}", dtype);
+                assert_eq!(dg.size(), 0);
+            }
+        }
+
+        // a default outside of the declared range, as in `int8(-5-5) = 10`
+        let mut ranged: DCNumericType = DCNumericType::from(DCTypeEnum::TInt8);
+        ranged.set_range(DCNumericRange::from(-5_i64..5)).unwrap();
+
+        let mut dg: Datagram = Datagram::default();
+        assert!(ranged.pack_value(&mut dg, I(10)).is_err());
+        assert!(ranged.pack_value(&mut dg, I(-5)).is_ok());
+    }
+
+    #[test]
+    fn integer_hashes_distinct() {
+        use crate::dcfield::{DCField, FieldParent};
+        use crate::dclass::DClass;
+        use crate::dconfig::DCFileConfig;
+        use crate::read_dc;
+
+        let dcf = read_dc(DCFileConfig::default(), String::default()).unwrap();
+        let owner: DClass = DClass::new(&dcf, "DistributedNode");
+
+        let hash_of = |dtype: DCTypeEnum| {
+            let mut field: DCField = DCField::new("setX", FieldParent::DClass(&owner));
+            field.set_field_type(DCNumericType::from(dtype).get_base_type().clone());
+
+            let mut hashgen: DCHashGenerator = DCHashGenerator::default();
+            field.generate_hash(&mut hashgen);
+            hashgen.get_hash()
+        };
+
+        let hashes: Vec<u32> = [
+            DCTypeEnum::TInt8,
+            DCTypeEnum::TInt16,
+            DCTypeEnum::TInt32,
+            DCTypeEnum::TInt64,
+            DCTypeEnum::TUInt8,
+            DCTypeEnum::TUInt16,
+            DCTypeEnum::TUInt32,
+            DCTypeEnum::TUInt64,
+        ]
+        .into_iter()
+        .map(hash_of)
+        .collect();
+
+        for (i, hash) in hashes.iter().enumerate() {
+            assert!(!hashes[..i].contains(hash), "hash {} of type {} repeats", hash, i);
+        }
+    }
+
+    #[test]
+    fn float32_round_trip() {
+        let float32: DCNumericType = DCNumericType::from(DCTypeEnum::TFloat32);
+        assert_eq!(float32.get_base_type().get_size(), 4);
+
+        let mut dg: Datagram = Datagram::default();
+        float32
+            .pack_value(&mut dg, DCNumber::FloatingPoint(-1.5))
+            .unwrap();
+
+        assert_eq!(dg.get_data(), (-1.5_f32).to_le_bytes().to_vec());
+
+        let mut dgi: DatagramIterator = dg.into();
+
+        assert_eq!(
+            float32.unpack_value(&mut dgi).unwrap(),
+            DCNumber::FloatingPoint(-1.5)
+        );
+        assert_eq!(dgi.get_remaining(), 0);
+
+        // values past f32::MAX would silently pack as infinity
+        let too_big = DCNumber::FloatingPoint(f64::from(f32::MAX) * 2.0);
+        let mut dg: Datagram = Datagram::default();
+
+        assert!(float32.pack_value(&mut dg, too_big).is_err());
+        assert!(DCNumericType::from(DCTypeEnum::TFloat64)
+            .pack_value(&mut dg, too_big)
+            .is_ok());
+
+        let mut ranged: DCNumericType = DCNumericType::from(DCTypeEnum::TFloat32);
+        assert!(ranged.set_range(DCNumericRange::from(0.0..f64::MAX)).is_err());
+        assert!(ranged.set_range(DCNumericRange::from(0.0..1.0)).is_ok());
+    }
+
+    #[test]
+    fn float32_hashes_distinct_from_float64() {
+        use crate::dcfield::{DCField, FieldParent};
+        use crate::dclass::DClass;
+        use crate::dconfig::DCFileConfig;
+        use crate::read_dc;
+
+        let dcf = read_dc(DCFileConfig::default(), String::default()).unwrap();
+        let owner: DClass = DClass::new(&dcf, "DistributedNode");
+
+        let hash_of = |dtype: DCTypeEnum| {
+            let mut field: DCField = DCField::new("setX", FieldParent::DClass(&owner));
+            field.set_field_type(DCNumericType::from(dtype).get_base_type().clone());
+
+            let mut hashgen: DCHashGenerator = DCHashGenerator::default();
+            field.generate_hash(&mut hashgen);
+            hashgen.get_hash()
+        };
+
+        assert_ne!(hash_of(DCTypeEnum::TFloat32), hash_of(DCTypeEnum::TFloat64));
+        assert_eq!(hash_of(DCTypeEnum::TFloat32), hash_of(DCTypeEnum::TFloat32));
+    }
+}
@@ -42,6 +42,9 @@ pub struct DCFileConfig {
     /// be used. This also enables shadowing (overloading) of
     /// inherited method names from a base class.
     pub dc_virtual_inheritance: bool,
+    /// Selects which implementation the legacy hash of the DC file
+    /// must match, where Panda3D and Astron compute it differently.
+    pub dc_hash_compatibility: HashCompatibility,
 }
 
 /// Creates the config struct with Panda's defaults.
@@ -51,10 +54,33 @@ impl Default for DCFileConfig {
             dc_multiple_inheritance: true,
             dc_sort_inheritance_by_file: true,
             dc_virtual_inheritance: true,
+            dc_hash_compatibility: HashCompatibility::default(),
         }
     }
 }
 
+/// The implementation of the legacy DC hash to be compatible with.
+///
+/// Both compute the same hash for most DC files, including files with
+/// structs, which both hash as classes with no parents. They differ in
+/// **keyword ordering**: if a field has a custom keyword, the names of
+/// its keywords are hashed, instead of the bitmask of historical flags.
+/// Panda3D hashes them in the order they are written on the field,
+/// while Astron hashes them sorted by name, so that
+/// `setColor() p2p unreliable` and `setColor() unreliable p2p`
+/// only have the same hash with Astron.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HashCompatibility {
+    /// Hash the way Panda3D does, which is what Donet has always done.
+    /// See `DCKeywordList::generate_hash` in Panda3D's
+    /// `direct/src/dcparser/dcKeywordList.cxx`.
+    #[default]
+    Panda3D,
+    /// Hash the way Astron does, for clients that use Astron's DC parser.
+    /// See `hash_keywords` in Astron's `src/dclass/file/hash_legacy.cpp`.
+    Astron,
+}
+
 impl std::fmt::Display for DCFileConfig {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "/*")?;
@@ -20,9 +20,11 @@
 //! Data model that represents a single parameter of an atomic
 //! field, which together form a RPC method signature.
 
+use crate::dcaccessor::{self, FieldValue};
 use crate::dcatomic::DCAtomicField;
-use crate::dctype::DCTypeDefinition;
+use crate::dctype::{DCTypeDefinition, DCTypeEnum};
 use crate::hashgen::*;
+use crate::parser::error::SemanticError;
 
 /// Represents the type specification of a parameter within an atomic field.
 #[derive(Debug)]
@@ -63,16 +65,261 @@ impl<'dc> DCParameter<'dc> {
         self.default_value.clone()
     }
 
-    pub fn set_type(&mut self, dtype: DCTypeDefinition) {
-        self.base_type = dtype;
+    #[inline(always)]
+    pub fn get_type(&self) -> &DCTypeDefinition {
+        &self.base_type
+    }
+
+    #[inline(always)]
+    pub fn get_identifier(&self) -> Option<&str> {
+        self.identifier.as_deref()
+    }
+
+    /// Returns true if both parameters are of the same type, regardless
+    /// of their identifiers. See [`DCTypeDefinition::same_type_as`].
+    pub fn same_type_as(&self, other: &DCParameter) -> bool {
+        self.base_type.same_type_as(&other.base_type)
+    }
+
+    /// Returns true if the value can be packed as an argument for
+    /// this parameter, so that arguments can be checked one by one
+    /// before packing them.
+    ///
+    /// An integer is accepted by an integer parameter of any width,
+    /// as long as it has the same signedness and the value fits, such
+    /// as `300` for an `int16`, but not for an `int8`. Signed integers,
+    /// unsigned integers and floating point numbers are never converted
+    /// into each other. Strings and blobs of a fixed length must have
+    /// exactly that length, and structs must have a value for each
+    /// member that its member type accepts.
+    pub fn accepts(&self, value: &FieldValue) -> bool {
+        dcaccessor::accepts_value(&self.base_type, value)
     }
+}
+
+/// Builds a [`DCParameter`] in one step, from its base type and
+/// an optional identifier and default value.
+///
+/// The default value, if any, is validated against the base type
+/// when the parameter is built.
+pub struct DCParameterBuilder<'dc> {
+    parent: &'dc DCAtomicField<'dc>,
+    base_type: DCTypeDefinition,
+    identifier: Option<String>,
+    default_value: Option<Vec<u8>>,
+}
 
-    pub fn set_identifier(&mut self, name: &str) {
+impl<'dc> DCParameterBuilder<'dc> {
+    pub fn new(parent: &'dc DCAtomicField<'dc>, base_type: DCTypeDefinition) -> Self {
+        Self {
+            parent,
+            base_type,
+            identifier: None,
+            default_value: None,
+        }
+    }
+
+    pub fn identifier(mut self, name: &str) -> Self {
         self.identifier = Some(name.to_owned());
+        self
+    }
+
+    pub fn default_value(mut self, v: Vec<u8>) -> Self {
+        self.default_value = Some(v);
+        self
+    }
+
+    /// Produces the [`DCParameter`], or [`SemanticError::InvalidDefault`]
+    /// if the default value is not a valid encoding of the base type.
+    pub fn build(self) -> Result<DCParameter<'dc>, SemanticError> {
+        if let Some(default) = &self.default_value {
+            if !default_matches_type(&self.base_type, default) {
+                return Err(SemanticError::InvalidDefault);
+            }
+        }
+
+        Ok(DCParameter {
+            parent: self.parent,
+            type_alias: self.base_type.get_alias().unwrap_or_default(),
+            base_type: self.base_type,
+            identifier: self.identifier,
+            has_default_value: self.default_value.is_some(),
+            default_value: self.default_value.unwrap_or_default(),
+        })
+    }
+}
+
+/// Checks that the byte length of a packed default value is
+/// consistent with the given type.
+///
+/// Array, struct, and method types are not validated here, as
+/// their layout depends on their element types.
+fn default_matches_type(dtype: &DCTypeDefinition, value: &[u8]) -> bool {
+    // Checks that the value is a length tag followed by that many bytes.
+    let tagged_len = |tag_size: usize| -> bool {
+        if value.len() < tag_size {
+            return false;
+        }
+        let mut tag: usize = 0;
+
+        for (i, byte) in value[..tag_size].iter().enumerate() {
+            tag |= (*byte as usize) << (8 * i); // little endian
+        }
+        value.len() - tag_size == tag
+    };
+
+    match dtype.get_dc_type() {
+        DCTypeEnum::TInt8 | DCTypeEnum::TUInt8 | DCTypeEnum::TChar => value.len() == 1,
+        DCTypeEnum::TInt16 | DCTypeEnum::TUInt16 => value.len() == 2,
+        DCTypeEnum::TInt32 | DCTypeEnum::TUInt32 | DCTypeEnum::TFloat32 => value.len() == 4,
+        DCTypeEnum::TInt64 | DCTypeEnum::TUInt64 | DCTypeEnum::TFloat64 => value.len() == 8,
+        DCTypeEnum::TString | DCTypeEnum::TBlob if !dtype.is_variable_length() => {
+            value.len() == usize::from(dtype.get_size())
+        }
+        DCTypeEnum::TString | DCTypeEnum::TBlob | DCTypeEnum::TVarString | DCTypeEnum::TVarBlob => {
+            tagged_len(2)
+        }
+        DCTypeEnum::TBlob32 | DCTypeEnum::TVarBlob32 => tagged_len(4),
+        DCTypeEnum::TArray | DCTypeEnum::TVarArray | DCTypeEnum::TStruct | DCTypeEnum::TMethod => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dcfield::FieldParent;
+    use crate::dclass::DClass;
+    use crate::dconfig::DCFileConfig;
+    use crate::read_dc;
+
+    #[test]
+    fn build_parameter() {
+        let dcf = read_dc(DCFileConfig::default(), String::default()).unwrap();
+        let dclass = DClass::new(&dcf, "DistributedDonut");
+        let atomic = DCAtomicField::new("setRadius", FieldParent::DClass(&dclass));
+
+        let param: DCParameter = DCParameterBuilder::new(&atomic, DCTypeEnum::TUInt16.into())
+            .identifier("radius")
+            .default_value(vec![0x10, 0x00])
+            .build()
+            .unwrap();
+
+        assert_eq!(param.get_type().get_dc_type(), DCTypeEnum::TUInt16);
+        assert_eq!(param.get_identifier(), Some("radius"));
+        assert!(param.has_default_value());
+        assert_eq!(param.get_default_value(), vec![0x10, 0x00]);
+
+        let param: DCParameter = DCParameterBuilder::new(&atomic, DCTypeEnum::TVarString.into())
+            .default_value(vec![0x02, 0x00, b'h', b'i'])
+            .build()
+            .unwrap();
+
+        assert_eq!(param.get_identifier(), None);
+        assert!(param.has_default_value());
+
+        let named: DCParameter = DCParameterBuilder::new(&atomic, DCTypeEnum::TVarString.into())
+            .identifier("name")
+            .build()
+            .unwrap();
+
+        assert!(param.same_type_as(&named));
+
+        let param: DCParameter = DCParameterBuilder::new(&atomic, DCTypeEnum::TInt8.into())
+            .build()
+            .unwrap();
+
+        assert!(!param.has_default_value());
     }
 
-    pub fn set_default_value(&mut self, v: Vec<u8>) {
-        self.default_value = v;
-        self.has_default_value = true;
+    #[test]
+    fn default_type_mismatch() {
+        let dcf = read_dc(DCFileConfig::default(), String::default()).unwrap();
+        let dclass = DClass::new(&dcf, "DistributedDonut");
+        let atomic = DCAtomicField::new("setRadius", FieldParent::DClass(&dclass));
+
+        // a 32-bit value for a 16-bit parameter
+        let res = DCParameterBuilder::new(&atomic, DCTypeEnum::TUInt16.into())
+            .default_value(vec![0x10, 0x00, 0x00, 0x00])
+            .build();
+
+        assert!(matches!(res, Err(SemanticError::InvalidDefault)));
+
+        // string length tag does not match the string length
+        let res = DCParameterBuilder::new(&atomic, DCTypeEnum::TVarString.into())
+            .default_value(vec![0x05, 0x00, b'h', b'i'])
+            .build();
+
+        assert!(matches!(res, Err(SemanticError::InvalidDefault)));
+    }
+
+    #[test]
+    fn accepts_argument_values() {
+        use crate::dctype::DCNumber;
+
+        let dcf = read_dc(DCFileConfig::default(), String::default()).unwrap();
+        let dclass = DClass::new(&dcf, "DistributedDonut");
+        let atomic = DCAtomicField::new("setState", FieldParent::DClass(&dclass));
+
+        let param = |dtype: DCTypeDefinition| DCParameterBuilder::new(&atomic, dtype).build().unwrap();
+        let int = |v: i64| FieldValue::Number(DCNumber::Integer(v));
+        let uint = |v: u64| FieldValue::Number(DCNumber::UnsignedInteger(v));
+        let float = |v: f64| FieldValue::Number(DCNumber::FloatingPoint(v));
+
+        let int8: DCParameter = param(DCTypeEnum::TInt8.into());
+        let int16: DCParameter = param(DCTypeEnum::TInt16.into());
+        let int64: DCParameter = param(DCTypeEnum::TInt64.into());
+        let uint8: DCParameter = param(DCTypeEnum::TUInt8.into());
+        let float32: DCParameter = param(DCTypeEnum::TFloat32.into());
+        let float64: DCParameter = param(DCTypeEnum::TFloat64.into());
+
+        // integers of the same signedness, of any width they fit in
+        assert!(int8.accepts(&int(-128)));
+        assert!(int16.accepts(&int(300)));
+        assert!(int64.accepts(&int(i64::MIN)));
+        assert!(uint8.accepts(&uint(255)));
+        assert!(!int8.accepts(&int(300)));
+        assert!(!uint8.accepts(&uint(256)));
+
+        // signedness and floating point are never converted
+        assert!(!int16.accepts(&uint(1)));
+        assert!(!uint8.accepts(&int(1)));
+        assert!(!int64.accepts(&float(1.0)));
+        assert!(!float64.accepts(&int(1)));
+        assert!(float32.accepts(&float(0.5)));
+        assert!(!float32.accepts(&float(f64::MAX)));
+        assert!(float64.accepts(&float(f64::MAX)));
+
+        let mut code: DCTypeDefinition = DCTypeEnum::TString.into();
+        code.size = 4;
+        let code: DCParameter = param(code);
+        let name: DCParameter = param(DCTypeEnum::TVarString.into());
+        let data: DCParameter = param(DCTypeEnum::TVarBlob.into());
+
+        assert!(code.accepts(&FieldValue::String("DNUT".into())));
+        assert!(!code.accepts(&FieldValue::String("DONUT".into())));
+        assert!(name.accepts(&FieldValue::String("DONUT".into())));
+        assert!(!name.accepts(&FieldValue::Blob(vec![1, 2])));
+        assert!(data.accepts(&FieldValue::Blob(vec![1, 2])));
+        assert!(!data.accepts(&FieldValue::String("DONUT".into())));
+
+        // struct Tag { uint8 layer; string label; };
+        let tag: DCParameter = param(DCTypeDefinition::new_struct(
+            "Tag",
+            vec![DCTypeEnum::TUInt8.into(), DCTypeEnum::TVarString.into()],
+        ));
+
+        assert!(tag.accepts(&FieldValue::Struct(vec![
+            uint(7),
+            FieldValue::String("glazed".into())
+        ])));
+        assert!(!tag.accepts(&FieldValue::Struct(vec![
+            uint(700),
+            FieldValue::String("glazed".into())
+        ])));
+        assert!(!tag.accepts(&FieldValue::Struct(vec![uint(7)])));
+        assert!(!tag.accepts(&uint(7)));
+
+        // arrays are not supported by field accessors
+        assert!(!param(DCTypeEnum::TVarArray.into()).accepts(&FieldValue::Blob(vec![])));
     }
 }
@@ -21,11 +21,32 @@
 
 use crate::dcfile::DCFile;
 use crate::dconfig::*;
+use crate::dctype::{DCTypeDefinition, DCTypeEnum};
+use crate::globals;
 use crate::hashgen::*;
 
+/// A member of a DC struct. Members of a plain type may declare the
+/// value they are packed with when unset, such as `int16 x = 5`.
+#[derive(Debug, Clone)]
+pub enum StructMember<'dc> {
+    Value {
+        name: String,
+        dc_type: DCTypeDefinition,
+        default_value: Option<Vec<u8>>,
+    },
+    /// A member whose type is another DC struct.
+    Struct {
+        name: String,
+        dc_struct: &'dc DCStruct<'dc>,
+    },
+}
+
 #[derive(Debug, Clone)]
 pub struct DCStruct<'dc> {
     dcfile: &'dc DCFile<'dc>,
+    struct_id: globals::DClassId,
+    name: String,
+    members: Vec<StructMember<'dc>>,
 }
 
 impl std::fmt::Display for DCStruct<'_> {
@@ -41,14 +62,337 @@ impl DCFileConfigAccessor for DCStruct<'_> {
 }
 
 impl LegacyDCHash for DCStruct<'_> {
-    fn generate_hash(&self, _: &mut DCHashGenerator) {
-        // TODO
+    fn generate_hash(&self, hashgen: &mut DCHashGenerator) {
+        // Hashed as a class with no parents, like Panda3D's
+        // DCClass::generate_hash (direct/src/dcparser/dcClass.cxx) and
+        // Astron's hash_struct (src/dclass/file/hash_legacy.cpp) do.
+        // Members are hashed by type only, as DCParameter::generate_hash
+        // in Panda3D leaves out the name of a parameter.
+        hashgen.add_string(self.name.clone());
+        hashgen.add_int(1); // is a struct
+        hashgen.add_int(0); // number of parents
+        hashgen.add_int(self.members.len().try_into().unwrap());
+
+        for member in &self.members {
+            match member {
+                StructMember::Value { dc_type, .. } => dc_type.generate_hash(hashgen),
+                StructMember::Struct { dc_struct, .. } => dc_struct.generate_hash(hashgen),
+            }
+        }
     }
 }
 
+impl<'dc> DCStruct<'dc> {
+    pub fn new(dcfile: &'dc DCFile<'dc>, name: &str) -> Self {
+        Self {
+            dcfile,
+            struct_id: 0,
+            name: name.to_owned(),
+            members: vec![],
+        }
+    }
+
+    /// Structs are numbered in the same space of IDs as distributed
+    /// classes; see [`DCFile::add_struct`].
+    #[inline(always)]
+    pub fn get_struct_id(&self) -> globals::DClassId {
+        self.struct_id
+    }
+
+    #[inline(always)]
+    pub(crate) fn set_struct_id(&mut self, id: globals::DClassId) {
+        self.struct_id = id;
+    }
+
+    #[inline(always)]
+    pub fn get_name(&self) -> String {
+        self.name.clone()
+    }
+
+    #[inline(always)]
+    pub fn get_num_members(&self) -> usize {
+        self.members.len()
+    }
+
+    #[inline(always)]
+    pub fn get_member(&self, index: usize) -> Option<&StructMember<'dc>> {
+        self.members.get(index)
+    }
+
+    pub fn add_member(&mut self, member: StructMember<'dc>) {
+        self.members.push(member);
+    }
+
+    /// Returns the type of this struct, for use as the type of a
+    /// parameter or field. Members that are structs are included
+    /// with their own type, so nested structs pack recursively.
+    pub fn get_type(&self) -> DCTypeDefinition {
+        let members: Vec<DCTypeDefinition> = self
+            .members
+            .iter()
+            .map(|member| match member {
+                StructMember::Value { dc_type, .. } => dc_type.clone(),
+                StructMember::Struct { dc_struct, .. } => dc_struct.get_type(),
+            })
+            .collect();
+
+        DCTypeDefinition::new_struct(&self.name, members)
+    }
+
+    /// Packs the default value of this struct, which is the default
+    /// of each of its members, in order. Members without a default
+    /// are packed as the zero value of their type, and members that
+    /// are structs are packed with their own default, recursively.
+    pub fn get_default_value(&self) -> Result<Vec<u8>, String> {
+        let mut packed: Vec<u8> = vec![];
+
+        for member in &self.members {
+            match member {
+                StructMember::Value {
+                    default_value: Some(value),
+                    ..
+                } => packed.extend_from_slice(value),
+                StructMember::Value { name, dc_type, .. } => {
+                    packed.extend(zero_value(dc_type).ok_or_else(|| {
+                        format!(
+                            "Member `{}` of struct `{}` has no default, and {} has no zero value.",
+                            name,
+                            self.name,
+                            dc_type.get_dc_type()
+                        )
+                    })?)
+                }
+                StructMember::Struct { dc_struct, .. } => packed.extend(dc_struct.get_default_value()?),
+            }
+        }
+        Ok(packed)
+    }
+}
+
+/// Returns the zero value of a type, as packed on the wire, or
+/// `None` for types whose layout depends on their element types.
+pub(crate) fn zero_value(dtype: &DCTypeDefinition) -> Option<Vec<u8>> {
+    let size: usize = match dtype.get_dc_type() {
+        DCTypeEnum::TInt8 | DCTypeEnum::TUInt8 | DCTypeEnum::TChar => 1,
+        DCTypeEnum::TInt16 | DCTypeEnum::TUInt16 => 2,
+        DCTypeEnum::TInt32 | DCTypeEnum::TUInt32 | DCTypeEnum::TFloat32 => 4,
+        DCTypeEnum::TInt64 | DCTypeEnum::TUInt64 | DCTypeEnum::TFloat64 => 8,
+        DCTypeEnum::TString | DCTypeEnum::TBlob if !dtype.is_variable_length() => dtype.get_size().into(),
+        // an empty value, which is only its length tag
+        DCTypeEnum::TString | DCTypeEnum::TBlob | DCTypeEnum::TVarString | DCTypeEnum::TVarBlob => 2,
+        DCTypeEnum::TVarArray => 2,
+        DCTypeEnum::TBlob32 | DCTypeEnum::TVarBlob32 => 4,
+        DCTypeEnum::TStruct if !dtype.get_struct_members().is_empty() => {
+            let members: Option<Vec<Vec<u8>>> = dtype.get_struct_members().iter().map(zero_value).collect();
+            return members.map(|members| members.concat());
+        }
+        DCTypeEnum::TArray | DCTypeEnum::TStruct | DCTypeEnum::TMethod => return None,
+    };
+    Some(vec![0; size])
+}
+
 /// Contains intermediate DC struct element structure and logic
 /// for semantic analysis as the DC struct is being built.
 pub(crate) mod interim {
     #[derive(Debug)]
     pub struct DCStruct {}
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::read_dc;
+
+    #[test]
+    fn nested_struct_default_value() {
+        let dcf = read_dc(DCFileConfig::default(), String::default()).unwrap();
+
+        // struct Vec3 { int16 x = 5; int16 y; uint8 z = 7; };
+        let mut vec3: DCStruct = DCStruct::new(&dcf, "Vec3");
+
+        vec3.add_member(StructMember::Value {
+            name: "x".into(),
+            dc_type: DCTypeEnum::TInt16.into(),
+            default_value: Some(vec![0x05, 0x00]),
+        });
+        vec3.add_member(StructMember::Value {
+            name: "y".into(),
+            dc_type: DCTypeEnum::TInt16.into(),
+            default_value: None,
+        });
+        vec3.add_member(StructMember::Value {
+            name: "z".into(),
+            dc_type: DCTypeEnum::TUInt8.into(),
+            default_value: Some(vec![0x07]),
+        });
+
+        // struct Pose { string label = "hi"; Vec3 position; };
+        let mut pose: DCStruct = DCStruct::new(&dcf, "Pose");
+
+        pose.add_member(StructMember::Value {
+            name: "label".into(),
+            dc_type: DCTypeEnum::TVarString.into(),
+            default_value: Some(vec![0x02, 0x00, b'h', b'i']),
+        });
+        pose.add_member(StructMember::Struct {
+            name: "position".into(),
+            dc_struct: &vec3,
+        });
+
+        assert_eq!(pose.get_num_members(), 2);
+        assert_eq!(
+            pose.get_default_value().unwrap(),
+            vec![0x02, 0x00, b'h', b'i', 0x05, 0x00, 0x00, 0x00, 0x07]
+        );
+
+        pose.add_member(StructMember::Value {
+            name: "path".into(),
+            dc_type: DCTypeEnum::TArray.into(),
+            default_value: None,
+        });
+        assert!(pose.get_default_value().is_err());
+    }
+
+    #[test]
+    fn struct_hash_compatibility() {
+        let astron: DCFileConfig = DCFileConfig {
+            dc_hash_compatibility: HashCompatibility::Astron,
+            ..DCFileConfig::default()
+        };
+        let panda_dcf = read_dc(DCFileConfig::default(), String::default()).unwrap();
+        let astron_dcf = read_dc(astron, String::default()).unwrap();
+
+        // struct <name> { int16 <x>; int16 <y>; };
+        let struct_hash = |dcf: &DCFile, name: &str, x: &str, y: &str| {
+            let mut vec2: DCStruct = DCStruct::new(dcf, name);
+
+            for member in [x, y] {
+                vec2.add_member(StructMember::Value {
+                    name: member.into(),
+                    dc_type: DCTypeEnum::TInt16.into(),
+                    default_value: None,
+                });
+            }
+            let mut hashgen: DCHashGenerator = DCHashGenerator::default();
+
+            vec2.generate_hash(&mut hashgen);
+            hashgen.get_hash()
+        };
+
+        // struct Vec2 { int16 x; int16 y; };
+        let mut expected: DCHashGenerator = DCHashGenerator::default();
+        let int16: DCTypeDefinition = DCTypeEnum::TInt16.into();
+
+        expected.add_string("Vec2".into());
+        expected.add_int(1);
+        expected.add_int(0);
+        expected.add_int(2);
+        int16.generate_hash(&mut expected);
+        int16.generate_hash(&mut expected);
+
+        // both hash a struct as a class with no parents
+        assert_eq!(struct_hash(&panda_dcf, "Vec2", "x", "y"), expected.get_hash());
+        assert_eq!(struct_hash(&astron_dcf, "Vec2", "x", "y"), expected.get_hash());
+
+        // the name of the struct is hashed, but not the names of members
+        assert_ne!(
+            struct_hash(&panda_dcf, "Vec2", "x", "y"),
+            struct_hash(&panda_dcf, "Point2", "x", "y")
+        );
+        assert_eq!(
+            struct_hash(&panda_dcf, "Vec2", "x", "y"),
+            struct_hash(&panda_dcf, "Vec2", "u", "v")
+        );
+    }
+
+    #[test]
+    fn nested_struct_field_type() {
+        use crate::dcaccessor::{FieldAccessor, FieldValue};
+        use crate::dcfield::{ClassField, DCField, FieldParent};
+        use crate::dclass::DClass;
+        use crate::dctype::DCNumber;
+
+        let dcf = read_dc(DCFileConfig::default(), String::default()).unwrap();
+
+        // sizes are set as they are for parsed types; zero is variable length
+        let member = |name: &str, dtype: DCTypeEnum, size: u16| {
+            let mut dc_type: DCTypeDefinition = dtype.into();
+            dc_type.size = size;
+
+            StructMember::Value {
+                name: name.into(),
+                dc_type,
+                default_value: None,
+            }
+        };
+
+        // struct Vec2 { int16 x; int16 y; };
+        let mut vec2: DCStruct = DCStruct::new(&dcf, "Vec2");
+        vec2.add_member(member("x", DCTypeEnum::TInt16, 2));
+        vec2.add_member(member("y", DCTypeEnum::TInt16, 2));
+
+        // struct Pose { string label; Vec2 position; uint8 layer; };
+        let mut pose: DCStruct = DCStruct::new(&dcf, "Pose");
+        pose.add_member(member("label", DCTypeEnum::TVarString, 0));
+        pose.add_member(StructMember::Struct {
+            name: "position".into(),
+            dc_struct: &vec2,
+        });
+        pose.add_member(member("layer", DCTypeEnum::TUInt8, 1));
+
+        assert_eq!(vec2.get_type().get_size(), 4);
+        assert!(pose.get_type().is_variable_length());
+        assert_eq!(pose.get_default_value().unwrap(), vec![0, 0, 0, 0, 0, 0, 0]);
+
+        // setPose(Pose) broadcast;
+        let dclass: DClass = DClass::new(&dcf, "DistributedDonut");
+        let mut field: DCField = DCField::new("setPose", FieldParent::DClass(&dclass));
+        field.set_field_type(pose.get_type());
+
+        let field: ClassField = ClassField::Field(field);
+        let accessor: FieldAccessor = FieldAccessor::from(&field);
+
+        let value: FieldValue = FieldValue::Struct(vec![
+            FieldValue::String("spawn".into()),
+            FieldValue::Struct(vec![
+                FieldValue::Number(DCNumber::Integer(-3)),
+                FieldValue::Number(DCNumber::Integer(4)),
+            ]),
+            FieldValue::Number(DCNumber::UnsignedInteger(1)),
+        ]);
+
+        let packed: Vec<u8> = accessor.pack(std::slice::from_ref(&value)).unwrap().get_data();
+        assert_eq!(
+            packed,
+            vec![5, 0, b's', b'p', b'a', b'w', b'n', 0xfd, 0xff, 4, 0, 1]
+        );
+        assert_eq!(accessor.unpack(&packed).unwrap(), vec![value]);
+
+        // a missing member of the nested struct is not packed
+        let partial: FieldValue = FieldValue::Struct(vec![
+            FieldValue::String("spawn".into()),
+            FieldValue::Struct(vec![FieldValue::Number(DCNumber::Integer(-3))]),
+            FieldValue::Number(DCNumber::UnsignedInteger(1)),
+        ]);
+        assert!(accessor.pack(&[partial]).is_err());
+
+        let hash_of = |dc_struct: &DCStruct| {
+            let mut hashgen: DCHashGenerator = DCHashGenerator::default();
+            dc_struct.generate_hash(&mut hashgen);
+            hashgen.get_hash()
+        };
+
+        // struct Pose { string label; uint8 layer; Vec2 position; };
+        let mut reordered: DCStruct = DCStruct::new(&dcf, "Pose");
+        reordered.add_member(member("label", DCTypeEnum::TVarString, 0));
+        reordered.add_member(member("layer", DCTypeEnum::TUInt8, 1));
+        reordered.add_member(StructMember::Struct {
+            name: "position".into(),
+            dc_struct: &vec2,
+        });
+
+        assert_eq!(hash_of(&pose), hash_of(&pose.clone()));
+        assert_ne!(hash_of(&pose), hash_of(&reordered));
+        assert!(!pose.get_type().same_type_as(&reordered.get_type()));
+    }
+}
@@ -19,14 +19,17 @@
 
 //! Data model that represents a DC switch statement.
 
+use crate::datagram::datagram::Datagram;
+use crate::datagram::iterator::DatagramIterator;
+use crate::dcaccessor::{pack_value, unpack_value, FieldValue};
 use crate::dcfield::DCField;
+use crate::dctype::DCTypeDefinition;
 use crate::hashgen::*;
 use std::collections::HashMap;
 
 /// Represents a case in a DC switch declaration.
 #[derive(Debug)]
 pub struct SwitchCase<'dc> {
-    switch: &'dc DCSwitch<'dc>,
     /// Note that in the legacy DC language, switch cases
     /// always assume to break, no matter if a break
     /// statement was parsed at syntax analysis. This
@@ -37,26 +40,6 @@ pub struct SwitchCase<'dc> {
     fields: Vec<DCField<'dc>>,
 }
 
-impl std::fmt::Display for SwitchCase<'_> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if self.is_default() {
-            writeln!(f, "default:")?;
-        } else {
-            write!(f, "case ")?;
-            self.switch.key.format_packed_data(f, &self.value, false)?;
-            writeln!(f, ":")?;
-        }
-
-        for field in &self.fields {
-            f.write_str(&field.to_string())?;
-        }
-        if self.breaks {
-            writeln!(f, "break;")?;
-        }
-        Ok(())
-    }
-}
-
 impl LegacyDCHash for SwitchCase<'_> {
     fn generate_hash(&self, hashgen: &mut DCHashGenerator) {
         if !self.is_default() {
@@ -72,6 +55,16 @@ impl LegacyDCHash for SwitchCase<'_> {
 }
 
 impl<'dc> SwitchCase<'dc> {
+    /// Creates a case that is selected when the switch key packs to
+    /// `value`. An empty `value` creates a default case.
+    pub fn new(value: Vec<u8>, fields: Vec<DCField<'dc>>, breaks: bool) -> Self {
+        Self {
+            breaks,
+            value,
+            fields,
+        }
+    }
+
     /// Returns true if this case is a default case.
     pub fn is_default(&self) -> bool {
         self.value.is_empty()
@@ -129,8 +122,21 @@ impl std::fmt::Display for DCSwitch<'_> {
 
         writeln!(f, ") {{")?;
 
-        for case in &self.cases {
-            f.write_str(&case.to_string())?;
+        for case in self.cases.iter().chain(&self.default_case) {
+            if case.is_default() {
+                writeln!(f, "default:")?;
+            } else {
+                write!(f, "case ")?;
+                self.key.format_packed_data(f, &case.value, false)?;
+                writeln!(f, ":")?;
+            }
+
+            for field in &case.fields {
+                f.write_str(&field.to_string())?;
+            }
+            if case.breaks {
+                writeln!(f, "break;")?;
+            }
         }
         writeln!(f, "}};")
     }
@@ -157,6 +163,35 @@ impl LegacyDCHash for DCSwitch<'_> {
 }
 
 impl<'dc> DCSwitch<'dc> {
+    /// Creates a switch on the given key field, with no cases.
+    pub fn new(name: Option<String>, key: DCField<'dc>) -> Self {
+        Self {
+            name,
+            key,
+            cases: vec![],
+            default_case: None,
+            case_fields: vec![],
+            cases_by_value: HashMap::default(),
+        }
+    }
+
+    /// Adds a case to the switch. A default case replaces the
+    /// current default case, if there is one.
+    ///
+    /// Returns an error if another case has the same value.
+    pub fn add_case(&mut self, case: SwitchCase<'dc>) -> Result<(), String> {
+        if case.is_default() {
+            self.default_case = Some(case);
+            return Ok(());
+        }
+        if self.cases_by_value.contains_key(&case.value) {
+            return Err(format!("Switch already has a case with value {:?}.", case.value));
+        }
+        self.cases_by_value.insert(case.value.clone(), self.cases.len());
+        self.cases.push(case);
+        Ok(())
+    }
+
     /// Returns the optional identifier for this switch.
     #[inline(always)]
     pub fn get_name(&self) -> Option<String> {
@@ -199,8 +234,79 @@ impl<'dc> DCSwitch<'dc> {
         self.cases_by_value.get(&value).copied()
     }
 
-    // TODO
-    pub fn apply_switch(&self, _value: Vec<u8>, _length: usize) {}
+    /// Returns the case that is selected by the given packed value
+    /// of the key, which is the default case if no case has that value.
+    ///
+    /// `None` is returned if no case matches and there is no default case.
+    pub fn apply_switch(&self, value: &[u8]) -> Option<&SwitchCase<'dc>> {
+        match self.cases_by_value.get(value) {
+            Some(index) => self.cases.get(*index),
+            None => self.default_case.as_ref(),
+        }
+    }
+
+    /// Packs the value of the key, followed by the values of the
+    /// fields of the case that the key selects, in order.
+    ///
+    /// Returns an error if the key selects no case, and the switch
+    /// has no default case.
+    pub fn pack(&self, key: &FieldValue, values: &[FieldValue]) -> Result<Datagram, String> {
+        let mut dg: Datagram = Datagram::default();
+        pack_value(&mut dg, field_type(&self.key)?, key)?;
+
+        let case: &SwitchCase = self.select_case(key, dg.get_buffer())?;
+
+        if values.len() != case.fields.len() {
+            return Err(format!(
+                "Switch case for {} has {} fields, but {} values were given.",
+                key,
+                case.fields.len(),
+                values.len()
+            ));
+        }
+        for (field, value) in case.fields.iter().zip(values) {
+            pack_value(&mut dg, field_type(field)?, value)?;
+        }
+        Ok(dg)
+    }
+
+    /// Unpacks the value of the key, and then the values of the
+    /// fields of the case that the key selects. The iterator is left
+    /// after the last field of the case, as more fields may follow.
+    ///
+    /// Returns an error if the key selects no case, and the switch
+    /// has no default case.
+    pub fn unpack(&self, dgi: &mut DatagramIterator) -> Result<(FieldValue, Vec<FieldValue>), String> {
+        let key_type: &DCTypeDefinition = field_type(&self.key)?;
+        let key: FieldValue = unpack_value(dgi, key_type).map_err(|err| err.to_string())?;
+
+        // Cases are looked up by their packed value, so pack the key again.
+        let mut packed_key: Datagram = Datagram::default();
+        pack_value(&mut packed_key, key_type, &key)?;
+
+        let case: &SwitchCase = self.select_case(&key, packed_key.get_buffer())?;
+        let mut values: Vec<FieldValue> = vec![];
+
+        for field in &case.fields {
+            values.push(unpack_value(dgi, field_type(field)?).map_err(|err| err.to_string())?);
+        }
+        Ok((key, values))
+    }
+
+    fn select_case(&self, key: &FieldValue, packed_key: &[u8]) -> Result<&SwitchCase<'dc>, String> {
+        self.apply_switch(packed_key).ok_or_else(|| {
+            format!(
+                "Switch key {} matches no case, and there is no default case.",
+                key
+            )
+        })
+    }
+}
+
+fn field_type<'a>(field: &'a DCField) -> Result<&'a DCTypeDefinition, String> {
+    field
+        .get_field_type()
+        .ok_or_else(|| format!("Field `{}` has no type.", field.get_field_name()))
 }
 
 /// Contains intermediate DC Switch structure and logic
@@ -226,3 +332,80 @@ pub(crate) mod interim {
         fn add_case(&mut self) {}
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dcfield::FieldParent;
+    use crate::dclass::DClass;
+    use crate::dconfig::DCFileConfig;
+    use crate::dctype::{DCNumber, DCTypeEnum};
+    use crate::read_dc;
+
+    fn unsigned(v: u64) -> FieldValue {
+        FieldValue::Number(DCNumber::UnsignedInteger(v))
+    }
+
+    #[test]
+    fn switch_case_packing() {
+        let dcf = read_dc(DCFileConfig::default(), String::default()).unwrap();
+        let dclass = DClass::new(&dcf, "DistributedDonut");
+
+        let new_field = |name: &str, dtype: DCTypeEnum| {
+            let mut field = DCField::new(name, FieldParent::DClass(&dclass));
+            field.set_field_type(dtype.into());
+            field
+        };
+
+        let mut switch = DCSwitch::new(Some("Topping".into()), new_field("kind", DCTypeEnum::TUInt8));
+
+        let sprinkles = vec![new_field("count", DCTypeEnum::TUInt16)];
+        switch
+            .add_case(SwitchCase::new(vec![1], sprinkles, true))
+            .unwrap();
+
+        // key, then the fields of the matched case
+        let dg: Datagram = switch.pack(&unsigned(1), &[unsigned(300)]).unwrap();
+        assert_eq!(dg.get_data(), vec![1, 0x2c, 0x01]);
+
+        let (key, values) = switch.unpack(&mut dg.into()).unwrap();
+        assert_eq!(key, unsigned(1));
+        assert_eq!(values, vec![unsigned(300)]);
+
+        // no case for 2, and no default case
+        assert!(switch.pack(&unsigned(2), &[]).is_err());
+
+        let mut dg: Datagram = Datagram::default();
+        dg.add_u8(2).unwrap();
+        assert!(switch.unpack(&mut dg.into()).is_err());
+
+        let glaze = vec![new_field("flavor", DCTypeEnum::TVarString)];
+        switch.add_case(SwitchCase::new(vec![], glaze, true)).unwrap();
+
+        let flavor = FieldValue::String("maple".into());
+        let dg: Datagram = switch.pack(&unsigned(2), std::slice::from_ref(&flavor)).unwrap();
+        assert_eq!(dg.get_data(), vec![2, 5, 0, b'm', b'a', b'p', b'l', b'e']);
+
+        let (key, values) = switch.unpack(&mut dg.into()).unwrap();
+        assert_eq!(key, unsigned(2));
+        assert_eq!(values, vec![flavor]);
+
+        // the defined case still takes precedence over the default case
+        assert!(switch.apply_switch(&[1]).is_some_and(|case| !case.is_default()));
+    }
+
+    #[test]
+    fn duplicate_case_value() {
+        let dcf = read_dc(DCFileConfig::default(), String::default()).unwrap();
+        let dclass = DClass::new(&dcf, "DistributedDonut");
+
+        let mut key = DCField::new("kind", FieldParent::DClass(&dclass));
+        key.set_field_type(DCTypeEnum::TUInt8.into());
+
+        let mut switch = DCSwitch::new(None, key);
+
+        switch.add_case(SwitchCase::new(vec![1], vec![], true)).unwrap();
+        assert!(switch.add_case(SwitchCase::new(vec![1], vec![], true)).is_err());
+        assert_eq!(switch.get_num_cases(), 1);
+    }
+}
@@ -78,6 +78,9 @@ pub struct DCTypeDefinition {
     alias: Option<String>,
     pub data_type: DCTypeEnum,
     pub size: DgSizeTag,
+    /// For struct types, the type of each member of the
+    /// struct, in order. See [`crate::dcstruct::DCStruct::get_type`].
+    members: Vec<DCTypeDefinition>,
 }
 
 /// Creates a new DCTypeDefinition struct with a DC type set.
@@ -87,6 +90,7 @@ impl From<DCTypeEnum> for DCTypeDefinition {
             alias: None,
             data_type: value,
             size: 0_u16,
+            members: vec![],
         }
     }
 }
@@ -111,10 +115,34 @@ impl LegacyDCHash for DCTypeDefinition {
         if self.alias.is_some() {
             hashgen.add_string(self.alias.clone().unwrap())
         }
+        if self.data_type == DCTypeEnum::TStruct {
+            hashgen.add_int(self.members.len().try_into().unwrap());
+
+            for member in &self.members {
+                member.generate_hash(hashgen);
+            }
+        }
     }
 }
 
 impl DCTypeDefinition {
+    /// Creates a struct type named `name`, which packs each of the
+    /// given member types in order. The struct is of variable length
+    /// if any of its members are.
+    pub fn new_struct(name: &str, members: Vec<DCTypeDefinition>) -> Self {
+        let size: DgSizeTag = match members.iter().any(|m| m.is_variable_length()) {
+            true => 0_u16,
+            false => members.iter().map(|m| m.get_size()).sum(),
+        };
+
+        Self {
+            alias: Some(name.to_owned()),
+            data_type: DCTypeEnum::TStruct,
+            size,
+            members,
+        }
+    }
+
     pub fn get_dc_type(&self) -> DCTypeEnum {
         self.data_type.clone()
     }
@@ -145,9 +173,40 @@ impl DCTypeDefinition {
     pub fn set_alias(&mut self, alias: String) {
         self.alias = Some(alias);
     }
+
+    /// Returns the type of each member of a struct type, in
+    /// order, or an empty slice if this is not a struct type.
+    #[inline(always)]
+    pub fn get_struct_members(&self) -> &[DCTypeDefinition] {
+        &self.members
+    }
+
+    /// Returns true if both types are structurally the same type,
+    /// of the same kind and size, regardless of their aliases.
+    ///
+    /// Struct types are the same if their members are the same.
+    pub fn same_type_as(&self, other: &DCTypeDefinition) -> bool {
+        self.data_type == other.data_type
+            && self.size == other.size
+            && self.members.len() == other.members.len()
+            && self
+                .members
+                .iter()
+                .zip(&other.members)
+                .all(|(a, b)| a.same_type_as(b))
+    }
+}
+
+/// Compares two optional types with [`DCTypeDefinition::same_type_as`].
+pub(crate) fn same_optional_type(a: Option<&DCTypeDefinition>, b: Option<&DCTypeDefinition>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a.same_type_as(b),
+        (None, None) => true,
+        _ => false,
+    }
 }
 
-#[derive(Copy, Clone, PartialEq)] // required for unwrapping when in an option type
+#[derive(Debug, Copy, Clone, PartialEq)] // required for unwrapping when in an option type
 pub enum DCNumber {
     Integer(i64),
     UnsignedInteger(u64),
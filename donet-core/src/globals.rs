@@ -59,6 +59,26 @@ pub const BCHAN_CLIENTS: Channel = 10;
 pub const BCHAN_STATESERVERS: Channel = 12;
 pub const BCHAN_DBSERVERS: Channel = 13;
 
+// ---------- Channel Layout ---------- //
+
+/// Returns the location channel of the given parent and zone.
+///
+/// Location channels are laid out as `(parent << 32) | zone`,
+/// so that every zone of a parent falls within one contiguous
+/// channel range. See [`parent_zones_channel_range`].
+pub fn location_as_channel(parent: DoId, zone: Zone) -> Channel {
+    ((parent as Channel) << ZONE_BITS) | (zone as Channel)
+}
+
+/// Returns the inclusive `(min, max)` channel range covering the
+/// location channels of all zones of the given parent.
+pub fn parent_zones_channel_range(parent: DoId) -> (Channel, Channel) {
+    (
+        location_as_channel(parent, 0),
+        location_as_channel(parent, ZONE_MAX),
+    )
+}
+
 // ---------- DC File Feature ---------- //
 
 cfg_if! {
@@ -83,4 +103,17 @@ mod tests {
         assert_eq!(MsgType::from(Protocol::CAAddInterest), 1200);
         assert_eq!(MsgType::from(Protocol::SSDeleteAIObjects), 2009);
     }
+
+    #[test]
+    fn location_channel_layout() {
+        assert_eq!(location_as_channel(4000, 0), 4000 << 32);
+        assert_eq!(location_as_channel(4000, 123), (4000 << 32) | 123);
+        assert_eq!(location_as_channel(DOID_MAX, ZONE_MAX), CHANNEL_MAX);
+
+        let (min, max): (Channel, Channel) = parent_zones_channel_range(4000);
+
+        assert_eq!(min, location_as_channel(4000, 0));
+        assert_eq!(max, location_as_channel(4000, ZONE_MAX));
+        assert!(max < location_as_channel(4001, 0));
+    }
 }
@@ -66,11 +66,13 @@ pub mod datagram;
 cfg_if! {
     if #[cfg(feature = "dcfile")] {
         mod parser;
+        pub mod dcaccessor;
         pub mod dcarray;
         pub mod dcatomic;
         pub mod dcfield;
         pub mod dcfile;
         pub mod dckeyword;
+        pub mod dclisting;
         pub mod dclass;
         pub mod dcmolecular;
         pub mod dcnumeric;
@@ -260,3 +262,21 @@ pub fn read_dc<'a>(config: dconfig::DCFileConfig, input: String) -> Result<DCFil
 
     parser::dcparse_pipeline(config, dcparse_input)
 }
+
+/// Same as [`read_dc`], but instead of stopping at the first error,
+/// the parser skips a declaration with a syntax error, up to the next
+/// `;` outside of braces, and keeps going.
+///
+/// Returns a best-effort DC file built from the declarations that
+/// could be read, along with every error found in the input, so
+/// that editor tooling can report all of them at once. The errors
+/// are not rendered to stderr.
+#[cfg(feature = "dcfile")]
+pub fn read_dc_lenient<'a>(
+    config: dconfig::DCFileConfig,
+    input: String,
+) -> (DCFile<'a>, Vec<codespan_reporting::diagnostic::Diagnostic<usize>>) {
+    let dcparse_input: Vec<parser::InputFile> = vec![("input.dc".to_string(), input)];
+
+    parser::dcparse_pipeline_lenient(config, dcparse_input)
+}
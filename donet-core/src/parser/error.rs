@@ -71,6 +71,8 @@ pub enum SemanticError {
     AlreadyDefined(String),
     #[error("`{0}` is not defined")]
     NotDefined(String),
+    #[error("typedef `{0}` is never used")]
+    UnusedTypedef(String),
 
     // dc file
     #[error("multiple inheritance is not allowed")]
@@ -79,6 +81,8 @@ pub enum SemanticError {
     DClassOverflow,
     #[error("maximum number of fields declared")]
     FieldOverflow,
+    #[error("`{field}` shadows a field inherited from `{parent}`")]
+    ShadowedField { field: String, parent: String },
 
     // python-style imports
     #[error("redundant view suffix `{0}`")]
@@ -135,10 +139,12 @@ impl ToErrorCode for SemanticError {
             // generic
             Self::AlreadyDefined(_) => "E0200",
             Self::NotDefined(_) => "E0201",
+            Self::UnusedTypedef(_) => "E0202",
             // dc file
             Self::MultipleInheritanceDisabled => "E0210",
             Self::DClassOverflow => "E0211",
             Self::FieldOverflow => "E0212",
+            Self::ShadowedField { field: _, parent: _ } => "E0213",
             // python-style imports
             Self::RedundantViewSuffix(_) => "E0220",
             // keywords
@@ -204,6 +210,22 @@ impl Diagnostic {
             error: err.into(),
         }
     }
+
+    /// Same as [`Diagnostic::error`], but the diagnostic does not
+    /// fail the pipeline, as it points at a likely mistake in an
+    /// otherwise valid DC file.
+    pub fn warning(span: Span, pipeline: &mut PipelineData, err: impl Into<PipelineError>) -> Self {
+        Self {
+            severity: codespan_diag::Severity::Warning,
+            ..Self::error(span, pipeline, err)
+        }
+    }
+
+    /// Points this diagnostic at a file other than the one
+    /// currently being processed by the pipeline.
+    pub fn in_file(self, file_id: usize) -> Self {
+        Self { file_id, ..self }
+    }
 }
 
 /// Allows converting our Diagnostic type into a codespan Diagnostic type.
@@ -37,6 +37,7 @@ mod semantics;
 use crate::dcfile::DCFile;
 use crate::dconfig::*;
 use anyhow::Result;
+use codespan_reporting::diagnostic::Diagnostic;
 use error::DCReadError;
 use pipeline::PipelineData;
 
@@ -54,38 +55,67 @@ pub(crate) fn dcparse_pipeline<'a>(
     // Create new pipeline data struct with [`DCFileConfig`]
     let mut pipeline_data: PipelineData<'_> = PipelineData::from(config);
 
+    run_pipeline(&mut pipeline_data, &inputs)
+}
+
+/// Same as [`dcparse_pipeline`], but the pipeline recovers from errors,
+/// returning a best-effort DC file along with every error found.
+pub(crate) fn dcparse_pipeline_lenient<'a>(
+    config: DCFileConfig,
+    inputs: Vec<InputFile>,
+) -> (DCFile<'a>, Vec<Diagnostic<usize>>) {
+    let mut pipeline_data: PipelineData<'_> = PipelineData::from(config);
+    pipeline_data.set_lenient();
+
+    let dc_file: DCFile<'a> =
+        run_pipeline(&mut pipeline_data, &inputs).expect("Lenient DC parser pipeline failed.");
+
+    (dc_file, pipeline_data.take_errors())
+}
+
+fn run_pipeline<'a, 'b>(
+    pipeline_data: &mut PipelineData<'b>,
+    inputs: &'b [InputFile],
+) -> Result<DCFile<'a>, DCReadError> {
     // Create codespan files for each DC file
-    for input in &inputs {
+    for input in inputs {
         let _: usize = pipeline_data.files.add(&input.0, &input.1);
     }
 
     // Create an abstract syntax tree per DC file
-    for input in &inputs {
-        let lexer: lexer::Lexer<'_> = lexer::Lexer::new(&input.1);
+    for input in inputs {
+        let mut tokens: Vec<(lexer::DCToken, lexer::Span)> = lexer::Lexer::new(&input.1).collect();
 
-        let ast: ast::Root = match parser::parse(lexer) {
+        let ast: ast::Root = loop {
             // See issue #19 for why LALR parser cannot return custom errors.
-            Err(err) => {
-                if let Some(parser_err) = err.clone().0 {
-                    // Extract parser error details
-                    let span: lexer::Span = parser_err.1;
-                    let token: lexer::DCToken = parser_err.0;
-                    let msg: String = err.1.to_owned();
-
-                    let diag: error::Diagnostic = error::Diagnostic::error(
-                        span,
-                        &mut pipeline_data,
-                        error::PipelineError::Parser(error::ParseError::Error(token, msg)),
-                    );
-
-                    pipeline_data
-                        .emit_diagnostic(diag.into())
-                        .expect("Failed to emit diagnostic.");
-                }
+            let err = match parser::parse(tokens.iter().cloned()) {
+                Ok(ast) => break ast,
+                Err(err) => err,
+            };
+
+            if let Some((token, span)) = err.0.clone() {
+                let msg: String = err.1.to_owned();
+
+                let diag: error::Diagnostic = error::Diagnostic::error(
+                    span,
+                    pipeline_data,
+                    error::PipelineError::Parser(error::ParseError::Error(token, msg)),
+                );
+
+                pipeline_data
+                    .emit_diagnostic(diag.into())
+                    .expect("Failed to emit diagnostic.");
+            }
 
+            if !pipeline_data.is_lenient() {
                 return Err(DCReadError::Syntax);
             }
-            Ok(ast) => ast,
+            if tokens.is_empty() {
+                break ast::Root {
+                    type_declarations: vec![],
+                };
+            }
+            skip_declaration(&mut tokens, err.0.map(|(_, span)| span));
         };
 
         pipeline_data.syntax_trees.push(ast);
@@ -93,5 +123,44 @@ pub(crate) fn dcparse_pipeline<'a>(
     }
 
     // Process all abstract syntax trees in semantic analyzer.
-    semantics::semantic_analyzer(&mut pipeline_data)
+    semantics::semantic_analyzer(pipeline_data)
+}
+
+/// Recovers from a syntax error by removing the tokens of the
+/// declaration that holds the token at `error_span`, which ends at
+/// the next `;` outside of braces. If the error is at the end of the
+/// input, the unfinished declaration at the end is removed instead.
+fn skip_declaration(tokens: &mut Vec<(lexer::DCToken, lexer::Span)>, error_span: Option<lexer::Span>) {
+    use lexer::DCToken;
+
+    let error_at: usize = error_span
+        .and_then(|span| tokens.iter().position(|(_, s)| *s == span))
+        .unwrap_or(tokens.len());
+
+    // Indices one past each `;` that ends a declaration.
+    let mut boundaries: Vec<usize> = vec![];
+    let mut depth: usize = 0;
+
+    for (i, (token, _)) in tokens.iter().enumerate() {
+        match token {
+            DCToken::OpenBraces => depth += 1,
+            DCToken::CloseBraces => depth = depth.saturating_sub(1),
+            DCToken::Semicolon if depth == 0 => boundaries.push(i + 1),
+            _ => {}
+        }
+    }
+
+    let start: usize = boundaries
+        .iter()
+        .rev()
+        .find(|b| **b <= error_at && **b < tokens.len())
+        .copied()
+        .unwrap_or(0);
+    let end: usize = boundaries
+        .iter()
+        .find(|b| **b > error_at)
+        .copied()
+        .unwrap_or(tokens.len());
+
+    tokens.drain(start..end);
 }
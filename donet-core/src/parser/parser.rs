@@ -207,10 +207,10 @@ parser! {
             })
         },
         // This rule handles a specific piece of illegal grammar that is legal in Panda.
-        // The parser will print a useful message to stdout describing the issue,
+        // The parser will print a useful message to stderr describing the issue,
         // and will ignore this grammar and continue without a panic.
         Typedef UInt8T BoolT => {
-            println!("{}\n\n\"typedef uint8 bool;\" is deprecated!\n\n\
+            eprintln!("{}\n\n\"typedef uint8 bool;\" is deprecated!\n\n\
             Cannot declare type alias for uint8 as 'bool', as it is a reserved identifier \
             in the DC language.\nDonet introduces the 'bool' data type, which is an alias \
             for uint8 under the hood.\n", span!());
@@ -57,6 +57,9 @@ pub(crate) struct PipelineData<'a> {
     _config: term::Config,
     diagnostics_enabled: bool,
     errors_emitted: usize,
+    /// Set in lenient mode, in which error diagnostics are
+    /// collected here instead of being rendered to stderr.
+    collected_errors: Option<Vec<Diagnostic<usize>>>,
     pub files: SimpleFiles<&'a str, &'a str>,
     current_file: usize,
     pub syntax_trees: Vec<ast::Root>,
@@ -68,7 +71,7 @@ pub(crate) struct PipelineData<'a> {
 /// Upon drop, emit a final diagnostic with the finish status of the pipeline.
 impl Drop for PipelineData<'_> {
     fn drop(&mut self) {
-        if self.errors_emitted > 0 {
+        if self.errors_emitted > 0 && self.collected_errors.is_none() {
             let diag = Diagnostic::error().with_message(format!(
                 "Failed to read DC files due to {} previous errors.",
                 self.errors_emitted
@@ -97,6 +100,7 @@ impl From<DCFileConfig> for PipelineData<'_> {
                 }
             },
             errors_emitted: 0,
+            collected_errors: None,
             files: SimpleFiles::new(),
             current_file: 0,
             syntax_trees: vec![],
@@ -115,8 +119,13 @@ impl PipelineData<'_> {
     pub(crate) fn emit_diagnostic(&mut self, diag: Diagnostic<usize>) -> Result<(), files::Error> {
         if diag.severity == Severity::Error {
             self.errors_emitted += 1;
+
+            if let Some(errors) = &mut self.collected_errors {
+                errors.push(diag);
+                return Ok(());
+            }
         }
-        if !self.diagnostics_enabled {
+        if !self.diagnostics_enabled || self.collected_errors.is_some() {
             return Ok(());
         }
         term::emit(&mut self._writer.lock(), &self._config, &self.files, &diag)
@@ -145,6 +154,25 @@ impl PipelineData<'_> {
     pub(crate) fn failing(&self) -> bool {
         self.errors_emitted > 0
     }
+
+    /// Switches the pipeline to lenient mode, in which it recovers
+    /// from errors to report as many of them as possible.
+    pub(crate) fn set_lenient(&mut self) {
+        self.collected_errors = Some(vec![]);
+    }
+
+    #[inline(always)]
+    pub(crate) fn is_lenient(&self) -> bool {
+        self.collected_errors.is_some()
+    }
+
+    /// Takes the error diagnostics collected in lenient mode.
+    pub(crate) fn take_errors(&mut self) -> Vec<Diagnostic<usize>> {
+        self.collected_errors
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
 }
 
 #[cfg(test)]
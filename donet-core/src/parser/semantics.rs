@@ -31,11 +31,76 @@
 //! [`Abstract Syntax Tree`]: https://en.wikipedia.org/wiki/Abstract_syntax_tree
 
 use super::ast;
-use super::error::DCReadError;
+use super::error::{DCReadError, Diagnostic, SemanticError};
+use super::lexer::Span;
 use super::PipelineData;
 use crate::dcfile;
 use crate::dconfig::*;
 use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+
+/// Declarations that the consistency checks need to look back on
+/// while the syntax trees are walked, in declaration order.
+#[derive(Default)]
+struct LintState {
+    /// Field identifiers and parents of each dclass, by dclass identifier.
+    dclasses: HashMap<String, (Vec<String>, Vec<String>)>,
+    /// Typedef aliases, with the file and span they were declared at.
+    typedefs: Vec<(String, usize, Span)>,
+    /// Identifiers that are used as a type by any declaration.
+    type_references: HashSet<String>,
+}
+
+impl LintState {
+    /// Returns every field inherited through the given parents, each
+    /// paired with the identifier of the dclass that declares it.
+    fn inherited_fields(&self, parents: &[String]) -> Vec<(String, String)> {
+        let mut fields: Vec<(String, String)> = vec![];
+        let mut visited: HashSet<&str> = HashSet::default();
+        let mut pending: Vec<&String> = parents.iter().rev().collect();
+
+        while let Some(parent) = pending.pop() {
+            if !visited.insert(parent) {
+                continue;
+            }
+            // Parents that are not declared are not this check's concern.
+            if let Some((parent_fields, grandparents)) = self.dclasses.get(parent) {
+                for field in parent_fields {
+                    fields.push((field.clone(), parent.clone()));
+                }
+                pending.extend(grandparents.iter().rev());
+            }
+        }
+        fields
+    }
+
+    fn add_type_reference(&mut self, data_type: &ast::NonMethodDataType) {
+        match data_type {
+            ast::NonMethodDataType::StructType(identifier) => {
+                self.type_references.insert(identifier.clone());
+            }
+            ast::NonMethodDataType::TypeWithArray(array) => {
+                if let ast::ArrayableType::Struct(identifier) = &array.data_type {
+                    self.type_references.insert(identifier.clone());
+                }
+            }
+            ast::NonMethodDataType::NumericType(_) => {}
+        }
+    }
+
+    fn add_parameter_references(&mut self, parameters: &ast::MethodBody) {
+        for parameter in parameters {
+            self.add_type_reference(&parameter.data_type);
+        }
+    }
+
+    fn add_named_field_references(&mut self, field: &ast::NamedField) {
+        match field {
+            ast::NamedField::ParameterField(pf) => self.add_type_reference(&pf.parameter.data_type),
+            ast::NamedField::MethodAsField(mf) => self.add_parameter_references(&mf.parameters),
+        }
+    }
+}
 
 /// Takes in the [`Abstract Syntax Trees`] from the last stage of the pipeline
 /// and outputs a [`crate::dcfile::DCFile`] immutable structure.
@@ -47,6 +112,7 @@ pub fn semantic_analyzer<'a>(pipeline: &mut PipelineData) -> Result<dcfile::DCFi
 
     // create a new interim DC file struct from our pipeline's dc parser configuration
     let mut dc_file = dcfile::interim::DCFile::from(pipeline.get_dc_config().clone());
+    let mut lint: LintState = LintState::default();
 
     // Iterate through all ASTs and add them to our DCFile intermediate object.
     for ast in pipeline.syntax_trees.clone() {
@@ -58,9 +124,22 @@ pub fn semantic_analyzer<'a>(pipeline: &mut PipelineData) -> Result<dcfile::DCFi
                 ast::TypeDeclaration::KeywordType(keyword) => {
                     dc_file.add_keyword(pipeline, keyword);
                 }
-                ast::TypeDeclaration::StructType(_) => {}
-                ast::TypeDeclaration::DClassType(_) => {}
-                ast::TypeDeclaration::TypedefType(_) => {}
+                ast::TypeDeclaration::StructType(strukt) => {
+                    add_struct_references(&mut lint, &strukt);
+                }
+                ast::TypeDeclaration::DClassType(dclass) => {
+                    check_dclass_fields(pipeline, &mut dc_file, &mut lint, &dclass);
+                }
+                ast::TypeDeclaration::TypedefType(type_def) => {
+                    check_typedef_transforms(pipeline, &type_def);
+
+                    lint.add_type_reference(&type_def.data_type);
+
+                    if let Some(alias) = &type_def.alias_identifier {
+                        lint.typedefs
+                            .push((alias.clone(), pipeline.current_file(), type_def.span));
+                    }
+                }
                 // Ignore is returned by productions that parsed certain
                 // grammar that may be deprecated but ignored for
                 // compatibility & should not be added to the DC file.
@@ -70,7 +149,18 @@ pub fn semantic_analyzer<'a>(pipeline: &mut PipelineData) -> Result<dcfile::DCFi
         pipeline.next_file(); // tell the pipeline we are processing the next file
     }
 
-    if pipeline.failing() {
+    // Typedefs can be used before they are declared, and in
+    // any DC file, so they can only be checked once all are read.
+    for (alias, file_id, span) in std::mem::take(&mut lint.typedefs) {
+        if !lint.type_references.contains(&alias) {
+            let diag: Diagnostic =
+                Diagnostic::warning(span, pipeline, SemanticError::UnusedTypedef(alias)).in_file(file_id);
+
+            dc_file.add_warning(pipeline, diag);
+        }
+    }
+
+    if pipeline.failing() && !pipeline.is_lenient() {
         Err(DCReadError::Semantic)
     } else {
         // Convert intermediate DC file structure to final immutable DC file structure.
@@ -78,6 +168,116 @@ pub fn semantic_analyzer<'a>(pipeline: &mut PipelineData) -> Result<dcfile::DCFi
     }
 }
 
+/// Warns of fields that shadow a field inherited from one of the
+/// dclass' ancestors, and of keywords repeated on a single field.
+fn check_dclass_fields(
+    pipeline: &mut PipelineData,
+    dc_file: &mut dcfile::interim::DCFile,
+    lint: &mut LintState,
+    dclass: &ast::DClass,
+) {
+    let inherited: Vec<(String, String)> = lint.inherited_fields(&dclass.parents);
+    let mut field_identifiers: Vec<String> = vec![];
+
+    for field in &dclass.fields {
+        let (span, identifier): (Span, Option<&String>) = match field {
+            ast::AtomicOrMolecular::Atomic(atomic) => {
+                let mut recorded_keywords: HashSet<&str> = HashSet::default();
+
+                for keyword in &atomic.keywords {
+                    if !recorded_keywords.insert(keyword) {
+                        let err = SemanticError::RedundantKeyword(keyword.clone());
+                        let diag: Diagnostic = Diagnostic::warning(atomic.span, pipeline, err);
+
+                        dc_file.add_warning(pipeline, diag);
+                    }
+                }
+                lint.add_parameter_references(&atomic.parameters);
+
+                (atomic.span, atomic.identifier.as_ref())
+            }
+            ast::AtomicOrMolecular::Molecular(molecular) => (molecular.span, Some(&molecular.identifier)),
+        };
+
+        let Some(identifier) = identifier else {
+            continue;
+        };
+
+        if let Some((_, parent)) = inherited.iter().find(|(field, _)| field == identifier) {
+            let err = SemanticError::ShadowedField {
+                field: identifier.clone(),
+                parent: parent.clone(),
+            };
+            let diag: Diagnostic = Diagnostic::warning(span, pipeline, err);
+
+            dc_file.add_warning(pipeline, diag);
+        }
+        field_identifiers.push(identifier.clone());
+    }
+
+    lint.dclasses.insert(
+        dclass.identifier.clone(),
+        (field_identifiers, dclass.parents.clone()),
+    );
+}
+
+/// Records the types used by the fields of a struct.
+fn add_struct_references(lint: &mut LintState, strukt: &ast::Struct) {
+    for field in &strukt.fields {
+        match field {
+            ast::StructField::ParameterField(pf) => lint.add_type_reference(&pf.parameter.data_type),
+            ast::StructField::MethodAsField(mf) => lint.add_parameter_references(&mf.parameters),
+            ast::StructField::Switch(switch) => {
+                lint.add_type_reference(&switch.key_parameter.parameter.data_type);
+
+                for case in &switch.cases {
+                    for case_field in &case.fields {
+                        lint.add_named_field_references(case_field);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Validates the numeric transforms of the aliased type of a typedef.
+fn check_typedef_transforms(pipeline: &mut PipelineData, type_def: &ast::TypeDefinition) {
+    match &type_def.data_type {
+        ast::NonMethodDataType::NumericType(numeric) => check_numeric_transforms(pipeline, numeric),
+        ast::NonMethodDataType::TypeWithArray(array) => {
+            if let ast::ArrayableType::Numeric(numeric) = &array.data_type {
+                check_numeric_transforms(pipeline, numeric);
+            }
+        }
+        ast::NonMethodDataType::StructType(_) => {}
+    }
+}
+
+/// Emits an error if the divisor of the numeric type is not an integer
+/// in the 16-bit unsigned range greater than 0, or if its modulus is
+/// not greater than 0.
+fn check_numeric_transforms(pipeline: &mut PipelineData, numeric: &ast::NumericType) {
+    if let Some(divisor) = numeric.divisor {
+        if divisor < 1.0 || divisor > f64::from(u16::MAX) || divisor.fract() != 0.0 {
+            let diag: Diagnostic = Diagnostic::error(numeric.span, pipeline, SemanticError::InvalidDivisor);
+
+            pipeline
+                .emit_diagnostic(diag.into())
+                .expect("Failed to emit diagnostic.");
+        }
+    }
+
+    if let Some(modulus) = numeric.modulus {
+        if modulus <= 0.0 {
+            let diag: Diagnostic = Diagnostic::error(numeric.span, pipeline, SemanticError::InvalidModulus);
+
+            pipeline
+                .emit_diagnostic(diag.into())
+                .expect("Failed to emit diagnostic.");
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -115,6 +315,37 @@ mod tests {
         }
     }
 
+    #[test]
+    fn lenient_read_reports_every_syntax_error() {
+        let dc_string: &str = "
+            keyword broadcast;
+            keyword ram;
+            typedef uint32 doId
+            keyword airecv;
+
+            dclass DistributedDonut {
+              set_name(string) ram;
+            };
+
+            dclass DistributedCake {
+              set_flavor(string) ram
+            };
+            keyword db;
+        ";
+
+        let (dcf, errors) = crate::read_dc_lenient(DCFileConfig::default(), dc_string.into());
+
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        assert!(errors.iter().all(|e| e.code.as_deref() == Some("E0100")));
+
+        // declarations around the errors are still read, but the
+        // keyword after the unterminated typedef is skipped with it
+        let keywords: Vec<String> = (0..dcf.get_num_keywords())
+            .map(|i| dcf.get_keyword(i).unwrap().get_name())
+            .collect();
+        assert_eq!(keywords, vec!["broadcast", "ram", "db"]);
+    }
+
     #[test]
     #[should_panic]
     fn redundant_view_suffix() {
@@ -137,4 +368,67 @@ mod tests {
 
         let _ = read_dc(dc_config, dc_string.into()).expect("Should fail.");
     }
+
+    #[test]
+    fn typedef_divisor_of_zero() {
+        let dc_config = DCFileConfig::default();
+
+        let valid: &str = "typedef uint16/100 price;";
+        assert!(read_dc(dc_config.clone(), valid.into()).is_ok());
+
+        let zero: &str = "typedef uint16/0 price;";
+        assert!(read_dc(dc_config, zero.into()).is_err());
+    }
+
+    #[test]
+    fn shadowed_field_and_duplicate_keyword_warnings() {
+        use codespan_reporting::diagnostic::{Diagnostic, Severity};
+
+        let dc_config = DCFileConfig::default();
+        let dc_string: &str = "
+            typedef uint32 doId;
+            typedef uint8 heading;
+
+            dclass DistributedNode {
+                setX(int16 x) broadcast;
+                setParent(doId parent) ram;
+            };
+
+            dclass DistributedAvatar : DistributedNode {
+                setName(string name) required broadcast ram ram;
+            };
+
+            dclass DistributedToon : DistributedAvatar {
+                setX(int16 x) broadcast;
+            };
+        ";
+
+        let dcf: dcfile::DCFile = read_dc(dc_config, dc_string.into()).expect("Warnings should not fail.");
+        let warnings: Vec<Diagnostic<usize>> = dcf.validate();
+
+        let found: Vec<(&str, &str)> = warnings
+            .iter()
+            .map(|diag| {
+                assert_eq!(diag.severity, Severity::Warning);
+                assert_eq!(diag.labels.len(), 1);
+                (diag.code.as_deref().unwrap(), diag.message.as_str())
+            })
+            .collect();
+
+        assert_eq!(
+            found,
+            vec![
+                ("E0230", "redundant keyword `ram`"),
+                ("E0213", "`setX` shadows a field inherited from `DistributedNode`"),
+                ("E0202", "typedef `heading` is never used"),
+            ]
+        );
+
+        // labels point at the offending declarations
+        let span_of = |diag: &Diagnostic<usize>| &dc_string[diag.labels[0].range.clone()];
+
+        assert!(span_of(&warnings[0]).starts_with("setName("));
+        assert!(span_of(&warnings[1]).starts_with("setX("));
+        assert!(span_of(&warnings[2]).starts_with("typedef uint8 heading"));
+    }
 }
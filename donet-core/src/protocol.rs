@@ -19,7 +19,10 @@
 
 //! This module defines the `Protocol` enum, which stores every
 //! type of message in the Donet protocol, along with their 16-bit ID.
+//! It also defines the standard reason codes for client ejects.
 
+use crate::globals::MsgType;
+use std::ops::RangeInclusive;
 use strum_macros::EnumIter;
 
 /// Enum variants for all message types in the Donet protocol.
@@ -156,4 +159,125 @@ pub enum Protocol {
     MDSetConName = 9012,
     MDSetConUrl = 9013,
     MDLogMessage = 9014,
+    MDGetSubscriptions = 9020,
+    MDGetSubscriptionsResp = 9021,
+    MDDrain = 9030,
+}
+
+impl Protocol {
+    /// Returns the subsystem that this message belongs to,
+    /// which is decided by the range its message type is in.
+    pub fn category(self) -> MessageCategory {
+        match MsgType::from(self) {
+            0..=999 => MessageCategory::Client,
+            1000..=1999 => MessageCategory::ClientAgent,
+            2000..=2199 => MessageCategory::StateServer,
+            2200..=2999 => MessageCategory::DBSS,
+            3000..=3999 => MessageCategory::Database,
+            _ => MessageCategory::MessageDirector,
+        }
+    }
+}
+
+/// Subsystems of the Donet protocol, which each own
+/// a range of message types. See [`Protocol::category`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, EnumIter)]
+pub enum MessageCategory {
+    Client,
+    ClientAgent,
+    StateServer,
+    DBSS,
+    Database,
+    MessageDirector,
+}
+
+impl MessageCategory {
+    /// Returns the range of message types owned by this subsystem.
+    pub fn range(self) -> RangeInclusive<MsgType> {
+        match self {
+            Self::Client => 0..=999,
+            Self::ClientAgent => 1000..=1999,
+            Self::StateServer => 2000..=2199,
+            Self::DBSS => 2200..=2999,
+            Self::Database => 3000..=3999,
+            Self::MessageDirector => 9000..=9999,
+        }
+    }
+}
+
+/// Standard reason codes sent to a client in a `CLIENT_EJECT` message.
+///
+/// Codes shared with Astron keep Astron's values, so that existing
+/// clients can interpret them.
+#[repr(u16)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, EnumIter)]
+pub enum EjectReason {
+    OversizedDatagram = 106,
+    NoHello = 107,
+    InvalidMsgType = 108,
+    TruncatedDatagram = 109,
+    SecurityViolation = 113,
+    BadVersion = 124,
+    BadDCHash = 125,
+    ServerFull = 150,
+    AdminKick = 151,
+    HeartbeatTimeout = 345,
+}
+
+impl From<EjectReason> for u16 {
+    fn from(value: EjectReason) -> Self {
+        value as u16
+    }
+}
+
+impl EjectReason {
+    /// Human readable explanation sent along with the reason code.
+    pub fn reason(self) -> &'static str {
+        match self {
+            Self::OversizedDatagram => "Received a datagram that is too large.",
+            Self::NoHello => "First message was not CLIENT_HELLO.",
+            Self::InvalidMsgType => "Received an invalid message type.",
+            Self::TruncatedDatagram => "Received a truncated datagram.",
+            Self::SecurityViolation => "Client sent a message it is not allowed to send.",
+            Self::BadVersion => "Client version does not match the server version.",
+            Self::BadDCHash => "Client DC file hash does not match the server DC file hash.",
+            Self::ServerFull => "The server is full.",
+            Self::AdminKick => "Disconnected by an administrator.",
+            Self::HeartbeatTimeout => "Server timed out while waiting for a heartbeat.",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use strum::IntoEnumIterator;
+
+    #[test]
+    fn message_categories() {
+        assert_eq!(Protocol::ClientHello.category(), MessageCategory::Client);
+        assert_eq!(Protocol::CASetState.category(), MessageCategory::ClientAgent);
+        assert_eq!(
+            Protocol::SSObjectSetField.category(),
+            MessageCategory::StateServer
+        );
+        assert_eq!(Protocol::DBSSObjectDeleteDisk.category(), MessageCategory::DBSS);
+        assert_eq!(Protocol::DBCreateObject.category(), MessageCategory::Database);
+        assert_eq!(
+            Protocol::MDAddChannel.category(),
+            MessageCategory::MessageDirector
+        );
+
+        // every message is within the range of its category
+        for message in Protocol::iter() {
+            let category: MessageCategory = message.category();
+
+            assert!(
+                category.range().contains(&MsgType::from(message)),
+                "{:?} is outside of the {:?} range.",
+                message,
+                category
+            );
+        }
+    }
 }
@@ -17,7 +17,11 @@
     License along with Donet. If not, see <https://www.gnu.org/licenses/>.
 */
 
+use donet_core::globals::{DoId, INVALID_DOID};
 use serde::Deserialize;
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+use toml::Table;
 
 #[derive(Deserialize, PartialEq, Debug, Clone)]
 pub struct DonetConfig {
@@ -26,11 +30,55 @@ pub struct DonetConfig {
     pub services: Services,
 }
 
+impl DonetConfig {
+    /// Reads a configuration from several TOML files, where each file
+    /// is overlaid on top of the files before it.
+    ///
+    /// Tables are merged key by key, so a later file only needs to hold
+    /// the values it overrides, such as a `prod.toml` layered on top of
+    /// a `base.toml`. Any other value, including arrays, is replaced.
+    pub fn from_layers(paths: &[&Path]) -> Result<Self> {
+        let mut merged: Table = Table::new();
+
+        for path in paths {
+            let contents: String = std::fs::read_to_string(path)?;
+
+            let layer: Table = toml::from_str(&contents).map_err(|err| {
+                Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("{}: {}", path.display(), err.message()),
+                )
+            })?;
+            merge_tables(&mut merged, layer);
+        }
+
+        toml::Value::Table(merged)
+            .try_into()
+            .map_err(|err: toml::de::Error| Error::new(ErrorKind::InvalidInput, err.message().to_owned()))
+    }
+}
+
+/// Deep-merges the `overlay` table into `base`.
+fn merge_tables(base: &mut Table, overlay: Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base_table)), toml::Value::Table(overlay_table)) => {
+                merge_tables(base_table, overlay_table);
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
 #[derive(Deserialize, PartialEq, Debug, Clone)]
 pub struct Daemon {
     pub name: String,
     pub id: Option<u32>,
     pub log_level: Option<String>,
+    pub thread_name: Option<String>,    // default: "donet-worker"
+    pub restart_on_panic: Option<bool>, // default: false
 }
 
 #[derive(Deserialize, PartialEq, Debug, Clone)]
@@ -41,6 +89,49 @@ pub struct Global {
     pub dc_multiple_inheritance: Option<bool>,
     pub dc_sort_inheritance_by_file: Option<bool>,
     pub dc_virtual_inheritance: Option<bool>,
+    pub dc_hash_compatibility: Option<HashCompatibility>, // default: panda3d
+    pub uberdogs: Option<Vec<UberDOG>>,                   // default: none
+}
+
+/// A well-known singleton Distributed Object with a fixed DoId, which
+/// exists without being created, so it can be sent to immediately.
+#[derive(Deserialize, PartialEq, Debug, Clone)]
+pub struct UberDOG {
+    pub id: DoId,
+    pub class: String,
+    pub anonymous: Option<bool>, // default: false
+}
+
+impl Global {
+    /// Returns the UberDOG configured with the given DoId, if any.
+    pub fn get_uberdog(&self, id: DoId) -> Option<&UberDOG> {
+        self.uberdogs.iter().flatten().find(|uberdog| uberdog.id == id)
+    }
+
+    /// Checks that every UberDOG has a valid DoId, that is not
+    /// shared with any other UberDOG.
+    pub fn check_uberdogs(&self) -> Result<()> {
+        let uberdogs: &[UberDOG] = self.uberdogs.as_deref().unwrap_or_default();
+
+        for (i, uberdog) in uberdogs.iter().enumerate() {
+            if uberdog.id == INVALID_DOID {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("UberDOG {} has the invalid DoId {}.", uberdog.class, uberdog.id),
+                ));
+            }
+            if let Some(other) = uberdogs[..i].iter().find(|other| other.id == uberdog.id) {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "UberDOGs {} and {} are both configured with DoId {}.",
+                        other.class, uberdog.class, uberdog.id
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
 }
 
 #[derive(Deserialize, PartialEq, Debug, Clone)]
@@ -62,8 +153,18 @@ pub struct ClientAgent {
 
 #[derive(Deserialize, PartialEq, Debug, Clone)]
 pub struct MessageDirector {
-    pub bind: String,             // '<host>:<port>'
-    pub upstream: Option<String>, // '<host>:<port>'
+    pub bind: String,                        // '<host>:<port>' or 'unix:<path>'
+    pub upstream: Option<String>,            // '<host>:<port>' or 'unix:<path>'
+    pub framing: Option<u8>,                 // 16 or 32 (bits), default: 16
+    pub write_timeout: Option<u64>,          // seconds, default: none
+    pub max_pending_bytes: Option<u64>,      // bytes, default: none
+    pub idle_timeout: Option<u64>,           // seconds, default: none
+    pub keepalive: Option<u64>,              // seconds, default: none
+    pub drain_timeout: Option<u64>,          // seconds, default: 30
+    pub shutdown_timeout: Option<u64>,       // seconds, default: 5
+    pub record: Option<String>,              // file path, default: none
+    pub watchdog_timeout: Option<u64>,       // seconds, default: none
+    pub max_reconnect_attempts: Option<u32>, // default: unlimited
 }
 
 #[derive(Deserialize, PartialEq, Debug, Clone)]
@@ -97,10 +198,32 @@ pub struct DBSS {
 
 #[derive(Deserialize, PartialEq, Debug, Clone)]
 pub struct EventLogger {
-    pub bind: String,            // '<host>:<port>'
-    pub output: String,          // path, relative to fs root
-    pub log_format: String,      // e.g. "el-%Y-%m-%d-%H-%M-%S.log"
-    pub rotate_interval: String, // e.g. "1d"
+    pub bind: String,                      // '<host>:<port>'
+    pub output: String,                    // path, relative to fs root
+    pub log_format: String,                // e.g. "el-%Y-%m-%d-%H-%M-%S.log"
+    pub rotate_interval: String,           // e.g. "1d"
+    pub flush_interval: Option<u64>,       // milliseconds, default: written as received
+    pub flush_events: Option<Vec<String>>, // event types, default: none
+}
+
+/// Which implementation the legacy hash of the DC files must match.
+/// See libdonet's dconfig.rs for how they differ.
+#[derive(Deserialize, PartialEq, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum HashCompatibility {
+    #[default]
+    Panda3D,
+    Astron,
+}
+
+#[cfg(feature = "requires_dc")]
+impl From<HashCompatibility> for donet_core::dconfig::HashCompatibility {
+    fn from(value: HashCompatibility) -> Self {
+        match value {
+            HashCompatibility::Panda3D => Self::Panda3D,
+            HashCompatibility::Astron => Self::Astron,
+        }
+    }
 }
 
 /// Creates a donet-core `DCFileConfig` struct from [`DonetConfig`].
@@ -123,6 +246,122 @@ impl From<DonetConfig> for donet_core::dconfig::DCFileConfig {
             .global
             .dc_virtual_inheritance
             .unwrap_or(this.dc_virtual_inheritance);
+
+        if let Some(compatibility) = value.global.dc_hash_compatibility {
+            this.dc_hash_compatibility = compatibility.into();
+        }
         this
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn override_layer_keeps_base_values() -> Result<()> {
+        let base_path = std::env::temp_dir().join("donet_config_base_test.toml");
+        let prod_path = std::env::temp_dir().join("donet_config_prod_test.toml");
+
+        std::fs::write(
+            &base_path,
+            r#"
+            [daemon]
+            name = "Base Daemon"
+            log_level = "info"
+
+            [global]
+            dc_files = ["base.dc"]
+
+            [services.database_server]
+            control_channel = 75757
+            db_backend = "mysql"
+
+            [services.database_server.sql]
+            host = "127.0.0.1:3306"
+            user = "donet"
+            pass = "donet"
+            database = "donet"
+            "#,
+        )?;
+        std::fs::write(
+            &prod_path,
+            r#"
+            [services.database_server.sql]
+            host = "db.example.com:3306"
+            "#,
+        )?;
+
+        let conf: DonetConfig = DonetConfig::from_layers(&[&base_path, &prod_path])?;
+        let db: DBServer = conf.services.database_server.expect("DB server config missing.");
+        let sql: SQL = db.sql.expect("SQL config missing.");
+
+        assert_eq!(sql.host, "db.example.com:3306");
+        assert_eq!(sql.user, "donet");
+        assert_eq!(sql.database, "donet");
+        assert_eq!(db.control_channel, 75757);
+        assert_eq!(conf.daemon.name, "Base Daemon");
+        assert_eq!(conf.daemon.log_level, Some("info".into()));
+        assert_eq!(conf.global.dc_files, vec!["base.dc".to_owned()]);
+        Ok(())
+    }
+
+    #[test]
+    fn uberdogs_by_doid() -> Result<()> {
+        let config = |uberdogs: &str| -> DonetConfig {
+            toml::from_str(&format!(
+                r#"
+                [daemon]
+                name = "UberDOG Daemon"
+
+                [global]
+                dc_files = ["game.dc"]
+                {}
+
+                [services]
+                "#,
+                uberdogs
+            ))
+            .unwrap()
+        };
+
+        let conf: DonetConfig = config(
+            r#"uberdogs = [
+                { id = 4665, class = "AuthManager", anonymous = true },
+                { id = 4666, class = "ChatManager" },
+            ]"#,
+        );
+        conf.global.check_uberdogs()?;
+
+        let auth: &UberDOG = conf.global.get_uberdog(4665).expect("UberDOG missing.");
+
+        assert_eq!(auth.class, "AuthManager");
+        assert_eq!(auth.anonymous, Some(true));
+        assert_eq!(conf.global.get_uberdog(4666).unwrap().anonymous, None);
+        assert!(conf.global.get_uberdog(4667).is_none());
+
+        // no UberDOGs configured at all
+        let conf: DonetConfig = config("");
+
+        assert!(conf.global.check_uberdogs().is_ok());
+        assert!(conf.global.get_uberdog(4665).is_none());
+
+        let conf: DonetConfig = config(
+            r#"uberdogs = [
+                { id = 4665, class = "AuthManager" },
+                { id = 4665, class = "ChatManager" },
+            ]"#,
+        );
+        let err: Error = conf.global.check_uberdogs().unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        assert_eq!(
+            err.to_string(),
+            "UberDOGs AuthManager and ChatManager are both configured with DoId 4665."
+        );
+
+        let conf: DonetConfig = config(r#"uberdogs = [{ id = 0, class = "AuthManager" }]"#);
+        assert!(conf.global.check_uberdogs().is_err());
+        Ok(())
+    }
+}
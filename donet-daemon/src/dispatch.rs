@@ -0,0 +1,157 @@
+/*
+    This file is part of Donet.
+
+    Copyright © 2024 Max Rodriguez <me@maxrdz.com>
+
+    Donet is free software; you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License,
+    as published by the Free Software Foundation, either version 3
+    of the License, or (at your option) any later version.
+
+    Donet is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public
+    License along with Donet. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Dispatching of received messages to handlers registered
+//! per message type, instead of matching on every message type.
+
+use donet_core::globals::MsgType;
+use donet_core::Protocol;
+use donet_network::RecvData;
+use log::warn;
+use std::collections::HashMap;
+use std::future::Future;
+use std::io::{Error, ErrorKind, Result};
+use std::pin::Pin;
+
+/// Future returned by [`MessageHandler::handle`].
+pub type HandlerFuture<'a> = Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>>;
+
+/// Handles every received message of the message type(s) that
+/// it is registered for in a [`DispatchTable`].
+///
+/// `S` is the service state that the handler is given mutable
+/// access to while handling a message.
+pub trait MessageHandler<S>: Send + Sync {
+    /// Handles a message, whose datagram iterator has already
+    /// read the message type, and is positioned at its arguments.
+    fn handle<'a>(&'a self, service: &'a mut S, data: RecvData) -> HandlerFuture<'a>;
+}
+
+/// Table of the message handlers of a service, by message type.
+pub struct DispatchTable<S> {
+    handlers: HashMap<MsgType, Box<dyn MessageHandler<S>>>,
+}
+
+impl<S> Default for DispatchTable<S> {
+    fn default() -> Self {
+        Self {
+            handlers: HashMap::default(),
+        }
+    }
+}
+
+impl<S: Send> DispatchTable<S> {
+    /// Registers the handler for the given message type, replacing
+    /// the handler previously registered for it, if any.
+    pub fn register(&mut self, msg_type: Protocol, handler: impl MessageHandler<S> + 'static) {
+        self.handlers.insert(msg_type.into(), Box::new(handler));
+    }
+
+    pub fn is_handled(&self, msg_type: Protocol) -> bool {
+        self.handlers.contains_key(&MsgType::from(msg_type))
+    }
+
+    /// Reads the message type of the given message, and hands the
+    /// message to the handler registered for it.
+    ///
+    /// The datagram iterator must be positioned at the message type,
+    /// e.g. after reading the recipients and sender of an internal
+    /// message. Messages with no registered handler are logged, and
+    /// an error of kind [`ErrorKind::Unsupported`] is returned.
+    pub async fn dispatch(&self, service: &mut S, mut data: RecvData) -> Result<()> {
+        let msg_type: Protocol = data.dgi.read_msg_type()?;
+
+        match self.handlers.get(&MsgType::from(msg_type)) {
+            Some(handler) => handler.handle(service, data).await,
+            None => {
+                warn!(
+                    "Received unhandled message type {:?} from {}.",
+                    msg_type, data.remote
+                );
+
+                Err(Error::new(
+                    ErrorKind::Unsupported,
+                    format!("No handler is registered for message type {:?}.", msg_type),
+                ))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use donet_core::datagram::datagram::Datagram;
+    use donet_core::globals::Channel;
+    use std::net::SocketAddr;
+
+    #[derive(Default)]
+    struct Subscriptions {
+        channels: Vec<Channel>,
+    }
+
+    struct AddChannel;
+
+    impl MessageHandler<Subscriptions> for AddChannel {
+        fn handle<'a>(&'a self, service: &'a mut Subscriptions, mut data: RecvData) -> HandlerFuture<'a> {
+            Box::pin(async move {
+                service.channels.push(data.dgi.read_channel()?);
+                Ok(())
+            })
+        }
+    }
+
+    fn control_message(msg_type: Protocol, channel: Channel) -> RecvData {
+        let mut dg: Datagram = Datagram::default();
+
+        dg.add_u16(msg_type.into()).unwrap();
+        dg.add_channel(channel).unwrap();
+
+        RecvData {
+            remote: SocketAddr::from(([127, 0, 0, 1], 7199)),
+            dg: dg.clone(),
+            dgi: dg.into(),
+        }
+    }
+
+    #[tokio::test]
+    async fn dispatch_registered_and_unhandled() -> Result<()> {
+        let mut table: DispatchTable<Subscriptions> = DispatchTable::default();
+        let mut service: Subscriptions = Subscriptions::default();
+
+        table.register(Protocol::MDAddChannel, AddChannel);
+
+        assert!(table.is_handled(Protocol::MDAddChannel));
+        assert!(!table.is_handled(Protocol::MDRemoveChannel));
+
+        table
+            .dispatch(&mut service, control_message(Protocol::MDAddChannel, 5000))
+            .await?;
+        assert_eq!(service.channels, vec![5000]);
+
+        let err: Error = table
+            .dispatch(&mut service, control_message(Protocol::MDRemoveChannel, 5000))
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::Unsupported);
+        assert_eq!(service.channels, vec![5000]);
+        Ok(())
+    }
+}
@@ -21,6 +21,7 @@
 extern crate cfg_if;
 
 pub mod config;
+pub mod dispatch;
 pub mod event;
 pub mod logger;
 pub mod meson;
@@ -21,12 +21,13 @@ use crate::config;
 use std::future::Future;
 use std::io::Result;
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, Notify};
 use tokio::task::JoinHandle;
 
 cfg_if! {
     if #[cfg(feature = "requires_dc")] {
         pub use donet_core::dcfile::DCFile;
+        use std::io::{Error, ErrorKind};
     } else {
         /// Dummy DCFile struct for the [`DonetService`] trait
         /// to use on builds that do not require the DC file.
@@ -40,6 +41,31 @@ cfg_if! {
     }
 }
 
+/// The DC file is parsed only once on daemon startup, and every
+/// service is handed a pointer to the same [`DCFile`] instance.
+pub type SharedDCFile = Arc<DCFile<'static>>;
+
+/// Parses the DC files listed in the daemon configuration
+/// into a [`SharedDCFile`], to be shared by all services.
+#[cfg(feature = "requires_dc")]
+pub fn load_dc_files(conf: &config::DonetConfig) -> Result<SharedDCFile> {
+    use donet_core::dconfig::DCFileConfig;
+
+    let dc_config: DCFileConfig = conf.clone().into();
+    let files: Vec<String> = conf.global.dc_files.clone();
+
+    match donet_core::read_dc_files(dc_config, files) {
+        Ok(dc) => {
+            log::info!("Loaded DC file(s) with hash {}.", dc.get_pretty_hash());
+            Ok(Arc::new(dc))
+        }
+        Err(dc_err) => Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Failed to parse DC file(s): {}", dc_err),
+        )),
+    }
+}
+
 /// Must be implemented by all Donet services in order to be
 /// bootstrapped on daemon startup using this daemon's configuration.
 ///
@@ -51,14 +77,17 @@ pub trait DonetService {
     type Service;
     type Configuration;
 
+    /// Human readable name of the service, used for logging.
+    const NAME: &'static str;
+
     fn create(
         conf: Self::Configuration,
-        dc: Option<DCFile<'static>>,
+        dc: Option<SharedDCFile>,
     ) -> impl Future<Output = Result<Arc<Mutex<Self::Service>>>> + Send;
 
     fn start(
         conf: config::DonetConfig,
-        dc: Option<DCFile<'static>>,
+        dc: Option<SharedDCFile>,
     ) -> impl Future<Output = Result<JoinHandle<Result<()>>>> + Send;
 
     /// This service's main asynchronous loop.
@@ -66,16 +95,144 @@ pub trait DonetService {
 
     /// Spawns a new Tokio asynchronous task that executes the given
     /// async function, and returns its Tokio join handle.
+    ///
+    /// The task is named after the service, which is logged by the
+    /// daemon's panic hook if the task, or a task it spawns with
+    /// [`spawn_in_service`], panics.
     fn spawn_async_task(
         service_loop: impl Future<Output = Result<()>> + Send + 'static,
     ) -> JoinHandle<Result<()>> {
         // Hack to reassure the compiler that we want to return an IO result.
         set_future_return_type::<Result<()>, _>(&service_loop);
 
-        tokio::task::spawn(service_loop)
+        tokio::task::spawn(SERVICE_NAME.scope(Self::NAME, service_loop))
     }
 }
 
+tokio::task_local! {
+    /// Name of the service whose task is being polled.
+    static SERVICE_NAME: &'static str;
+}
+
+/// Returns the name of the service whose task is running on this
+/// thread, or `None` if called outside of a service's tasks.
+pub fn current_service() -> Option<&'static str> {
+    SERVICE_NAME.try_with(|name| *name).ok()
+}
+
+/// Spawns a new Tokio asynchronous task, like [`tokio::task::spawn`].
+///
+/// If spawned from a task of a service, the new task carries the name
+/// of the service, so that a panic in the task is logged as a panic
+/// of the service. Services spawn all of their tasks with this.
+pub fn spawn_in_service<F>(task: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    match current_service() {
+        Some(name) => tokio::task::spawn(SERVICE_NAME.scope(name, task)),
+        None => tokio::task::spawn(task),
+    }
+}
+
+/// Installs a panic hook that logs every panic as an error, along
+/// with the name of the service that panicked, if it was one of a
+/// service's tasks. Logged messages are passed to `log`.
+pub fn install_panic_hook(log: impl Fn(String) + Send + Sync + 'static) {
+    std::panic::set_hook(Box::new(move |info| {
+        let thread = std::thread::current();
+        let thread_name: &str = thread.name().unwrap_or("<unnamed>");
+
+        let location: String = match info.location() {
+            Some(location) => location.to_string(),
+            None => "<unknown>".to_owned(),
+        };
+        let context: String = match current_service() {
+            Some(service) => format!("The {} service", service),
+            None => "A task".to_owned(),
+        };
+        log(format!(
+            "{} panicked on thread '{}' at {}: {}",
+            context,
+            thread_name,
+            location,
+            panic_message(info.payload())
+        ));
+    }));
+}
+
+/// Returns the message that a panic was raised with.
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&'static str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "<non-string panic payload>"
+    }
+}
+
+/// Awaits the main task of a started service, returning what the
+/// service returned once it exits on its own.
+///
+/// If the task panics and `restart` is set, which should only be set
+/// for non-essential services, the service is started again with
+/// `start`. Otherwise, or if the service fails to start again,
+/// `panicked` is notified, so that the daemon can exit cleanly instead
+/// of running without the service, and an error is returned.
+///
+/// Aborting the returned task also aborts the service's main task.
+pub fn supervise<S, F>(
+    name: &'static str,
+    handle: JoinHandle<Result<()>>,
+    restart: bool,
+    panicked: Arc<Notify>,
+    mut start: S,
+) -> JoinHandle<Result<()>>
+where
+    S: FnMut() -> F + Send + 'static,
+    F: Future<Output = Result<JoinHandle<Result<()>>>> + Send,
+{
+    use std::io::{Error, ErrorKind};
+
+    /// Aborts the service's main task when the supervisor is aborted.
+    struct AbortOnDrop(JoinHandle<Result<()>>);
+
+    impl Drop for AbortOnDrop {
+        fn drop(&mut self) {
+            self.0.abort();
+        }
+    }
+
+    tokio::task::spawn(async move {
+        let mut task: AbortOnDrop = AbortOnDrop(handle);
+
+        loop {
+            match (&mut task.0).await {
+                Ok(result) => return result,
+                Err(err) if err.is_panic() && restart => {
+                    log::warn!("Restarting the {} service after it panicked.", name);
+
+                    match start().await {
+                        Ok(handle) => task = AbortOnDrop(handle),
+                        Err(err) => {
+                            panicked.notify_one();
+                            return Err(err);
+                        }
+                    }
+                }
+                Err(err) if err.is_panic() => {
+                    panicked.notify_one();
+
+                    return Err(Error::other(format!("The {} service panicked.", name)));
+                }
+                Err(err) => return Err(Error::new(ErrorKind::Interrupted, err)),
+            }
+        }
+    })
+}
+
 /// Hack to reassure the compiler the result type of a future.
 pub fn set_future_return_type<T, F: Future<Output = T>>(_arg: &F) {}
 
@@ -84,6 +241,164 @@ mod tests {
     use super::set_future_return_type;
     use std::io::Result;
 
+    #[test]
+    #[cfg(feature = "requires_dc")]
+    fn dc_files_loaded_from_config() -> Result<()> {
+        use super::*;
+        use crate::config::*;
+
+        // unique to this process, so parallel test runs do not collide
+        let dc_path = std::env::temp_dir().join(format!("donet_shared_dc_test_{}.dc", std::process::id()));
+        std::fs::write(
+            &dc_path,
+            "keyword required;\nfrom views import DistributedDonut\n",
+        )?;
+
+        let conf: DonetConfig = DonetConfig {
+            daemon: Daemon {
+                name: "Test Daemon".into(),
+                id: None,
+                log_level: None,
+                thread_name: None,
+                restart_on_panic: None,
+            },
+            global: Global {
+                eventlogger: None,
+                dc_files: vec![dc_path.to_string_lossy().into_owned()],
+                dc_multiple_inheritance: None,
+                dc_sort_inheritance_by_file: None,
+                dc_virtual_inheritance: None,
+                dc_hash_compatibility: None,
+                uberdogs: None,
+            },
+            services: Services {
+                client_agent: None,
+                message_director: None,
+                state_server: None,
+                database_server: None,
+                dbss: None,
+                event_logger: None,
+            },
+        };
+
+        let dc: SharedDCFile = load_dc_files(&conf)?;
+
+        // parsed with the DC file config of the daemon
+        let expected: DCFile = donet_core::read_dc_files(conf.clone().into(), conf.global.dc_files.clone())
+            .expect("Failed to parse DC file.");
+
+        assert_eq!(dc.listing(), expected.listing());
+        assert_eq!(dc.get_legacy_hash(), expected.get_legacy_hash());
+        assert_eq!(dc.get_num_imports(), 1);
+        assert_eq!(
+            dc.get_python_import(0).symbols,
+            vec!["DistributedDonut".to_owned()]
+        );
+
+        std::fs::remove_file(&dc_path)?;
+
+        let err: Error = load_dc_files(&conf).err().expect("Loaded a missing DC file.");
+
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        assert!(err.to_string().starts_with("Failed to parse DC file(s): "));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn panicked_service_is_logged() {
+        use super::*;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        /// Stub service whose main task panics the first time it runs.
+        struct PanickingService;
+
+        static RUNS: AtomicUsize = AtomicUsize::new(0);
+
+        impl DonetService for PanickingService {
+            type Service = ();
+            type Configuration = ();
+
+            const NAME: &'static str = "Panicking Stub";
+
+            async fn create(_: (), _: Option<SharedDCFile>) -> Result<Arc<Mutex<()>>> {
+                Ok(Arc::new(Mutex::new(())))
+            }
+
+            async fn start(
+                _: config::DonetConfig,
+                _: Option<SharedDCFile>,
+            ) -> Result<JoinHandle<Result<()>>> {
+                let service = Self::create((), None).await?;
+                Ok(Self::spawn_async_task(Self::main(service)))
+            }
+
+            async fn main(_: Arc<Mutex<()>>) -> Result<()> {
+                if RUNS.fetch_add(1, Ordering::SeqCst) == 0 {
+                    let subtask = spawn_in_service(async { panic!("injected subtask panic") });
+
+                    assert!(subtask.await.unwrap_err().is_panic());
+                    panic!("injected panic");
+                }
+                Ok(())
+            }
+        }
+
+        let start = || async {
+            let service = PanickingService::create((), None).await?;
+            Ok(PanickingService::spawn_async_task(PanickingService::main(
+                service,
+            )))
+        };
+
+        let logged: Arc<std::sync::Mutex<Vec<String>>> = Arc::default();
+        let log_sink = logged.clone();
+        install_panic_hook(move |message| log_sink.lock().unwrap().push(message));
+
+        // Not restarted; the daemon is told that the service panicked.
+        let panicked: Arc<Notify> = Arc::new(Notify::new());
+        let supervisor = supervise(
+            PanickingService::NAME,
+            start().await.unwrap(),
+            false,
+            panicked.clone(),
+            start,
+        );
+        let err = supervisor.await.unwrap().unwrap_err();
+
+        assert_eq!(err.to_string(), "The Panicking Stub service panicked.");
+        panicked.notified().await;
+
+        // Restarted; the second run exits on its own.
+        RUNS.store(0, Ordering::SeqCst);
+
+        let supervisor = supervise(
+            PanickingService::NAME,
+            start().await.unwrap(),
+            true,
+            panicked.clone(),
+            start,
+        );
+        supervisor.await.unwrap().unwrap();
+        assert_eq!(RUNS.load(Ordering::SeqCst), 2);
+
+        drop(std::panic::take_hook());
+
+        let logged = logged.lock().unwrap();
+        assert_eq!(logged.len(), 4);
+
+        // the subtask panics first, then the main task, on both runs
+        let panics = ["injected subtask panic", "injected panic"].repeat(2);
+
+        for (message, panic) in logged.iter().zip(panics) {
+            assert!(
+                message.starts_with("The Panicking Stub service panicked on thread "),
+                "{}",
+                message
+            );
+            assert!(message.ends_with(&format!(": {}", panic)), "{}", message);
+        }
+    }
+
     #[test]
     fn test_future_return_type_util() {
         let test_future = async move {
@@ -33,7 +33,7 @@ pub type SqlResult = std::result::Result<(), Box<dyn std::error::Error>>;
 
 pub struct DBCredentials {
     pub host: String,
-    pub port: i16,
+    pub port: u16,
     pub database: String,
     pub user: String,
     pub password: String,
@@ -65,7 +65,7 @@ struct Field {
 }
 
 pub struct DatabaseServer {
-    dc_file: DCFile<'static>,
+    dc_file: SharedDCFile,
     _sql_pool: Pool,
     sql_conn: PooledConn,
     _credentials: DBCredentials,
@@ -75,9 +75,11 @@ impl DonetService for DatabaseServer {
     type Service = Self;
     type Configuration = config::DBServer;
 
+    const NAME: &'static str = "Database Server";
+
     async fn create(
         conf: Self::Configuration,
-        dc: Option<DCFile<'static>>,
+        dc: Option<SharedDCFile>,
     ) -> Result<Arc<Mutex<Self::Service>>> {
         // TODO: Check for db backend type once we have multiple DB backend support.
         let sql_config: config::SQL;
@@ -96,7 +98,7 @@ impl DonetService for DatabaseServer {
 
         let creds: DBCredentials = DBCredentials {
             host: host_port[1].to_owned(),
-            port: host_port[0].parse::<i16>().unwrap(),
+            port: host_port[0].parse::<u16>().unwrap(),
             database: sql_config.database.to_owned(),
             user: sql_config.user.to_owned(),
             password: sql_config.pass.to_owned(),
@@ -156,7 +158,7 @@ impl DonetService for DatabaseServer {
         })))
     }
 
-    async fn start(conf: config::DonetConfig, dc: Option<DCFile<'static>>) -> Result<JoinHandle<Result<()>>> {
+    async fn start(conf: config::DonetConfig, dc: Option<SharedDCFile>) -> Result<JoinHandle<Result<()>>> {
         // NOTE: We are unwrapping an Option without checking,
         // as this method can only be called if 'database_server'
         // is of a 'Some' type, which guarantees no panic scenario.
@@ -28,13 +28,14 @@ use donet_daemon::service::*;
 use donet_network::udp;
 use log::{debug, error, info, trace};
 use regex::Regex;
-use std::io::{Error, ErrorKind, Result};
+use std::io::{Error, ErrorKind, Result, Write};
 use std::sync::Arc;
 use std::time::SystemTime;
 use tokio::fs::File;
 use tokio::io::AsyncWriteExt;
 use tokio::sync::Mutex;
 use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
 
 /// Interval unit types for log rotation intervals.
 #[derive(Debug, PartialEq, Eq)]
@@ -56,18 +57,35 @@ pub struct EventLogger {
     binding: udp::Socket,
     log_format: String,
     log_file: Arc<Mutex<Option<File>>>,
+    log_path: Option<String>,
     rotation_interval: Interval,
     next_rotation: i64, // unix timestamp
+    /// If set, events are buffered, and written out and synced to
+    /// disk once every interval, instead of as they are received.
+    flush_interval: Option<std::time::Duration>,
+    /// Event types that are written out and synced to disk as soon
+    /// as they are received, whether or not events are buffered.
+    flush_events: Vec<String>,
+    pending: String,
 }
 
 impl DonetService for EventLogger {
     type Service = Self;
     type Configuration = config::EventLogger;
 
+    const NAME: &'static str = "Event Logger";
+
     async fn create(
         mut conf: Self::Configuration,
-        _: Option<DCFile<'static>>,
+        _: Option<SharedDCFile>,
     ) -> Result<Arc<Mutex<Self::Service>>> {
+        if conf.flush_interval == Some(0) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Event Logger flush interval cannot be zero.",
+            ));
+        }
+
         Ok(Arc::new(Mutex::new(Self {
             binding: udp::Socket::bind(&conf.bind).await?,
             log_format: {
@@ -78,12 +96,16 @@ impl DonetService for EventLogger {
                 format!("{}{}", conf.output, conf.log_format)
             },
             log_file: Arc::new(Mutex::new(None)),
+            log_path: None,
             rotation_interval: Self::str_to_interval(&conf.rotate_interval),
             next_rotation: 0_i64, // set once first log opened
+            flush_interval: conf.flush_interval.map(std::time::Duration::from_millis),
+            flush_events: conf.flush_events.unwrap_or_default(),
+            pending: String::default(),
         })))
     }
 
-    async fn start(conf: config::DonetConfig, _: Option<DCFile<'static>>) -> Result<JoinHandle<Result<()>>> {
+    async fn start(conf: config::DonetConfig, _: Option<SharedDCFile>) -> Result<JoinHandle<Result<()>>> {
         // We can unwrap safely here since this function only is called if it is `Some`.
         let service_conf = conf.services.event_logger.unwrap();
 
@@ -121,8 +143,25 @@ impl DonetService for EventLogger {
                 .expect("Failed to process log opened event!");
         }
 
+        let buffered: bool = service_lock.flush_interval.is_some();
+
+        // The period does not matter if events are not buffered, as
+        // the flush timer is then never polled.
+        let mut flush_timer = tokio::time::interval(
+            service_lock
+                .flush_interval
+                .unwrap_or(std::time::Duration::from_secs(1)),
+        );
+        flush_timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
         loop {
-            let (len, addr) = service_lock.binding.socket.recv_from(&mut buffer).await?;
+            let (len, addr) = tokio::select! {
+                res = service_lock.binding.socket.recv_from(&mut buffer) => res?,
+                _ = flush_timer.tick(), if buffered => {
+                    service_lock.flush_log().await?;
+                    continue;
+                }
+            };
             trace!("Got packet from {}.", addr);
 
             dg = Datagram::default();
@@ -161,7 +200,7 @@ impl EventLogger {
     ///
     /// [`MessagePack`]: https://msgpack.org
     async fn process_datagram(
-        &self,
+        &mut self,
         addr: core::net::SocketAddr,
         data: &mut String,
         dgi: &mut DatagramIterator,
@@ -169,6 +208,9 @@ impl EventLogger {
         // new datagram being processed, clear previous data
         data.clear();
 
+        let flush_now: bool =
+            msgpack::peek_event_type(dgi).is_some_and(|event_type| self.flush_events.contains(&event_type));
+
         msgpack::decode_to_json(data, dgi)?;
 
         // Verify the msgpack contains a Map from the beginning.
@@ -196,15 +238,45 @@ impl EventLogger {
             &format!("{}", date.format("\"_time\": \"%Y-%m-%d %H:%M:%S%z\", ")),
         );
 
+        data.push('\n');
+
+        if flush_now || self.flush_interval.is_some() {
+            self.pending.push_str(data);
+
+            if flush_now {
+                self.flush_log().await?;
+            }
+            return Ok(());
+        }
         let mut guard = self.log_file.lock().await;
         let file = guard.as_mut().unwrap();
 
-        data.push('\n');
         file.write_all(data.as_bytes()).await?;
 
         Ok(())
     }
 
+    /// Writes out the buffered events to the current log file,
+    /// and waits for them to be synced to disk.
+    async fn flush_log(&mut self) -> Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let mut guard = self.log_file.lock().await;
+
+        let Some(file) = guard.as_mut() else {
+            return Ok(()); // no log opened yet; keep the events buffered
+        };
+        let pending: String = std::mem::take(&mut self.pending);
+
+        file.write_all(pending.as_bytes()).await?;
+        file.flush().await?;
+        file.sync_data().await?;
+
+        trace!("Flushed {} bytes of events to the log.", pending.len());
+        Ok(())
+    }
+
     /// Opens a new log file on disk once any writes to the current log
     /// file are finished, and creates a next log rotation timestamp.
     async fn open_log(&mut self) -> Result<()> {
@@ -216,6 +288,9 @@ impl EventLogger {
 
         debug!("New log filename: {}", filename);
 
+        // Buffered events belong to the log that is being closed.
+        self.flush_log().await?;
+
         {
             let mut file_guard = self.log_file.lock().await;
 
@@ -226,7 +301,8 @@ impl EventLogger {
             }
         }
 
-        let new_log: File = File::create_new(filename).await?;
+        let new_log: File = File::create_new(&filename).await?;
+        self.log_path = Some(filename);
 
         let mut file_guard = self.log_file.lock().await;
         file_guard.replace(new_log); // replace `None` with new log file
@@ -319,9 +395,76 @@ impl EventLogger {
     }
 }
 
+impl Drop for EventLogger {
+    /// The Event Logger's task is aborted when the daemon shuts down,
+    /// so any events that are still buffered are written out here.
+    fn drop(&mut self) {
+        let Some(path) = self.log_path.as_ref() else {
+            return;
+        };
+        if self.pending.is_empty() {
+            return;
+        }
+        let res = std::fs::OpenOptions::new()
+            .append(true)
+            .open(path)
+            .and_then(|mut file| {
+                file.write_all(self.pending.as_bytes())?;
+                file.sync_data()
+            });
+
+        if let Err(err) = res {
+            error!("Failed to flush buffered events to {}: {}", path, err);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::{EventLogger, Interval, IntervalUnit};
+    use donet_daemon::config;
+    use donet_daemon::event::LoggedEvent;
+    use donet_daemon::service::DonetService;
+    use std::net::SocketAddr;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    fn test_config(name: &str, flush_interval: u64) -> (config::EventLogger, PathBuf) {
+        let log_format: String = format!("donet-el-{}-{}.log", name, std::process::id());
+        let path: PathBuf = std::env::temp_dir().join(&log_format);
+
+        // The log is created with `File::create_new`, so remove any
+        // log left behind by a previous run.
+        let _ = std::fs::remove_file(&path);
+
+        let conf = config::EventLogger {
+            bind: "127.0.0.1:0".into(),
+            output: std::env::temp_dir().to_string_lossy().into_owned(),
+            log_format,
+            rotate_interval: "1d".into(),
+            flush_interval: Some(flush_interval),
+            flush_events: Some(vec!["critical".into()]),
+        };
+        (conf, path)
+    }
+
+    fn send_event(to: SocketAddr, event_type: &str, msg: &str) {
+        let mut event = LoggedEvent::new(event_type, "test");
+        event.add("msg", msg);
+
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.send_to(&event.make_datagram().get_data(), to).unwrap();
+    }
+
+    async fn wait_for_log(path: &PathBuf, msg: &str) -> bool {
+        for _ in 0..100 {
+            if std::fs::read_to_string(path).is_ok_and(|log| log.contains(msg)) {
+                return true;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        false
+    }
 
     #[test]
     fn str_to_interval() {
@@ -345,4 +488,51 @@ mod tests {
         let _: Interval = EventLogger::str_to_interval("-1d");
         _ = EventLogger::str_to_interval("0d");
     }
+
+    #[tokio::test]
+    async fn events_persisted_after_flush_interval() {
+        let (conf, path) = test_config("flush-interval", 50);
+
+        let service = EventLogger::create(conf, None).await.unwrap();
+        let addr: SocketAddr = service.lock().await.binding.socket.local_addr().unwrap();
+
+        let handle = tokio::spawn(EventLogger::main(service));
+
+        send_event(addr, "unit", "Buffered Event");
+        assert!(wait_for_log(&path, "Buffered Event").await);
+
+        handle.abort();
+        let _ = handle.await;
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn shutdown_flushes_partial_buffer() {
+        // Long enough that the buffer is never flushed by the timer.
+        let (conf, path) = test_config("shutdown-flush", 3_600_000);
+
+        let service = EventLogger::create(conf, None).await.unwrap();
+        let addr: SocketAddr = service.lock().await.binding.socket.local_addr().unwrap();
+
+        let handle = tokio::spawn(EventLogger::main(service));
+
+        // Critical events skip the buffer.
+        send_event(addr, "critical", "Critical Event");
+        assert!(wait_for_log(&path, "Critical Event").await);
+
+        send_event(addr, "unit", "Pending Event");
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let log: String = std::fs::read_to_string(&path).unwrap();
+        assert!(!log.contains("Pending Event"));
+
+        // The daemon aborts the service's task on shutdown.
+        handle.abort();
+        assert!(handle.await.unwrap_err().is_cancelled());
+
+        let log: String = std::fs::read_to_string(&path).unwrap();
+        assert!(log.contains("Pending Event"));
+
+        std::fs::remove_file(&path).unwrap();
+    }
 }
@@ -158,11 +158,11 @@ pub fn decode_to_json(out: &mut String, dgi: &mut DatagramIterator) -> Result<()
     } else if marker == 0xca {
         // float32
         let data: u32 = dgi.read_u32()?;
-        out.push_str(&format!("{}", byte_order::swap_be_32(data) as f32));
+        out.push_str(&format!("{}", f32::from_bits(byte_order::swap_be_32(data))));
     } else if marker == 0xcb {
         // float64
         let data: u64 = dgi.read_u64()?;
-        out.push_str(&format!("{}", byte_order::swap_be_64(data) as f64));
+        out.push_str(&format!("{}", f64::from_bits(byte_order::swap_be_64(data))));
     } else if marker == 0xcc {
         // uint8
         out.push_str(&format!("{}", dgi.read_u8()?));
@@ -225,6 +225,45 @@ pub fn decode_to_json(out: &mut String, dgi: &mut DatagramIterator) -> Result<()
     Ok(())
 }
 
+/// Returns the value of the `type` key of a [`MsgPack`] map, if it is
+/// the first key of the map, as in events built with `LoggedEvent`.
+/// The iterator is left at the position it was given at.
+///
+/// [`MsgPack`]: https://msgpack.org
+pub fn peek_event_type(dgi: &mut DatagramIterator) -> Option<String> {
+    let start: usize = dgi.tell();
+    let event_type: Option<String> = read_event_type(dgi).ok().flatten();
+
+    dgi.seek(start);
+    event_type
+}
+
+fn read_event_type(dgi: &mut DatagramIterator) -> Result<Option<String>, IteratorError> {
+    let marker: u8 = dgi.read_u8()?;
+
+    if marker == 0xde {
+        dgi.skip(2)?; // map16
+    } else if marker == 0xdf {
+        dgi.skip(4)?; // map32
+    } else if !(0x80..=0x8f).contains(&marker) {
+        return Ok(None); // not a map
+    }
+
+    let mut key: String = String::default();
+    decode_to_json(&mut key, dgi)?;
+
+    if key != "\"type\"" {
+        return Ok(None);
+    }
+    let mut value: String = String::default();
+    decode_to_json(&mut value, dgi)?;
+
+    Ok(value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .map(str::to_owned))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -293,13 +332,13 @@ mod tests {
 
         dg.add_data(vec![0x90 + 0x2])?; // fixarray (2)
         dg.add_data(vec![0xca])?; // float32
-        dg.add_f32(f32::MAX)?; // value
+        dg.add_data(1.5_f32.to_be_bytes().to_vec())?; // value
         dg.add_data(vec![0xcb])?; // float64
-        dg.add_f64(f64::MAX)?; // value
+        dg.add_data((-0.25_f64).to_be_bytes().to_vec())?; // value
 
         decode_to_json(&mut output, &mut DatagramIterator::from(dg))?;
 
-        assert_eq!(output.as_str(), "[4294967300, 18446744073709552000]");
+        assert_eq!(output.as_str(), "[1.5, -0.25]");
         Ok(())
     }
 }
@@ -269,36 +269,24 @@ where
     async fn remove_subscriber(&mut self, sub: SubscriberRef, chan: Channel) -> bool {
         let map: &mut ChannelMap = self.get_channel_map();
 
-        let locked_sub: MutexGuard<'_, Subscriber> = sub.lock().await;
-        let mut sub_count: usize = map.subscriptions.len();
-
-        if sub_count == 0 {
+        let Some(subscriptions) = map.subscriptions.get_vec_mut(&chan) else {
             return false;
+        };
+        let sub_count: usize = subscriptions.len();
+
+        // Subscribers are compared by remote address, so we do not
+        // need to lock them, which may be locked by our caller.
+        subscriptions.retain(|subscription| *subscription != sub);
+
+        if subscriptions.len() == sub_count {
+            return false; // not subscribed to this channel
         }
-        for (key, subscriptions) in map.subscriptions.iter_all_mut() {
-            if *key != chan {
-                continue;
-            }
-            let mut index: usize = 0;
-            let mut found: bool = false;
-
-            for subscription in subscriptions.iter_mut() {
-                if *subscription.lock().await == *locked_sub {
-                    found = true;
-                    sub_count -= 1;
-                }
-                index += 1;
-            }
-            if found {
-                // The swap_remove() function requires a second mutable
-                // borrow of the vector which is illegal, so I had to work
-                // around this by using a `found` flag and performing the
-                // remove outside of the for loop which turns values into
-                // an iterator. This way we don't perform a second borrow.
-                subscriptions.swap_remove(index);
-            }
+        let was_last: bool = subscriptions.is_empty();
+
+        if was_last {
+            map.subscriptions.remove(&chan);
         }
-        sub_count == 0
+        was_last
     }
 
     /// Checks if a given subscriber has a subscription on the given
@@ -333,6 +321,22 @@ where
             }
         }
     }
+
+    /// Returns the subscribers for a list of channels, each only once,
+    /// in ascending order of their remote address.
+    ///
+    /// Subscribers are collected in a hash set, whose order changes
+    /// from run to run, so fan-out delivery uses this stable order
+    /// instead to be reproducible.
+    fn lookup_ordered(&mut self, channels: Vec<Channel>) -> Vec<SubscriberRef> {
+        #[allow(clippy::mutable_key_type)] // hashed by remote address only
+        let mut subs: HashSet<SubscriberRef> = HashSet::default();
+        self.lookup_channels(channels, &mut subs);
+
+        let mut subs: Vec<SubscriberRef> = subs.into_iter().collect();
+        subs.sort_unstable();
+        subs
+    }
 }
 
 #[cfg(test)]
@@ -414,4 +418,127 @@ mod tests {
         assert!(!mock.is_subscribed(&sub_lock, min - 1).await);
         assert!(!mock.is_subscribed(&sub_lock, max + 1).await);
     }
+
+    #[tokio::test]
+    async fn remove_one_of_many_subscribers() {
+        let mut mock = MockChannelCoordinator::default();
+        let mock_sub_1 = SubscriberRef::from(SocketAddr::from_str("127.0.0.1:1").unwrap());
+        let mock_sub_2 = SubscriberRef::from(SocketAddr::from_str("127.0.0.1:2").unwrap());
+
+        // many unrelated subscribers on other channels
+        for port in 1000_u16..2000 {
+            let sub = SubscriberRef::from(SocketAddr::from_str(&format!("127.0.0.2:{}", port)).unwrap());
+            mock.subscribe_channel(sub, Channel::from(port)).await;
+        }
+
+        mock.subscribe_channel(mock_sub_1.clone(), 4000).await;
+        mock.subscribe_channel(mock_sub_2.clone(), 4000).await;
+
+        // not subscribed to this channel
+        assert!(!mock.remove_subscriber(mock_sub_1.clone(), 1000).await);
+        assert_eq!(mock.map.subscriptions.get_vec(&1000).unwrap().len(), 1);
+
+        // the other subscriber of the channel is kept
+        assert!(!mock.remove_subscriber(mock_sub_2.clone(), 4000).await);
+        assert!(mock.map.subscriptions.get_vec(&4000) == Some(&vec![mock_sub_1.clone()]));
+
+        // the last subscriber of the channel removes it
+        assert!(mock.remove_subscriber(mock_sub_1.clone(), 4000).await);
+        assert!(!mock.map.subscriptions.contains_key(&4000));
+        assert_eq!(mock.map.subscriptions.len(), 1000);
+    }
+
+    #[tokio::test]
+    async fn subscriptions_dump() {
+        let mut mock = MockChannelCoordinator::default();
+        let mock_sub_1 = SubscriberRef::from(SocketAddr::from_str("127.0.0.1:1").unwrap());
+        let mock_sub_2 = SubscriberRef::from(SocketAddr::from_str("127.0.0.1:2").unwrap());
+
+        mock.subscribe_channel(mock_sub_1.clone(), 1000).await;
+        mock.subscribe_range(mock_sub_1.clone(), 5000, 6000).await;
+
+        let dump: SubscriptionDump = mock_sub_1.lock().await.dump_subscriptions();
+
+        assert_eq!(dump.remote, mock_sub_1.get_remote());
+        assert_eq!(dump.channels, vec![1000]);
+        assert_eq!(dump.ranges, vec![(5000, 6000)]);
+
+        // the other connection's dump is unaffected
+        let dump: SubscriptionDump = mock_sub_2.lock().await.dump_subscriptions();
+
+        assert!(dump.channels.is_empty());
+        assert!(dump.ranges.is_empty());
+    }
+
+    #[tokio::test]
+    async fn parent_zones_range_subscription() {
+        use donet_core::globals::{location_as_channel, parent_zones_channel_range};
+
+        let mut mock = MockChannelCoordinator::default();
+        let mock_sub_1 = SubscriberRef::from(SocketAddr::from_str("127.0.0.1:1").unwrap());
+
+        let parent: u32 = 4000;
+        let (min, max): (Channel, Channel) = parent_zones_channel_range(parent);
+
+        mock.subscribe_range(mock_sub_1.clone(), min, max).await;
+
+        // a message sent to a single zone of the parent is delivered
+        let mut subs: HashSet<SubscriberRef> = HashSet::default();
+        mock.lookup_channels(vec![location_as_channel(parent, 2100)], &mut subs);
+
+        assert!(subs.contains(&mock_sub_1));
+
+        // zones of a neighbouring parent are not covered
+        let mut subs: HashSet<SubscriberRef> = HashSet::default();
+        mock.lookup_channels(vec![location_as_channel(parent + 1, 0)], &mut subs);
+
+        assert!(subs.is_empty());
+    }
+
+    #[tokio::test]
+    async fn fan_out_in_stable_order() {
+        let remotes: Vec<SocketAddr> = [
+            "127.0.0.1:7", "127.0.0.1:3", "127.0.0.2:1", "127.0.0.1:12", "127.0.0.1:5",
+        ]
+        .into_iter()
+        .map(|addr| SocketAddr::from_str(addr).unwrap())
+        .collect();
+
+        let mut expected: Vec<SocketAddr> = remotes.clone();
+        expected.sort();
+
+        let delivery_order = |mock: &mut MockChannelCoordinator| -> Vec<SocketAddr> {
+            mock.lookup_ordered(vec![4000, 4001])
+                .iter()
+                .map(SubscriberRef::get_remote)
+                .collect()
+        };
+
+        // subscribe in opposite orders, through both kinds of subscription;
+        // overlapping ranges replace each other, so only one range each
+        let mut forward = MockChannelCoordinator::default();
+        let mut backward = MockChannelCoordinator::default();
+
+        for (i, remote) in remotes.iter().enumerate() {
+            let sub = SubscriberRef::from(*remote);
+
+            match i {
+                1 => forward.subscribe_range(sub, 3000, 5000).await,
+                _ => forward.subscribe_channel(sub, 4000).await,
+            }
+        }
+        for (i, remote) in remotes.iter().enumerate().rev() {
+            let sub = SubscriberRef::from(*remote);
+
+            match i {
+                3 => backward.subscribe_range(sub, 4001, 4001).await,
+                _ => backward.subscribe_channel(sub, 4001).await,
+            }
+        }
+
+        for _ in 0..10 {
+            assert_eq!(delivery_order(&mut forward), expected);
+            assert_eq!(delivery_order(&mut backward), expected);
+        }
+    }
 }
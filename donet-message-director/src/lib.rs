@@ -18,27 +18,44 @@
 */
 
 mod channel_map;
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
+mod priority;
+mod recorder;
 mod subscriber;
 mod upstream;
+mod watchdog;
 
 use channel_map::*;
 use core::net::SocketAddr;
 use donet_core::datagram::datagram::*;
+use donet_core::datagram::iterator::MessageHeader;
 use donet_core::globals::*;
 use donet_core::Protocol;
 use donet_daemon::config;
 use donet_daemon::service::*;
-use donet_network::{tcp, udp};
+use donet_network::audit::{ConnectionStats, DisconnectReason};
+use donet_network::transport::{Listener, SocketTransport, Stream, Transport};
+use donet_network::udp;
 use donet_network::{Client, HasClient, RecvData, RecvSendHandles};
 use log::{error, info, trace, warn};
+use multimap::MultiMap;
+use priority::RouteQueue;
+use recorder::*;
 use std::collections::HashSet;
 use std::io::{Error, ErrorKind, Result};
 use std::sync::Arc;
+use std::time::Duration;
 use subscriber::*;
-use tokio::net::TcpStream;
-use tokio::sync::{mpsc, Mutex, MutexGuard};
-use tokio::task::JoinHandle;
+use tokio::sync::{mpsc, Mutex, MutexGuard, Notify};
+use tokio::task::{AbortHandle, JoinHandle};
+use tokio::time::Instant;
 use upstream::*;
+use watchdog::Heartbeat;
+
+/// Transport used for both subscriber and upstream MD connections,
+/// which is TCP, unless bound to a `unix:` socket path.
+pub(crate) type MDTransport = SocketTransport;
 
 /// Represents an internal protocol header.
 ///
@@ -66,9 +83,22 @@ pub struct CreateInfo {
 }
 
 pub struct MessageDirector {
-    binding: Arc<Mutex<tcp::Acceptor>>,
+    /// Taken by the main loop, which closes it once draining.
+    binding: Option<<MDTransport as Transport>::Listener>,
+    framing: FramingWidth,
+    write_timeout: Option<Duration>,
+    max_pending_bytes: Option<u64>,
+    idle_timeout: Option<Duration>,
+    keepalive: Option<Duration>,
+    drain_timeout: Duration,
+    shutdown_timeout: Duration,
+    /// Notified to make the main loop stop accepting connections.
+    drain: Arc<Notify>,
+    watchdog_timeout: Option<Duration>,
+    heartbeat: Arc<Heartbeat>,
     upstream_md: Option<UpstreamMD>,
     event_logger: Option<udp::Socket>,
+    recorder: Option<Recorder>,
     channel_map: ChannelMap,
     subscribers: HashSet<SubscriberRef>,
     removed_subscribers: HashSet<SubscriberRef>,
@@ -78,21 +108,46 @@ impl DonetService for MessageDirector {
     type Service = Self;
     type Configuration = CreateInfo;
 
-    async fn create(
-        conf: Self::Configuration,
-        _: Option<DCFile<'static>>,
-    ) -> Result<Arc<Mutex<Self::Service>>> {
+    const NAME: &'static str = "Message Director";
+
+    async fn create(conf: Self::Configuration, _: Option<SharedDCFile>) -> Result<Arc<Mutex<Self::Service>>> {
         let bind_addr: &str = conf.service_conf.bind.as_str();
         let upstream: Option<String> = conf.service_conf.upstream;
         let logger_uri: Option<String> = conf.event_logger_url;
+        let record_path: Option<String> = conf.service_conf.record;
+        let keepalive: Option<Duration> = conf.service_conf.keepalive.map(Duration::from_secs);
+        let max_reconnect_attempts: Option<u32> = conf.service_conf.max_reconnect_attempts;
+
+        let framing: FramingWidth = match conf.service_conf.framing {
+            Some(bits) => FramingWidth::try_from(bits)?,
+            None => FramingWidth::default(),
+        };
 
         Ok(Arc::new(Mutex::new(MessageDirector {
-            binding: Arc::new(Mutex::new(tcp::Acceptor::bind(bind_addr).await?)),
+            binding: Some(MDTransport::listen(bind_addr).await?),
+            framing,
+            write_timeout: conf.service_conf.write_timeout.map(Duration::from_secs),
+            max_pending_bytes: conf.service_conf.max_pending_bytes,
+            idle_timeout: conf.service_conf.idle_timeout.map(Duration::from_secs),
+            keepalive,
+            drain_timeout: Duration::from_secs(conf.service_conf.drain_timeout.unwrap_or(30)),
+            shutdown_timeout: Duration::from_secs(conf.service_conf.shutdown_timeout.unwrap_or(5)),
+            drain: Arc::new(Notify::new()),
+            watchdog_timeout: conf.service_conf.watchdog_timeout.map(Duration::from_secs),
+            heartbeat: Arc::default(),
             upstream_md: {
                 match upstream {
                     Some(md_uri) => {
                         info!("Message Director will connect to upstream MD.");
-                        Some(UpstreamMD::connect(&md_uri).await?)
+                        Some(
+                            UpstreamMD::connect_with_retry(
+                                &md_uri,
+                                framing,
+                                keepalive,
+                                max_reconnect_attempts,
+                            )
+                            .await?,
+                        )
                     }
                     None => None,
                 }
@@ -112,13 +167,17 @@ impl DonetService for MessageDirector {
                     None => None,
                 }
             },
+            recorder: match record_path {
+                Some(path) => Some(Recorder::create(&path, framing).await?),
+                None => None,
+            },
             channel_map: ChannelMap::default(),
             subscribers: HashSet::default(),
             removed_subscribers: HashSet::default(),
         })))
     }
 
-    async fn start(conf: config::DonetConfig, _: Option<DCFile<'static>>) -> Result<JoinHandle<Result<()>>> {
+    async fn start(conf: config::DonetConfig, _: Option<SharedDCFile>) -> Result<JoinHandle<Result<()>>> {
         let service_conf: CreateInfo = CreateInfo {
             // We can unwrap safely here since this function only is called if it is `Some`.
             service_conf: conf.services.message_director.expect("MD conf not found."),
@@ -137,38 +196,79 @@ impl DonetService for MessageDirector {
         let (tx, mut rx) = mpsc::channel::<RecvData>(100);
 
         let service_clone_for_recv = service.clone();
+        let heartbeat: Arc<Heartbeat> = service.lock().await.heartbeat.clone();
+        let beat: Arc<Heartbeat> = heartbeat.clone();
 
         // spawn a tokio task for handling received datagrams from
         // clients connected to this MD.
         //
         // each client spawns tasks for handling their TCP stream,
         // so the way we communicate across tasks is via [`mpsc::channel`].
-        let handle: JoinHandle<Result<()>> = tokio::spawn(async move {
-            while let Some(recv_data) = rx.recv().await {
+        //
+        // control messages are routed ahead of any bulk traffic
+        // that is waiting to be routed, see [`RouteQueue`].
+        let handle: JoinHandle<Result<()>> = spawn_in_service(async move {
+            let mut queue: RouteQueue = RouteQueue::default();
+
+            while let Some(recv_data) = queue.next(&mut rx).await {
+                beat.begin();
                 let mut locked_service = service_clone_for_recv.lock().await;
 
                 if let Err(e) = locked_service.handle_datagram(recv_data).await {
                     warn!("Failed to handle received datagram: {}", e);
                 }
+                beat.end();
             }
             todo!("unhandled error. MD incoming datagram receiver returned None.")
         });
 
         // if we have an uplink connection, spawn send/receive tokio tasks
-        if let Some(upstream) = &service.lock().await.upstream_md {
-            let client = upstream.get_client();
-            let mut client_lock = client.lock().await;
+        let upstream_handles: Option<RecvSendHandles> = match &service.lock().await.upstream_md {
+            Some(upstream) => {
+                let client = upstream.get_client();
+                let mut client_lock = client.lock().await;
 
-            let handles = client_lock.spawn_recv_send_tasks(tx.clone()).await;
-        }
+                Some(client_lock.spawn_recv_send_tasks(tx.clone()).await)
+            }
+            None => None,
+        };
+
+        let binding: Option<<MDTransport as Transport>::Listener> = service.lock().await.binding.take();
+        let binding = binding.expect("MD main loop started more than once.");
 
-        let binding: Arc<Mutex<tcp::Acceptor>> = service.lock().await.binding.clone();
-        let binding_lock = binding.lock().await;
+        let drain: Arc<Notify> = service.lock().await.drain.clone();
+        let mut watchers: Vec<WatchedConnection> = vec![];
+
+        // if the routing task stalls, the process would look alive while
+        // routing nothing, so we exit, to be restarted by orchestration
+        let watchdog_timeout: Option<Duration> = service.lock().await.watchdog_timeout;
+        let stalled = async move {
+            match watchdog_timeout {
+                Some(window) => (watchdog::watch(heartbeat, window).await, window),
+                None => std::future::pending().await,
+            }
+        };
+        tokio::pin!(stalled);
 
-        // start the main loop (accepting new TCP connections)
+        // start the main loop (accepting new connections), until drained
         loop {
-            // here, we keep the TCP binding locked. only this loop needs it
-            match binding_lock.socket.accept().await {
+            let accepted = tokio::select! {
+                // never accept a connection once asked to drain
+                biased;
+                (routed, window) = &mut stalled => {
+                    error!(
+                        "Message Director stopped routing for {:?}, after routing {} datagrams.",
+                        window, routed
+                    );
+                    handle.abort();
+
+                    return Err(Error::new(ErrorKind::TimedOut, "MD routing task stalled."));
+                }
+                () = drain.notified() => break,
+                accepted = binding.accept() => accepted,
+            };
+
+            match accepted {
                 Ok((socket, address)) => {
                     info!("Received incoming connection from {}.", address);
 
@@ -177,9 +277,12 @@ impl DonetService for MessageDirector {
                     // create a new [`Subscriber`] from the new TCP connection,
                     // and pass a clone of `tx` for receiving its datagrams
                     match service_lock.new_connection(socket, tx.clone()).await {
-                        Ok((recv_handle, send_handle)) => {
+                        Ok((handles, stats)) => {
                             trace!("Created new subscriber.");
-                            // TODO! handle task joins
+                            info!("{}", stats.accept_record(None));
+
+                            watchers.retain(|watched| !watched.watcher.is_finished());
+                            watchers.push(WatchedConnection::spawn(service.clone(), stats, handles));
                         }
                         Err(err) => {
                             info!("Failed to accept subscriber {}: {}", address, err);
@@ -189,6 +292,34 @@ impl DonetService for MessageDirector {
                 Err(socket_err) => error!("Failed to get client: {}", socket_err),
             }
         }
+        // closes the listening socket, so new connections are refused
+        drop(binding);
+
+        let result: Result<()> = Self::finish_drain(service, watchers, upstream_handles).await;
+
+        // every connection is closed, so nothing is left to route
+        handle.abort();
+        result
+    }
+}
+
+/// A subscriber's connection, along with the task watching it.
+struct WatchedConnection {
+    watcher: JoinHandle<Result<()>>,
+    stats: ConnectionStats,
+    recv_loop: AbortHandle,
+    send_loop: AbortHandle,
+}
+
+impl WatchedConnection {
+    /// Spawns the task watching a connection, see [`MessageDirector::watch_connection`].
+    fn spawn(service: Arc<Mutex<MessageDirector>>, stats: ConnectionStats, handles: RecvSendHandles) -> Self {
+        Self {
+            recv_loop: handles.0.abort_handle(),
+            send_loop: handles.1.abort_handle(),
+            watcher: MessageDirector::watch_connection(service, stats.clone(), handles),
+            stats,
+        }
     }
 }
 
@@ -213,7 +344,7 @@ impl ChannelCoordinator for MessageDirector {
 
     async fn on_remove_channel(&mut self, channel: Channel) {
         if let Some(upstream) = &mut self.upstream_md {
-            upstream.stage_add_channel(channel).await;
+            upstream.stage_remove_channel(channel).await;
         }
     }
 
@@ -254,19 +385,27 @@ impl MessageDirector {
                     "Tried to remove subscriber that doesn't exist.",
                 );
 
-                {
+                let post_removes: MultiMap<Channel, Datagram> = {
                     let mut locked_sub: MutexGuard<'_, Subscriber> = sub_ref.lock().await;
 
-                    // Send out any post-remove messages the participant may have added.
-                    // This is done last, because we don't want to send messages
-                    // through the Director while a participant is being removed, as
-                    // certain data structures may not have their invariants satisfied
-                    // during that time.
-                    locked_sub.post_remove().await;
-                }
+                    locked_sub.take_post_removes()
+                };
 
                 // mark the subscriber for deletion
                 self.removed_subscribers.insert(sub_ref);
+
+                // Send out any post-remove messages the participant may have added.
+                // This is done last, because we don't want to send messages
+                // through the Director while a participant is being removed, as
+                // certain data structures may not have their invariants satisfied
+                // during that time.
+                for (sender, datagrams) in post_removes {
+                    for mut dg in datagrams {
+                        self.route_post_remove(&mut dg).await?;
+                    }
+                    // upstream sent them out with ours, so it can forget its copies
+                    self.recall_post_removes(sender).await;
+                }
                 Ok(())
             }
             None => {
@@ -276,6 +415,164 @@ impl MessageDirector {
         }
     }
 
+    /// Spawns a task that waits for the receive loop of a subscriber's
+    /// connection to end, which happens when it disconnects or is
+    /// dropped as idle, logs its disconnect record, and then removes
+    /// the subscriber.
+    fn watch_connection(
+        service: Arc<Mutex<Self>>,
+        stats: ConnectionStats,
+        (recv_handle, mut send_handle): RecvSendHandles,
+    ) -> JoinHandle<Result<()>> {
+        spawn_in_service(async move {
+            let remote: SocketAddr = stats.get_remote();
+
+            let reason: DisconnectReason = match recv_handle.await {
+                Ok(Ok(())) => DisconnectReason::Closed,
+                Ok(Err(err)) => DisconnectReason::Error(err.to_string()),
+                // the send loop ended first, which stops the receive loop
+                Err(_) => match (&mut send_handle).await {
+                    Ok(Err(err)) => DisconnectReason::Error(err.to_string()),
+                    _ => DisconnectReason::Shutdown,
+                },
+            };
+            info!("{}", stats.disconnect_record(reason));
+
+            // closes the connection, if the remote has not already
+            send_handle.abort();
+
+            let mut service_lock = service.lock().await;
+
+            // A draining MD removes the subscribers that are left before
+            // closing their connections, so it may already be gone.
+            match service_lock.get_subscriber_with_remote(remote) {
+                Some(_) => service_lock.remove_subscriber(remote).await,
+                None => Ok(()),
+            }
+        })
+    }
+
+    /// Stops the MD from accepting new connections, and has it exit
+    /// once its participants have left, for rolling restarts.
+    ///
+    /// Datagrams from the participants that are still connected keep
+    /// being routed while they move to another MD.
+    pub fn begin_drain(&self) {
+        info!("Draining Message Director; no longer accepting connections.");
+
+        // Stores a permit if the main loop is not waiting yet.
+        self.drain.notify_one();
+    }
+
+    /// Finishes draining the MD, once the main loop stopped accepting.
+    ///
+    /// Participants are given until the drain timeout to disconnect.
+    /// The ones left are then removed, which sends out their post
+    /// removes, and every connection is closed once the datagrams
+    /// queued for it are written out, or once the shutdown timeout
+    /// is reached, dropping the datagrams that are left.
+    async fn finish_drain(
+        service: Arc<Mutex<Self>>,
+        watchers: Vec<WatchedConnection>,
+        upstream_handles: Option<RecvSendHandles>,
+    ) -> Result<()> {
+        let drain_timeout: Duration = service.lock().await.drain_timeout;
+        let shutdown_timeout: Duration = service.lock().await.shutdown_timeout;
+
+        let migrated = tokio::time::timeout(drain_timeout, async {
+            while !service.lock().await.subscribers.is_empty() {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        })
+        .await;
+
+        if migrated.is_err() {
+            warn!(
+                "Participants did not leave within {:?}; removing them.",
+                drain_timeout
+            );
+
+            service.lock().await.remove_all_subscribers().await?;
+        }
+
+        let deadline: Instant = Instant::now() + shutdown_timeout;
+        let mut dropped: u64 = Self::flush_connections(watchers, deadline).await;
+
+        // Post removes may have been sent upstream, so flush it last.
+        if let Some((recv_handle, mut send_handle)) = upstream_handles {
+            let mut upstream_stats: Option<ConnectionStats> = None;
+
+            if let Some(upstream) = &service.lock().await.upstream_md {
+                let client = upstream.get_client();
+                let mut client_lock = client.lock().await;
+
+                upstream_stats = Some(client_lock.get_stats());
+                client_lock.close();
+            }
+
+            match tokio::time::timeout_at(deadline, &mut send_handle).await {
+                Ok(flushed) => flushed??,
+                Err(_) => {
+                    dropped += upstream_stats.map_or(0, |stats| stats.pending_datagrams());
+
+                    recv_handle.abort();
+                    send_handle.abort();
+                }
+            }
+        }
+
+        if dropped > 0 {
+            warn!(
+                "Dropped {} datagrams that were not written out within {:?}.",
+                dropped, shutdown_timeout
+            );
+        }
+        info!("Message Director drained.");
+        Ok(())
+    }
+
+    /// Waits until the deadline for each connection to write out the
+    /// datagrams queued for it and close. The connections left are
+    /// then closed, and the number of datagrams they dropped is returned.
+    async fn flush_connections(watchers: Vec<WatchedConnection>, deadline: Instant) -> u64 {
+        let mut dropped: u64 = 0;
+
+        for mut watched in watchers {
+            let result = match tokio::time::timeout_at(deadline, &mut watched.watcher).await {
+                Ok(result) => result,
+                Err(_) => {
+                    dropped += watched.stats.pending_datagrams();
+
+                    // the watcher then logs the connection being closed
+                    watched.recv_loop.abort();
+                    watched.send_loop.abort();
+                    watched.watcher.await
+                }
+            };
+
+            if let Ok(Err(err)) = result {
+                warn!("Failed to remove subscriber while draining: {}", err);
+            }
+        }
+        dropped
+    }
+
+    /// Removes every subscriber, sending out their post removes, then
+    /// closes their connections once their queued datagrams are written.
+    async fn remove_all_subscribers(&mut self) -> Result<()> {
+        let remaining: Vec<SubscriberRef> = self.subscribers.iter().cloned().collect();
+
+        // Remove all of them first, so that the post removes of each
+        // still reach the others before their connections are closed.
+        for sub in &remaining {
+            self.remove_subscriber(sub.get_remote()).await?;
+        }
+        for sub in remaining {
+            sub.lock().await.get_client().lock().await.close();
+        }
+        Ok(())
+    }
+
     /// Takes in a [`SocketAddr`], returns a [`SubscriberRef`] or `None`.
     ///
     /// Retrieval can be done by creating a dummy [`SubscriberRef`]
@@ -285,14 +582,38 @@ impl MessageDirector {
         self.subscribers.get(&remote.into()).cloned()
     }
 
+    /// Returns the subscriber that sent a control message. It may have
+    /// already been removed if it disconnected before the message was
+    /// handled, in which case the message is dropped with an error.
+    fn get_control_sender(&self, remote: SocketAddr) -> Result<SubscriberRef> {
+        self.get_subscriber_with_remote(remote).ok_or_else(|| {
+            Error::new(
+                ErrorKind::NotConnected,
+                format!("Control message from {}, which is not a subscriber.", remote),
+            )
+        })
+    }
+
     /// Creates a new [`Subscriber`] structure in memory from the
-    /// new connected client, and spawns TCP stream handler tasks.
+    /// new connected client, and spawns stream handler tasks.
+    ///
+    /// Also returns the statistics of the connection, for recording it.
     async fn new_connection(
         &mut self,
-        socket: TcpStream,
+        socket: impl Stream,
         tx: mpsc::Sender<RecvData>,
-    ) -> Result<RecvSendHandles> {
-        let client: Client = Client::from(socket);
+    ) -> Result<(RecvSendHandles, ConnectionStats)> {
+        if let Some(idle) = self.keepalive {
+            socket.set_keepalive(idle)?;
+        }
+        let mut client: Client = Client::new(socket)?;
+        let stats: ConnectionStats = client.get_stats();
+
+        client.set_framing(self.framing);
+        client.set_write_timeout(self.write_timeout);
+        client.set_max_pending_bytes(self.max_pending_bytes);
+        client.set_idle_timeout(self.idle_timeout);
+        client.set_task_spawner(spawn_in_service);
 
         let sub_ptr: SubscriberRef = self.add_subscriber(client).await?;
 
@@ -305,7 +626,7 @@ impl MessageDirector {
         let mut client_lock = client.lock().await;
 
         // start recv loop for subscriber client (connection)
-        Ok(client_lock.spawn_recv_send_tasks(tx).await)
+        Ok((client_lock.spawn_recv_send_tasks(tx).await, stats))
     }
 
     /// Entry point for all datagrams received from a client via their TCP socket.
@@ -333,6 +654,10 @@ impl MessageDirector {
             }
         }
 
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record(&data.dg).await?;
+        }
+
         // not a control msg, so there is a sender field ahead
         let sender: Channel = data.dgi.read_channel()?;
 
@@ -344,6 +669,37 @@ impl MessageDirector {
         self.route_datagram(header, data).await
     }
 
+    /// Routes every datagram in the given recording file again, in
+    /// recorded order, and returns the number of datagrams replayed.
+    ///
+    /// Replayed datagrams are treated as if they came from upstream,
+    /// so they are only delivered to our own subscribers.
+    pub async fn replay(&mut self, path: &str) -> Result<usize> {
+        let datagrams: Vec<Datagram> = read_recording(path, self.framing).await?;
+
+        // Do not record the replayed datagrams again.
+        let recorder: Option<Recorder> = self.recorder.take();
+
+        let mut result: Result<()> = Ok(());
+
+        for dg in &datagrams {
+            result = self
+                .handle_datagram(RecvData {
+                    remote: SocketAddr::from(([0, 0, 0, 0], 0)),
+                    dg: dg.clone(),
+                    dgi: dg.clone().into(),
+                })
+                .await;
+
+            if result.is_err() {
+                break;
+            }
+        }
+        self.recorder = recorder;
+
+        result.map(|_| datagrams.len())
+    }
+
     /// Handles a datagram that is a CONTROL message, a.k.a it had one recipient
     /// and the recipient channel was the control channel (channel 1).
     async fn handle_control_msg(&mut self, mut data: RecvData) -> Result<()> {
@@ -352,14 +708,14 @@ impl MessageDirector {
         match msg_type {
             Protocol::MDAddChannel => {
                 let channel: Channel = data.dgi.read_channel()?;
-                let sub: SubscriberRef = self.get_subscriber_with_remote(data.remote).unwrap();
+                let sub: SubscriberRef = self.get_control_sender(data.remote)?;
 
                 self.subscribe_channel(sub, channel).await;
                 Ok(())
             }
             Protocol::MDRemoveChannel => {
                 let channel: Channel = data.dgi.read_channel()?;
-                let sub: SubscriberRef = self.get_subscriber_with_remote(data.remote).unwrap();
+                let sub: SubscriberRef = self.get_control_sender(data.remote)?;
 
                 self.unsubscribe_channel(sub, channel).await;
                 Ok(())
@@ -368,7 +724,7 @@ impl MessageDirector {
                 let min: Channel = data.dgi.read_channel()?;
                 let max: Channel = data.dgi.read_channel()?;
 
-                let sub: SubscriberRef = self.get_subscriber_with_remote(data.remote).unwrap();
+                let sub: SubscriberRef = self.get_control_sender(data.remote)?;
 
                 self.subscribe_range(sub, min, max).await;
                 Ok(())
@@ -377,7 +733,7 @@ impl MessageDirector {
                 let min: Channel = data.dgi.read_channel()?;
                 let max: Channel = data.dgi.read_channel()?;
 
-                let sub: SubscriberRef = self.get_subscriber_with_remote(data.remote).unwrap();
+                let sub: SubscriberRef = self.get_control_sender(data.remote)?;
 
                 self.unsubscribe_range(sub, min, max).await;
                 Ok(())
@@ -392,7 +748,7 @@ impl MessageDirector {
                     }
                 };
 
-                let sub: SubscriberRef = self.get_subscriber_with_remote(data.remote).unwrap();
+                let sub: SubscriberRef = self.get_control_sender(data.remote)?;
 
                 trace!("Subscriber with remote {} added a post remove.", sub.get_remote());
 
@@ -403,7 +759,7 @@ impl MessageDirector {
             Protocol::MDClearPostRemoves => {
                 let sender: Channel = data.dgi.read_channel()?;
 
-                let sub: SubscriberRef = self.get_subscriber_with_remote(data.remote).unwrap();
+                let sub: SubscriberRef = self.get_control_sender(data.remote)?;
 
                 trace!("Subscriber with remote {} added a post remove.", sub.get_remote());
 
@@ -413,7 +769,7 @@ impl MessageDirector {
             }
             Protocol::MDSetConName => {
                 let con_name: String = data.dgi.read_string()?;
-                let sub: SubscriberRef = self.get_subscriber_with_remote(data.remote).unwrap();
+                let sub: SubscriberRef = self.get_control_sender(data.remote)?;
 
                 // Set the downstream connection's name
                 sub.lock().await.connection_name = Some(con_name);
@@ -421,13 +777,31 @@ impl MessageDirector {
             }
             Protocol::MDSetConUrl => {
                 let con_web_url: String = data.dgi.read_string()?;
-                let sub: SubscriberRef = self.get_subscriber_with_remote(data.remote).unwrap();
+                let sub: SubscriberRef = self.get_control_sender(data.remote)?;
 
                 // Set the downstream connection's web URL
                 sub.lock().await.connection_web_url = Some(con_web_url);
                 Ok(())
             }
             Protocol::MDLogMessage => self.route_log_message(data).await,
+            Protocol::MDDrain => {
+                self.begin_drain();
+                Ok(())
+            }
+            Protocol::MDGetSubscriptions => {
+                // Only answered for our own participants; control messages
+                // from upstream are never routed, so nobody else can ask.
+                let Some(sub) = self.get_subscriber_with_remote(data.remote) else {
+                    warn!("Subscriptions dump requested by non-subscriber {}.", data.remote);
+                    return Ok(());
+                };
+                let mut dg: Datagram = self.make_subscriptions_dump().await?;
+
+                if let Err(err) = sub.lock().await.handle_datagram(&mut dg).await {
+                    return Err(Error::new(ErrorKind::Other, err.to_string()));
+                }
+                Ok(())
+            }
             _ => {
                 warn!(
                     "Received control message with a non-control message type from {}",
@@ -439,20 +813,34 @@ impl MessageDirector {
         }
     }
 
-    /// Handles replicating and routing a datagram to its proper recipients
-    /// based on this message director's channel subscriptions map.
-    async fn route_datagram(&mut self, header: InternalHeader, mut data: RecvData) -> Result<()> {
-        let mut receiving_subscribers: HashSet<SubscriberRef> = HashSet::default();
+    /// Builds a `CONTROL_GET_SUBSCRIPTIONS_RESP` datagram with the
+    /// subscriptions of every connection, ordered by remote address.
+    async fn make_subscriptions_dump(&self) -> Result<Datagram> {
+        let mut dumps: Vec<SubscriptionDump> = vec![];
 
-        // get all subscribers of the recipient channels
-        self.lookup_channels(header.recipients, &mut receiving_subscribers);
+        for sub in &self.subscribers {
+            dumps.push(sub.lock().await.dump_subscriptions());
+        }
+        dumps.sort_by_key(|dump| dump.remote);
 
-        // replicate the message to all receiving subscribers
-        for sub in receiving_subscribers {
-            if let Err(err) = sub.lock().await.handle_datagram(&mut data.dg).await {
-                return Err(Error::new(ErrorKind::Other, err.to_string()));
-            }
+        let mut dg: Datagram = Datagram::default();
+
+        dg.add_control_header(Protocol::MDGetSubscriptionsResp.into())?;
+        dg.add_u16(
+            u16::try_from(dumps.len())
+                .map_err(|_| DatagramError::DatagramOverflow("Too many subscribers to dump."))?,
+        )?;
+
+        for dump in &dumps {
+            dump.write_datagram(&mut dg)?;
         }
+        Ok(dg)
+    }
+
+    /// Handles replicating and routing a datagram to its proper recipients
+    /// based on this message director's channel subscriptions map.
+    async fn route_datagram(&mut self, header: InternalHeader, mut data: RecvData) -> Result<()> {
+        self.deliver_datagram(header.recipients, &mut data.dg).await?;
 
         // Next, decide if this message needs to be routed **upstream**.
         //
@@ -481,6 +869,31 @@ impl MessageDirector {
         Ok(())
     }
 
+    /// Replicates a datagram to all of our subscribers of the given channels.
+    async fn deliver_datagram(&mut self, recipients: Vec<Channel>, dg: &mut Datagram) -> Result<()> {
+        // replicate the message to all subscribers of the recipient
+        // channels, in the same order for every delivery
+        for sub in self.lookup_ordered(recipients) {
+            if let Err(err) = sub.lock().await.handle_datagram(dg).await {
+                return Err(Error::new(ErrorKind::Other, err.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Routes a post remove of a removed subscriber, both to our own
+    /// subscribers and upstream, as if the subscriber had sent it.
+    async fn route_post_remove(&mut self, dg: &mut Datagram) -> Result<()> {
+        let header: MessageHeader = dg.parse_server_header()?;
+
+        self.deliver_datagram(header.recipients, dg).await?;
+
+        if let Some(upstream) = &self.upstream_md {
+            upstream.stage_datagram(dg.clone()).await;
+        }
+        Ok(())
+    }
+
     /// Sends the post remove for the given sender by sending it
     /// upstream, if there is an upstream connection.
     async fn preroute_post_remove(&mut self, sender: Channel, post_remove: Datagram) {
@@ -527,3 +940,424 @@ impl MessageDirector {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::{TcpListener, TcpSocket, TcpStream};
+
+    fn recv_data(remote: SocketAddr, dg: Datagram) -> RecvData {
+        RecvData {
+            remote,
+            dg: dg.clone(),
+            dgi: dg.into(),
+        }
+    }
+
+    #[tokio::test]
+    async fn idle_subscriber_reaped() -> Result<()> {
+        let service = MessageDirector::create(
+            CreateInfo {
+                service_conf: config::MessageDirector {
+                    bind: "127.0.0.1:0".into(),
+                    upstream: None,
+                    framing: None,
+                    write_timeout: None,
+                    max_pending_bytes: None,
+                    idle_timeout: Some(1),
+                    keepalive: Some(60),
+                    drain_timeout: None,
+                    shutdown_timeout: None,
+                    record: None,
+                    watchdog_timeout: None,
+                    max_reconnect_attempts: None,
+                },
+                event_logger_url: None,
+            },
+            None,
+        )
+        .await?;
+        let mut md = service.lock().await;
+
+        let listener: TcpListener = TcpListener::bind("127.0.0.1:0").await?;
+        let (md_tx, _md_rx) = mpsc::channel::<RecvData>(8);
+
+        // the idle participant never sends anything over its connection
+        let mut idle: TcpStream = TcpStream::connect(listener.local_addr()?).await?;
+        let (md_side, idle_remote) = listener.accept().await?;
+        let (idle_handles, idle_stats) = md.new_connection(md_side, md_tx.clone()).await?;
+
+        let watcher: TcpStream = TcpStream::connect(listener.local_addr()?).await?;
+        let (md_side, watcher_remote) = listener.accept().await?;
+        md.new_connection(md_side, md_tx).await?;
+
+        let idle_sub: SubscriberRef = md.get_subscriber_with_remote(idle_remote).unwrap();
+        let watcher_sub: SubscriberRef = md.get_subscriber_with_remote(watcher_remote).unwrap();
+
+        md.subscribe_channel(idle_sub, 5000).await;
+        md.subscribe_channel(watcher_sub, 6000).await;
+
+        let mut post_remove: Datagram = Datagram::default();
+        post_remove.add_internal_header(vec![6000], 5000, Protocol::SSObjectDeleteRAM.into())?;
+        post_remove.add_doid(100_000)?;
+
+        let mut dg: Datagram = Datagram::default();
+        dg.add_control_header(Protocol::MDAddPostRemove.into())?;
+        dg.add_channel(5000)?;
+        dg.add_blob(post_remove.get_data())?;
+
+        md.handle_datagram(recv_data(idle_remote, dg)).await?;
+        drop(md);
+
+        let (tx, mut rx) = mpsc::channel::<RecvData>(8);
+        let mut watcher: Client = Client::from(watcher);
+        let _ = watcher.spawn_recv_send_tasks(tx).await;
+
+        MessageDirector::watch_connection(service.clone(), idle_stats, idle_handles).await??;
+
+        // the idle connection was closed by the MD
+        assert_eq!(idle.read(&mut [0_u8; 8]).await?, 0);
+
+        let received: RecvData = rx.recv().await.unwrap();
+        assert_eq!(received.dg.get_data(), post_remove.get_data());
+
+        let mut md = service.lock().await;
+        assert!(md.get_subscriber_with_remote(idle_remote).is_none());
+
+        let mut subscribers: HashSet<SubscriberRef> = HashSet::default();
+        md.lookup_channels(vec![5000], &mut subscribers);
+        assert!(subscribers.is_empty());
+
+        // control messages still queued from the removed subscriber are dropped
+        let mut dg: Datagram = Datagram::default();
+        dg.add_control_header(Protocol::MDAddChannel.into())?;
+        dg.add_channel(7000)?;
+
+        let err: Error = md.handle_datagram(recv_data(idle_remote, dg)).await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotConnected);
+        Ok(())
+    }
+
+    /// Sends a `CONTROL_GET_SUBSCRIPTIONS` and waits for its response,
+    /// so that every message sent before it is known to be handled.
+    async fn sync_with_md(client: &mut Client, rx: &mut mpsc::Receiver<RecvData>) -> Result<()> {
+        let mut dg: Datagram = Datagram::default();
+        dg.add_control_header(Protocol::MDGetSubscriptions.into())?;
+
+        client.stage_datagram(dg).await.unwrap();
+
+        let mut resp: RecvData = rx.recv().await.unwrap();
+        resp.dgi.read_recipient_count()?;
+        resp.dgi.read_channel()?;
+
+        assert_eq!(resp.dgi.read_msg_type()?, Protocol::MDGetSubscriptionsResp);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn drain_refuses_connections() -> Result<()> {
+        let service = MessageDirector::create(
+            CreateInfo {
+                service_conf: config::MessageDirector {
+                    bind: "127.0.0.1:0".into(),
+                    upstream: None,
+                    framing: None,
+                    write_timeout: None,
+                    max_pending_bytes: None,
+                    idle_timeout: None,
+                    keepalive: None,
+                    drain_timeout: Some(5),
+                    shutdown_timeout: None,
+                    record: None,
+                    watchdog_timeout: None,
+                    max_reconnect_attempts: None,
+                },
+                event_logger_url: None,
+            },
+            None,
+        )
+        .await?;
+        let address: SocketAddr = service.lock().await.binding.as_ref().unwrap().local_addr()?;
+
+        let main: JoinHandle<Result<()>> = tokio::spawn(MessageDirector::main(service.clone()));
+
+        let mut sender: Client = Client::from(TcpStream::connect(address).await?);
+        let mut receiver: Client = Client::from(TcpStream::connect(address).await?);
+
+        let (tx, mut sender_rx) = mpsc::channel::<RecvData>(8);
+        let _ = sender.spawn_recv_send_tasks(tx).await;
+        let (tx, mut receiver_rx) = mpsc::channel::<RecvData>(8);
+        let _ = receiver.spawn_recv_send_tasks(tx).await;
+
+        let mut dg: Datagram = Datagram::default();
+        dg.add_control_header(Protocol::MDAddChannel.into())?;
+        dg.add_channel(5000)?;
+
+        receiver.stage_datagram(dg).await.unwrap();
+        sync_with_md(&mut receiver, &mut receiver_rx).await?;
+
+        let mut dg: Datagram = Datagram::default();
+        dg.add_control_header(Protocol::MDDrain.into())?;
+
+        sender.stage_datagram(dg).await.unwrap();
+        sync_with_md(&mut sender, &mut sender_rx).await?;
+
+        // The listener closes shortly after; connections made until
+        // then are left in its backlog, and are never accepted.
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while TcpStream::connect(address).await.is_ok() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("MD kept listening while draining.");
+
+        assert_eq!(service.lock().await.subscribers.len(), 2);
+
+        // datagrams from connected participants are still routed
+        let mut update: Datagram = Datagram::default();
+        update.add_internal_header(vec![5000], 6000, Protocol::SSObjectSetField.into())?;
+        update.add_string("in flight")?;
+
+        sender.stage_datagram(update.clone()).await.unwrap();
+
+        let received: RecvData = receiver_rx.recv().await.unwrap();
+        assert_eq!(received.dg.get_data(), update.get_data());
+
+        // once the participants leave, the MD exits
+        sender.close();
+        receiver.close();
+
+        let exited = tokio::time::timeout(Duration::from_secs(5), main).await;
+        exited.expect("MD did not exit after draining.").unwrap()?;
+
+        assert!(service.lock().await.subscribers.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn slow_consumer_flush_times_out() -> Result<()> {
+        let service = MessageDirector::create(
+            CreateInfo {
+                service_conf: config::MessageDirector {
+                    bind: "127.0.0.1:0".into(),
+                    upstream: None,
+                    framing: None,
+                    write_timeout: None,
+                    max_pending_bytes: None,
+                    idle_timeout: None,
+                    keepalive: None,
+                    drain_timeout: None,
+                    shutdown_timeout: Some(1),
+                    record: None,
+                    watchdog_timeout: None,
+                    max_reconnect_attempts: None,
+                },
+                event_logger_url: None,
+            },
+            None,
+        )
+        .await?;
+        let mut md = service.lock().await;
+
+        let listener: TcpListener = TcpListener::bind("127.0.0.1:0").await?;
+        let (md_tx, _md_rx) = mpsc::channel::<RecvData>(8);
+
+        // the slow consumer never reads what the MD sends it
+        let socket: TcpSocket = TcpSocket::new_v4()?;
+        socket.set_recv_buffer_size(4096)?;
+
+        let _slow: TcpStream = socket.connect(listener.local_addr()?).await?;
+        let (md_side, slow_remote) = listener.accept().await?;
+        let (handles, stats) = md.new_connection(md_side, md_tx).await?;
+
+        let slow_sub: SubscriberRef = md.get_subscriber_with_remote(slow_remote).unwrap();
+        md.subscribe_channel(slow_sub, 5000).await;
+
+        // Route until the socket buffers are full, and datagrams pile up
+        // in the send queue, but stop before the queue is full, so that
+        // routing does not block.
+        let mut routed: u64 = 0;
+
+        while stats.pending_datagrams() < 8 {
+            assert!(routed < 1000, "Routed datagrams never piled up.");
+
+            let mut update: Datagram = Datagram::default();
+            update.add_internal_header(vec![5000], 6000, Protocol::SSObjectSetField.into())?;
+            update.add_blob(vec![0; 60_000])?;
+
+            md.handle_datagram(recv_data("127.0.0.1:7199".parse().unwrap(), update))
+                .await?;
+            routed += 1;
+
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+
+        md.remove_all_subscribers().await?;
+        drop(md);
+
+        let watched: WatchedConnection = WatchedConnection::spawn(service.clone(), stats.clone(), handles);
+
+        let started: Instant = Instant::now();
+        let deadline: Instant = started + service.lock().await.shutdown_timeout;
+
+        let dropped: u64 = MessageDirector::flush_connections(vec![watched], deadline).await;
+
+        assert!(dropped >= 8 && dropped <= routed, "dropped {} datagrams", dropped);
+        assert!(started.elapsed() < Duration::from_secs(3));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unreachable_upstream_gives_up() -> Result<()> {
+        // nothing listens on the port once the listener is dropped
+        let unreachable: SocketAddr = TcpListener::bind("127.0.0.1:0").await?.local_addr()?;
+
+        let started: Instant = Instant::now();
+        let result = MessageDirector::create(
+            CreateInfo {
+                service_conf: config::MessageDirector {
+                    bind: "127.0.0.1:0".into(),
+                    upstream: Some(unreachable.to_string()),
+                    framing: None,
+                    write_timeout: None,
+                    max_pending_bytes: None,
+                    idle_timeout: None,
+                    keepalive: None,
+                    drain_timeout: None,
+                    shutdown_timeout: None,
+                    record: None,
+                    watchdog_timeout: None,
+                    max_reconnect_attempts: Some(2),
+                },
+                event_logger_url: None,
+            },
+            None,
+        )
+        .await;
+
+        let err: Error = result.err().expect("MD connected to an unreachable upstream.");
+
+        assert_eq!(err.kind(), ErrorKind::ConnectionRefused);
+        assert!(err.to_string().contains("after 2 reconnect attempts"));
+
+        // waited out the backoff before each of the two attempts
+        assert!(started.elapsed() >= Duration::from_millis(750));
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn subscription_over_unix_socket() -> Result<()> {
+        let path = std::env::temp_dir().join("donet-md-unix-test.sock");
+        let uri: String = format!("unix:{}", path.to_string_lossy());
+
+        let service = MessageDirector::create(
+            CreateInfo {
+                service_conf: config::MessageDirector {
+                    bind: uri.clone(),
+                    upstream: None,
+                    framing: None,
+                    write_timeout: None,
+                    max_pending_bytes: None,
+                    idle_timeout: None,
+                    keepalive: Some(60),
+                    drain_timeout: None,
+                    shutdown_timeout: None,
+                    record: None,
+                    watchdog_timeout: None,
+                    max_reconnect_attempts: None,
+                },
+                event_logger_url: None,
+            },
+            None,
+        )
+        .await?;
+
+        let main: JoinHandle<Result<()>> = tokio::spawn(MessageDirector::main(service.clone()));
+
+        let mut sender: Client = Client::new(MDTransport::connect(&uri).await?)?;
+        let mut receiver: Client = Client::new(MDTransport::connect(&uri).await?)?;
+
+        let (tx, mut sender_rx) = mpsc::channel::<RecvData>(8);
+        let _ = sender.spawn_recv_send_tasks(tx).await;
+        let (tx, mut receiver_rx) = mpsc::channel::<RecvData>(8);
+        let _ = receiver.spawn_recv_send_tasks(tx).await;
+
+        let mut dg: Datagram = Datagram::default();
+        dg.add_control_header(Protocol::MDAddChannel.into())?;
+        dg.add_channel(5000)?;
+
+        receiver.stage_datagram(dg).await.unwrap();
+        sync_with_md(&mut receiver, &mut receiver_rx).await?;
+        sync_with_md(&mut sender, &mut sender_rx).await?;
+
+        let mut update: Datagram = Datagram::default();
+        update.add_internal_header(vec![5000], 6000, Protocol::SSObjectSetField.into())?;
+        update.add_string("over a unix socket")?;
+
+        sender.stage_datagram(update.clone()).await.unwrap();
+
+        let received: RecvData = receiver_rx.recv().await.unwrap();
+        assert_eq!(received.dg.get_data(), update.get_data());
+
+        // each connection is told apart by its own placeholder address
+        assert_eq!(service.lock().await.subscribers.len(), 2);
+
+        main.abort();
+        std::fs::remove_file(&path)
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn watchdog_fires_on_stalled_router() -> Result<()> {
+        let service = MessageDirector::create(
+            CreateInfo {
+                service_conf: config::MessageDirector {
+                    bind: "127.0.0.1:0".into(),
+                    upstream: None,
+                    framing: None,
+                    write_timeout: None,
+                    max_pending_bytes: None,
+                    idle_timeout: None,
+                    keepalive: None,
+                    drain_timeout: None,
+                    shutdown_timeout: None,
+                    record: None,
+                    watchdog_timeout: Some(1),
+                    max_reconnect_attempts: None,
+                },
+                event_logger_url: None,
+            },
+            None,
+        )
+        .await?;
+        let address: SocketAddr = service.lock().await.binding.as_ref().unwrap().local_addr()?;
+
+        let main: JoinHandle<Result<()>> = tokio::spawn(MessageDirector::main(service.clone()));
+
+        let mut sender: Client = Client::from(TcpStream::connect(address).await?);
+        let (tx, mut sender_rx) = mpsc::channel::<RecvData>(8);
+        let _ = sender.spawn_recv_send_tasks(tx).await;
+
+        sync_with_md(&mut sender, &mut sender_rx).await?;
+
+        // an idle router is not a stalled one
+        tokio::time::sleep(Duration::from_millis(2500)).await;
+        assert!(!main.is_finished());
+
+        // holding the MD stalls the routing task on its next datagram
+        let stalled: MutexGuard<'_, MessageDirector> = service.lock().await;
+
+        let mut dg: Datagram = Datagram::default();
+        dg.add_control_header(Protocol::MDGetSubscriptions.into())?;
+        sender.stage_datagram(dg).await.unwrap();
+
+        let exited = tokio::time::timeout(Duration::from_secs(5), main).await;
+        let err: Error = exited.expect("Watchdog did not fire.").unwrap().unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::TimedOut);
+        drop(stalled);
+        Ok(())
+    }
+}
@@ -0,0 +1,212 @@
+/*
+    This file is part of Donet.
+
+    Copyright © 2024 Max Rodriguez <me@maxrdz.com>
+
+    Donet is free software; you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License,
+    as published by the Free Software Foundation, either version 3
+    of the License, or (at your option) any later version.
+
+    Donet is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public
+    License along with Donet. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! In-process stand-in for a Message Director, for unit testing
+//! the handlers of a service without opening any sockets.
+
+use super::channel_map::*;
+use super::subscriber::*;
+use core::net::SocketAddr;
+use donet_core::datagram::datagram::Datagram;
+use donet_core::datagram::iterator::DatagramIterator;
+use donet_core::globals::*;
+use donet_core::Protocol;
+use std::collections::HashMap;
+use std::io::Result;
+use std::ops::Range;
+
+/// Routes datagrams between participants using the same channel
+/// map as the [`crate::MessageDirector`], but instead of sending
+/// them over a connection, captures every datagram delivered.
+///
+/// Participants are identified by a remote address, which does not
+/// need to be reachable. A handler under test is driven by routing
+/// synthetic inbound messages to its channels, and its outbound
+/// effects are asserted on with [`Self::sent`] and [`Self::sent_to`].
+#[derive(Default)]
+pub struct MockMessageDirector {
+    channel_map: ChannelMap,
+    participants: HashMap<SocketAddr, SubscriberRef>,
+    /// Every datagram delivered, in order, along with the
+    /// participant that it was delivered to.
+    sent: Vec<(SocketAddr, Datagram)>,
+}
+
+impl HasChannelMap for MockMessageDirector {
+    fn get_channel_map(&mut self) -> &mut ChannelMap {
+        &mut self.channel_map
+    }
+}
+
+// There is no upstream MD to tell about changes in subscriptions.
+impl ChannelCoordinator for MockMessageDirector {
+    async fn on_add_channel(&mut self, _: Channel) {}
+    async fn on_remove_channel(&mut self, _: Channel) {}
+    async fn on_add_range(&mut self, _: Range<Channel>) {}
+    async fn on_remove_range(&mut self, _: Range<Channel>) {}
+}
+
+impl MockMessageDirector {
+    fn participant(&mut self, remote: SocketAddr) -> SubscriberRef {
+        self.participants
+            .entry(remote)
+            .or_insert_with(|| SubscriberRef::from(remote))
+            .clone()
+    }
+
+    /// Subscribes the participant to a single channel.
+    pub async fn add_channel(&mut self, participant: SocketAddr, channel: Channel) {
+        let sub: SubscriberRef = self.participant(participant);
+        self.subscribe_channel(sub, channel).await;
+    }
+
+    /// Unsubscribes the participant from a single channel.
+    pub async fn remove_channel(&mut self, participant: SocketAddr, channel: Channel) {
+        let sub: SubscriberRef = self.participant(participant);
+        self.unsubscribe_channel(sub, channel).await;
+    }
+
+    /// Subscribes the participant to an inclusive range of channels.
+    pub async fn add_range(&mut self, participant: SocketAddr, min: Channel, max: Channel) {
+        let sub: SubscriberRef = self.participant(participant);
+        self.subscribe_range(sub, min, max).await;
+    }
+
+    /// Routes a datagram with an internal header, as if the given
+    /// participant had sent it, capturing a copy of it for every
+    /// participant subscribed to one of its recipient channels.
+    ///
+    /// Control messages that add or remove subscriptions are applied
+    /// to the sender, like the Message Director does. Other control
+    /// messages are ignored.
+    pub async fn route(&mut self, sender: SocketAddr, dg: Datagram) -> Result<()> {
+        let mut dgi: DatagramIterator = dg.clone().into();
+        let mut recipients: Vec<Channel> = vec![];
+
+        for _ in 0..dgi.read_recipient_count()? {
+            recipients.push(dgi.read_channel()?);
+        }
+
+        if recipients == [CONTROL_CHANNEL] {
+            return self.handle_control_msg(sender, &mut dgi).await;
+        }
+
+        for sub in self.lookup_ordered(recipients) {
+            self.sent.push((sub.get_remote(), dg.clone()));
+        }
+        Ok(())
+    }
+
+    async fn handle_control_msg(&mut self, sender: SocketAddr, dgi: &mut DatagramIterator) -> Result<()> {
+        match dgi.read_msg_type()? {
+            Protocol::MDAddChannel => self.add_channel(sender, dgi.read_channel()?).await,
+            Protocol::MDRemoveChannel => self.remove_channel(sender, dgi.read_channel()?).await,
+            Protocol::MDAddRange => {
+                let (min, max) = (dgi.read_channel()?, dgi.read_channel()?);
+                self.add_range(sender, min, max).await;
+            }
+            Protocol::MDRemoveRange => {
+                let (min, max) = (dgi.read_channel()?, dgi.read_channel()?);
+                let sub: SubscriberRef = self.participant(sender);
+
+                self.unsubscribe_range(sub, min, max).await;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Returns every datagram delivered so far, in order, along
+    /// with the participant that it was delivered to.
+    #[inline(always)]
+    pub fn sent(&self) -> &[(SocketAddr, Datagram)] {
+        &self.sent
+    }
+
+    /// Returns the datagrams delivered to the given participant, in order.
+    pub fn sent_to(&self, participant: SocketAddr) -> Vec<&Datagram> {
+        self.sent
+            .iter()
+            .filter(|(remote, _)| *remote == participant)
+            .map(|(_, dg)| dg)
+            .collect()
+    }
+
+    /// Takes the datagrams delivered so far, so that the next
+    /// assertions only see datagrams delivered after this call.
+    pub fn take_sent(&mut self) -> Vec<(SocketAddr, Datagram)> {
+        std::mem::take(&mut self.sent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Payloads of the captured datagrams, as datagrams are not comparable.
+    fn payloads(sent: &[(SocketAddr, Datagram)]) -> Vec<(SocketAddr, Vec<u8>)> {
+        sent.iter().map(|(remote, dg)| (*remote, dg.get_data())).collect()
+    }
+
+    #[tokio::test]
+    async fn captures_routed_datagrams() -> Result<()> {
+        let mut md: MockMessageDirector = MockMessageDirector::default();
+
+        let state_server: SocketAddr = SocketAddr::from(([127, 0, 0, 1], 1));
+        let client_agent: SocketAddr = SocketAddr::from(([127, 0, 0, 1], 2));
+        let bystander: SocketAddr = SocketAddr::from(([127, 0, 0, 1], 3));
+
+        // the State Server subscribes through a control message
+        let mut subscribe: Datagram = Datagram::default();
+        subscribe.add_control_header(Protocol::MDAddChannel.into())?;
+        subscribe.add_channel(4002)?;
+
+        md.route(state_server, subscribe).await?;
+        md.add_range(bystander, 5000, 5999).await;
+
+        let mut dg: Datagram = Datagram::default();
+        dg.add_internal_header(vec![4002], 1000, Protocol::SSObjectDeleteRAM.into())?;
+        dg.add_doid(123_456)?;
+
+        md.route(client_agent, dg.clone()).await?;
+
+        assert_eq!(payloads(md.sent()), vec![(state_server, dg.get_data())]);
+        assert_eq!(md.sent_to(state_server).len(), 1);
+        assert!(md.sent_to(bystander).is_empty());
+
+        // delivered to everyone subscribed, by channel or range
+        let mut both: Datagram = Datagram::default();
+        both.add_internal_header(vec![4002, 5050], 1000, Protocol::SSObjectDeleteRAM.into())?;
+
+        md.take_sent();
+        md.route(client_agent, both.clone()).await?;
+
+        assert_eq!(
+            payloads(&md.take_sent()),
+            vec![(state_server, both.get_data()), (bystander, both.get_data())]
+        );
+
+        // no longer delivered once unsubscribed
+        md.remove_channel(state_server, 4002).await;
+        md.route(client_agent, dg).await?;
+
+        assert!(md.sent().is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,164 @@
+/*
+    This file is part of Donet.
+
+    Copyright © 2024 Max Rodriguez <me@maxrdz.com>
+
+    Donet is free software; you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License,
+    as published by the Free Software Foundation, either version 3
+    of the License, or (at your option) any later version.
+
+    Donet is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public
+    License along with Donet. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Two-level queue of received datagrams, so that control messages
+//! and ejects are routed ahead of a backlog of bulk traffic.
+
+use donet_core::datagram::iterator::DatagramIterator;
+use donet_core::globals::{Channel, CONTROL_CHANNEL};
+use donet_core::Protocol;
+use donet_network::RecvData;
+use std::collections::VecDeque;
+use tokio::sync::mpsc;
+
+/// Most datagrams taken off the receive channel ahead of routing.
+/// Once full, senders wait on the bounded channel again, so the
+/// queue does not take away the backpressure of the channel.
+const QUEUE_CAPACITY: usize = 100;
+
+/// Holds datagrams waiting to be routed, in two levels. Control
+/// messages and `CLIENTAGENT_EJECT` messages are routed before any
+/// other message, and each level keeps the order it was received in,
+/// so bulk traffic, such as field updates, is never reordered.
+#[derive(Default)]
+pub struct RouteQueue {
+    priority: VecDeque<RecvData>,
+    bulk: VecDeque<RecvData>,
+}
+
+impl RouteQueue {
+    pub fn push(&mut self, data: RecvData) {
+        match is_priority(&data) {
+            true => self.priority.push_back(data),
+            false => self.bulk.push_back(data),
+        }
+    }
+
+    pub fn pop(&mut self) -> Option<RecvData> {
+        self.priority.pop_front().or_else(|| self.bulk.pop_front())
+    }
+
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.priority.len() + self.bulk.len()
+    }
+
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the next datagram to route, waiting for one if none
+    /// are queued. Datagrams already waiting on the channel are queued
+    /// first, so a control message received behind a backlog of bulk
+    /// traffic is routed before it. Returns `None` once the channel
+    /// is closed and the queue is empty.
+    pub async fn next(&mut self, rx: &mut mpsc::Receiver<RecvData>) -> Option<RecvData> {
+        if self.is_empty() {
+            self.push(rx.recv().await?);
+        }
+        while self.len() < QUEUE_CAPACITY {
+            match rx.try_recv() {
+                Ok(data) => self.push(data),
+                Err(_) => break,
+            }
+        }
+        self.pop()
+    }
+}
+
+/// Returns true for control messages and `CLIENTAGENT_EJECT` messages.
+/// Malformed datagrams are bulk, and are reported once routed.
+fn is_priority(data: &RecvData) -> bool {
+    let mut dgi: DatagramIterator = data.dg.clone().into();
+
+    let Ok(recipient_count) = dgi.read_recipient_count() else {
+        return false;
+    };
+    let recipients: Result<Vec<Channel>, _> = (0..recipient_count).map(|_| dgi.read_channel()).collect();
+
+    match recipients {
+        Ok(recipients) if recipients == [CONTROL_CHANNEL] => true,
+        Ok(_) => dgi.read_channel().is_ok() && dgi.read_msg_type() == Ok(Protocol::CAEject),
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use donet_core::datagram::datagram::Datagram;
+    use std::net::SocketAddr;
+
+    fn recv_data(dg: Datagram) -> RecvData {
+        RecvData {
+            remote: SocketAddr::from(([127, 0, 0, 1], 7199)),
+            dg: dg.clone(),
+            dgi: dg.into(),
+        }
+    }
+
+    fn bulk(n: u32) -> RecvData {
+        let mut dg: Datagram = Datagram::default();
+        dg.add_internal_header(vec![4000], 1000, Protocol::SSObjectSetField.into())
+            .unwrap();
+        dg.add_doid(n).unwrap();
+        recv_data(dg)
+    }
+
+    #[tokio::test]
+    async fn control_message_skips_bulk_backlog() {
+        let (tx, mut rx) = mpsc::channel::<RecvData>(QUEUE_CAPACITY);
+        let mut queue: RouteQueue = RouteQueue::default();
+
+        for n in 0..90 {
+            tx.send(bulk(n)).await.unwrap();
+        }
+
+        let mut control: Datagram = Datagram::default();
+        control.add_control_header(Protocol::MDAddChannel.into()).unwrap();
+        control.add_channel(4000).unwrap();
+        tx.send(recv_data(control.clone())).await.unwrap();
+
+        let mut eject: Datagram = Datagram::default();
+        eject
+            .add_internal_header(vec![1_000_000], 1000, Protocol::CAEject.into())
+            .unwrap();
+        tx.send(recv_data(eject.clone())).await.unwrap();
+
+        // both are routed before the backlog received ahead of them
+        assert_eq!(
+            queue.next(&mut rx).await.unwrap().dg.get_data(),
+            control.get_data()
+        );
+        assert_eq!(queue.next(&mut rx).await.unwrap().dg.get_data(), eject.get_data());
+
+        // and the backlog is still routed in the order it was received
+        for n in 0..90 {
+            assert_eq!(
+                queue.next(&mut rx).await.unwrap().dg.get_data(),
+                bulk(n).dg.get_data()
+            );
+        }
+        assert!(queue.is_empty());
+
+        drop(tx);
+        assert!(queue.next(&mut rx).await.is_none());
+    }
+}
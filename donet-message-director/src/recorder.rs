@@ -0,0 +1,196 @@
+/*
+    This file is part of Donet.
+
+    Copyright © 2024 Max Rodriguez <me@maxrdz.com>
+
+    Donet is free software; you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License,
+    as published by the Free Software Foundation, either version 3
+    of the License, or (at your option) any later version.
+
+    Donet is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public
+    License along with Donet. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Recording of routed datagrams to a file, and reading them
+//! back for replaying against a Message Director.
+
+use donet_core::datagram::datagram::*;
+use donet_core::datagram::iterator::*;
+use log::info;
+use std::io::Result;
+use tokio::fs::File;
+use tokio::io::AsyncWriteExt;
+
+/// Appends every datagram given to a recording file, each one
+/// prefixed with its length, using the MD's framing width.
+pub struct Recorder {
+    file: File,
+    framing: FramingWidth,
+}
+
+impl Recorder {
+    /// Creates the recording file, truncating it if it already exists.
+    pub async fn create(path: &str, framing: FramingWidth) -> Result<Self> {
+        let file: File = File::create(path).await?;
+
+        info!("Recording routed datagrams to {}.", path);
+
+        Ok(Self { file, framing })
+    }
+
+    pub async fn record(&mut self, dg: &Datagram) -> Result<()> {
+        let mut frame: Datagram = Datagram::default();
+        frame.override_cap(usize::MAX);
+
+        frame.add_frame_size(self.framing, dg.size())?;
+        frame.add_data(dg.get_data())?;
+
+        self.file.write_all(frame.get_buffer()).await?;
+        self.file.flush().await
+    }
+}
+
+/// Reads back all the datagrams in a recording file, in recorded order.
+pub async fn read_recording(path: &str, framing: FramingWidth) -> Result<Vec<Datagram>> {
+    let mut contents: Datagram = Datagram::default();
+    contents.override_cap(usize::MAX);
+
+    contents.add_data(tokio::fs::read(path).await?)?;
+
+    let mut dgi: DatagramIterator = contents.into();
+    let mut datagrams: Vec<Datagram> = vec![];
+
+    while dgi.get_remaining() != 0 {
+        let size: usize = dgi.read_frame_size(framing)?;
+
+        let mut dg: Datagram = Datagram::default();
+        dg.override_cap(framing.max_payload());
+
+        dg.add_data(dgi.read_data(size)?)?;
+        datagrams.push(dg);
+    }
+    Ok(datagrams)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::channel_map::ChannelCoordinator;
+    use crate::{CreateInfo, MessageDirector};
+    use donet_core::datagram::datagram::*;
+    use donet_core::globals::Channel;
+    use donet_core::Protocol;
+    use donet_daemon::config;
+    use donet_daemon::service::DonetService;
+    use donet_network::{Client, RecvData};
+    use std::io::Result;
+    use std::net::SocketAddr;
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::sync::mpsc;
+
+    /// Connects a new participant to the MD, subscribed to the given
+    /// channel, returning its client and the queue of datagrams it receives.
+    async fn connect_participant(
+        md: &mut MessageDirector,
+        listener: &TcpListener,
+        channel: Channel,
+    ) -> Result<(Client, mpsc::Receiver<RecvData>)> {
+        let stream: TcpStream = TcpStream::connect(listener.local_addr()?).await?;
+        let (md_side, remote) = listener.accept().await?;
+
+        let (md_tx, _) = mpsc::channel::<RecvData>(8);
+        md.new_connection(md_side, md_tx).await?;
+
+        let sub = md.get_subscriber_with_remote(remote).unwrap();
+        md.subscribe_channel(sub, channel).await;
+
+        let (tx, rx) = mpsc::channel::<RecvData>(8);
+        let mut client: Client = Client::from(stream);
+
+        let _ = client.spawn_recv_send_tasks(tx).await;
+
+        Ok((client, rx))
+    }
+
+    fn routed_datagram(recipients: Vec<Channel>, payload: &str) -> RecvData {
+        let mut dg: Datagram = Datagram::default();
+
+        dg.add_internal_header(recipients, 1000, Protocol::SSObjectSetField.into())
+            .unwrap();
+        dg.add_string(payload).unwrap();
+
+        RecvData {
+            remote: SocketAddr::from(([0, 0, 0, 0], 0)),
+            dg: dg.clone(),
+            dgi: dg.into(),
+        }
+    }
+
+    async fn drain(rx: &mut mpsc::Receiver<RecvData>, count: usize) -> Vec<Vec<u8>> {
+        let mut received: Vec<Vec<u8>> = vec![];
+
+        for _ in 0..count {
+            received.push(rx.recv().await.unwrap().dg.get_data());
+        }
+        assert!(rx.try_recv().is_err(), "Received more datagrams than expected.");
+        received
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn record_and_replay() -> Result<()> {
+        let path = std::env::temp_dir().join("donet-md-recording-test.bin");
+        let path: String = path.to_string_lossy().into_owned();
+
+        let md = MessageDirector::create(
+            CreateInfo {
+                service_conf: config::MessageDirector {
+                    bind: "127.0.0.1:0".into(),
+                    upstream: None,
+                    framing: None,
+                    write_timeout: None,
+                    max_pending_bytes: None,
+                    idle_timeout: None,
+                    keepalive: None,
+                    drain_timeout: None,
+                    shutdown_timeout: None,
+                    record: Some(path.clone()),
+                    watchdog_timeout: None,
+                    max_reconnect_attempts: None,
+                },
+                event_logger_url: None,
+            },
+            None,
+        )
+        .await?;
+        let mut md = md.lock().await;
+
+        let listener: TcpListener = TcpListener::bind("127.0.0.1:0").await?;
+
+        let (_first_client, mut first) = connect_participant(&mut md, &listener, 5000).await?;
+        let (_second_client, mut second) = connect_participant(&mut md, &listener, 6000).await?;
+
+        md.handle_datagram(routed_datagram(vec![5000], "first")).await?;
+        md.handle_datagram(routed_datagram(vec![6000], "second")).await?;
+        md.handle_datagram(routed_datagram(vec![5000, 6000], "both"))
+            .await?;
+        md.handle_datagram(routed_datagram(vec![7000], "nobody")).await?;
+
+        let first_live: Vec<Vec<u8>> = drain(&mut first, 2).await;
+        let second_live: Vec<Vec<u8>> = drain(&mut second, 2).await;
+
+        assert_eq!(md.replay(&path).await?, 4);
+
+        assert_eq!(drain(&mut first, 2).await, first_live);
+        assert_eq!(drain(&mut second, 2).await, second_live);
+
+        // replaying must not have recorded the datagrams again
+        assert_eq!(md.replay(&path).await?, 4);
+
+        std::fs::remove_file(&path)
+    }
+}
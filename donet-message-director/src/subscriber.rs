@@ -94,6 +94,21 @@ impl std::hash::Hash for SubscriberRef {
     }
 }
 
+/// Orders subscribers by their remote address, which is consistent
+/// with [`core::cmp::PartialEq`], so that the subscribers of a datagram
+/// can be delivered to in a deterministic order.
+impl Ord for SubscriberRef {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.hash_key.cmp(&other.hash_key)
+    }
+}
+
+impl PartialOrd for SubscriberRef {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 impl SubscriberRef {
     /// Thin wrapper of the [`tokio::sync::Mutex::lock`] function.
     pub async fn lock(&self) -> MutexGuard<'_, Subscriber> {
@@ -113,6 +128,40 @@ impl SubscriberRef {
     }
 }
 
+/// Snapshot of the channel and range subscriptions of a single
+/// connection, as reported by `CONTROL_GET_SUBSCRIPTIONS`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubscriptionDump {
+    pub remote: SocketAddr,
+    pub connection_name: Option<String>,
+    /// Single channel subscriptions, in ascending order.
+    pub channels: Vec<Channel>,
+    /// Inclusive channel range subscriptions, in ascending order.
+    pub ranges: Vec<(Channel, Channel)>,
+}
+
+impl SubscriptionDump {
+    /// Appends this dump to a `CONTROL_GET_SUBSCRIPTIONS_RESP` datagram.
+    pub fn write_datagram(&self, dg: &mut Datagram) -> Result<(), DatagramError> {
+        let overflow = |_| DatagramError::DatagramOverflow("Too many subscriptions to dump.");
+
+        dg.add_string(&self.remote.to_string())?;
+        dg.add_string(self.connection_name.as_deref().unwrap_or_default())?;
+
+        dg.add_u16(u16::try_from(self.channels.len()).map_err(overflow)?)?;
+        for channel in &self.channels {
+            dg.add_channel(*channel)?;
+        }
+
+        dg.add_u16(u16::try_from(self.ranges.len()).map_err(overflow)?)?;
+        for (min, max) in &self.ranges {
+            dg.add_channel(*min)?;
+            dg.add_channel(*max)?;
+        }
+        Ok(())
+    }
+}
+
 /// Simple representation of a participant, or subscriber,
 /// that is connected to a Message Director instance.
 #[derive(Debug)]
@@ -206,11 +255,30 @@ impl Subscriber {
         locked_client.stage_datagram(dg.clone()).await
     }
 
+    /// Takes a snapshot of this subscriber's subscriptions.
+    pub fn dump_subscriptions(&self) -> SubscriptionDump {
+        let mut channels: Vec<Channel> = self.subscribed_channels.iter().copied().collect();
+        channels.sort_unstable();
+
+        SubscriptionDump {
+            remote: self.remote,
+            connection_name: self.connection_name.clone(),
+            channels,
+            ranges: self
+                .subscribed_ranges
+                .iter()
+                .map(|range| (range.lower(), range.upper()))
+                .collect(),
+        }
+    }
+
     pub async fn receive_disconnect(&mut self) {
         // TODO!
     }
 
-    pub async fn post_remove(&mut self) {
-        // TODO!
+    /// Takes the post removes of this subscriber, leaving none behind,
+    /// so they can be sent out once the subscriber is removed.
+    pub fn take_post_removes(&mut self) -> MultiMap<Channel, Datagram> {
+        std::mem::take(&mut self.post_removes)
     }
 }
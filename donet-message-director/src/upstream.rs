@@ -17,14 +17,24 @@
     License along with Donet. If not, see <https://www.gnu.org/licenses/>.
 */
 
+use crate::MDTransport;
 use donet_core::datagram::datagram::*;
 use donet_core::{globals::*, Protocol};
-use donet_network::{tcp, Client, HasClient};
-use std::io::Result;
+use donet_daemon::service::spawn_in_service;
+use donet_network::transport::{Stream, Transport};
+use donet_network::{Client, HasClient};
+use log::warn;
+use std::io::{Error, Result};
 use std::ops::Range;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::Mutex;
 
+/// Time waited before the first reconnect attempt to the upstream MD,
+/// which doubles after every failed attempt, up to [`MAX_BACKOFF`].
+const MIN_BACKOFF: Duration = Duration::from_millis(250);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
 /// Represents a connection to an upstream Message Director service.
 pub struct UpstreamMD {
     connection: Arc<Mutex<Client>>,
@@ -37,12 +47,62 @@ impl HasClient for UpstreamMD {
 }
 
 impl UpstreamMD {
-    pub async fn connect(address: &str) -> Result<Self> {
+    pub async fn connect(address: &str, framing: FramingWidth, keepalive: Option<Duration>) -> Result<Self> {
+        let stream = MDTransport::connect(address).await?;
+
+        if let Some(idle) = keepalive {
+            stream.set_keepalive(idle)?;
+        }
+        let mut client: Client = Client::new(stream)?;
+        client.set_framing(framing);
+        client.set_task_spawner(spawn_in_service);
+
         Ok(Self {
-            connection: Arc::new(Mutex::new(tcp::Connection::connect(address).await?.into())),
+            connection: Arc::new(Mutex::new(client)),
         })
     }
 
+    /// Connects to the upstream MD, retrying with an exponential
+    /// backoff if it cannot be reached.
+    ///
+    /// Gives up after `max_attempts` reconnect attempts, returning the
+    /// error of the last attempt, or retries forever if `None`, so an
+    /// MD started before its upstream connects once the upstream is up.
+    pub async fn connect_with_retry(
+        address: &str,
+        framing: FramingWidth,
+        keepalive: Option<Duration>,
+        max_attempts: Option<u32>,
+    ) -> Result<Self> {
+        let mut backoff: Duration = MIN_BACKOFF;
+        let mut attempts: u32 = 0;
+
+        loop {
+            let err: Error = match Self::connect(address, framing, keepalive).await {
+                Ok(upstream) => return Ok(upstream),
+                Err(err) => err,
+            };
+
+            if max_attempts.is_some_and(|max| attempts >= max) {
+                return Err(Error::new(
+                    err.kind(),
+                    format!(
+                        "Gave up connecting to upstream MD at {} after {} reconnect attempts: {}",
+                        address, attempts, err
+                    ),
+                ));
+            }
+            warn!(
+                "Failed to connect to upstream MD at {}: {}. Retrying in {:?}.",
+                address, err, backoff
+            );
+            tokio::time::sleep(backoff).await;
+
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+            attempts += 1;
+        }
+    }
+
     /// Pushes the given [`Datagram`] into the send queue channel
     /// for the send loop Tokio task for this TCP stream.
     ///
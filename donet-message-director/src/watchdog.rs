@@ -0,0 +1,93 @@
+/*
+    This file is part of Donet.
+
+    Copyright © 2024 Max Rodriguez <me@maxrdz.com>
+
+    Donet is free software; you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License,
+    as published by the Free Software Foundation, either version 3
+    of the License, or (at your option) any later version.
+
+    Donet is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public
+    License along with Donet. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Watchdog over the routing task, which notices when a datagram
+//! has been taken off the queue, but is never done being routed.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Progress counters of the routing task. An idle MD routes nothing
+/// either, so a stall is only told apart by a datagram being routed.
+#[derive(Default)]
+pub struct Heartbeat {
+    started: AtomicU64,
+    routed: AtomicU64,
+}
+
+impl Heartbeat {
+    /// Called once a datagram is taken off the queue for routing.
+    pub fn begin(&self) {
+        self.started.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Called once the datagram being routed has been handled.
+    pub fn end(&self) {
+        self.routed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn sample(&self) -> (u64, u64) {
+        // read `routed` first, so it is never ahead of `started`
+        let routed: u64 = self.routed.load(Ordering::Relaxed);
+        (self.started.load(Ordering::Relaxed), routed)
+    }
+}
+
+/// Waits until the routing task stalls, and returns the number of
+/// datagrams routed before it did. The task is stalled once the same
+/// datagram has been routing for a whole window, so it may take up to
+/// twice the window to notice.
+pub async fn watch(heartbeat: Arc<Heartbeat>, window: Duration) -> u64 {
+    let (mut started, mut routed) = heartbeat.sample();
+
+    loop {
+        tokio::time::sleep(window).await;
+
+        let (now_started, now_routed) = heartbeat.sample();
+
+        if started > routed && now_routed == routed {
+            return routed;
+        }
+        (started, routed) = (now_started, now_routed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn idle_router_not_stalled() {
+        let heartbeat: Arc<Heartbeat> = Arc::default();
+        heartbeat.begin();
+        heartbeat.end();
+
+        let window: Duration = Duration::from_millis(20);
+        let watched = tokio::time::timeout(window * 10, watch(heartbeat.clone(), window)).await;
+
+        assert!(watched.is_err(), "Watchdog fired on an idle router.");
+
+        // a datagram that never finishes routing is a stall
+        heartbeat.begin();
+
+        let watched = tokio::time::timeout(window * 10, watch(heartbeat, window)).await;
+        assert_eq!(watched.expect("Watchdog did not fire."), 1);
+    }
+}
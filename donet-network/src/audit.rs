@@ -0,0 +1,344 @@
+/*
+    This file is part of Donet.
+
+    Copyright © 2024 Max Rodriguez <me@maxrdz.com>
+
+    Donet is free software; you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License,
+    as published by the Free Software Foundation, either version 3
+    of the License, or (at your option) any later version.
+
+    Donet is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public
+    License along with Donet. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Structured records of connections being accepted and closed,
+//! which services log for auditing.
+
+use donet_core::globals::Channel;
+use donet_core::protocol::EjectReason;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Bytes read from and written to a connection's stream,
+/// updated by the receive and send loops of its [`crate::Client`].
+#[derive(Debug, Default)]
+pub(crate) struct Traffic {
+    pub bytes_in: AtomicU64,
+    pub bytes_out: AtomicU64,
+    /// Datagrams staged to be sent, which are not written yet.
+    pub datagrams_pending: AtomicU64,
+    /// Bytes of the datagrams staged to be sent, which are not written yet.
+    pub bytes_pending: AtomicU64,
+}
+
+/// Handle to the statistics of a [`crate::Client`]'s connection,
+/// which outlives the client, so a connection can still be
+/// recorded once it is closed.
+#[derive(Debug, Clone)]
+pub struct ConnectionStats {
+    remote: SocketAddr,
+    local: SocketAddr,
+    connected_at: Instant,
+    pub(crate) traffic: Arc<Traffic>,
+}
+
+impl ConnectionStats {
+    pub(crate) fn new(remote: SocketAddr, local: SocketAddr) -> Self {
+        Self {
+            remote,
+            local,
+            connected_at: Instant::now(),
+            traffic: Arc::default(),
+        }
+    }
+
+    #[inline(always)]
+    pub fn get_remote(&self) -> SocketAddr {
+        self.remote
+    }
+
+    #[inline(always)]
+    pub fn bytes_in(&self) -> u64 {
+        self.traffic.bytes_in.load(Ordering::Relaxed)
+    }
+
+    #[inline(always)]
+    pub fn bytes_out(&self) -> u64 {
+        self.traffic.bytes_out.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of bytes read from and written to the
+    /// connection combined. Like its parts, it never decreases.
+    #[inline(always)]
+    pub fn bytes_total(&self) -> u64 {
+        self.bytes_in().saturating_add(self.bytes_out())
+    }
+
+    /// Returns the number of datagrams staged to be sent over the
+    /// connection, which have not been written to its stream yet.
+    #[inline(always)]
+    pub fn pending_datagrams(&self) -> u64 {
+        self.traffic.datagrams_pending.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of bytes of the datagrams staged to be sent
+    /// over the connection, which have not been written to its stream yet.
+    #[inline(always)]
+    pub fn pending_bytes(&self) -> u64 {
+        self.traffic.bytes_pending.load(Ordering::Relaxed)
+    }
+
+    /// Records the connection being accepted, along with the
+    /// channel assigned to it, if the service assigns one.
+    pub fn accept_record(&self, channel: Option<Channel>) -> AcceptRecord {
+        AcceptRecord {
+            remote: self.remote,
+            local: self.local,
+            channel,
+        }
+    }
+
+    /// Records the connection being closed, for the given reason.
+    pub fn disconnect_record(&self, reason: DisconnectReason) -> DisconnectRecord {
+        DisconnectRecord {
+            remote: self.remote,
+            reason,
+            duration: self.connected_at.elapsed(),
+            bytes_in: self.bytes_in(),
+            bytes_out: self.bytes_out(),
+        }
+    }
+}
+
+/// Traffic of a set of connections combined, such as every client
+/// connected to a Client Agent.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TrafficTotals {
+    pub connections: usize,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+}
+
+impl TrafficTotals {
+    pub fn of<'a>(connections: impl IntoIterator<Item = &'a ConnectionStats>) -> Self {
+        connections.into_iter().fold(Self::default(), |totals, stats| Self {
+            connections: totals.connections + 1,
+            bytes_in: totals.bytes_in.saturating_add(stats.bytes_in()),
+            bytes_out: totals.bytes_out.saturating_add(stats.bytes_out()),
+        })
+    }
+}
+
+impl std::fmt::Display for TrafficTotals {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "connections={} bytes_in={} bytes_out={}",
+            self.connections, self.bytes_in, self.bytes_out
+        )
+    }
+}
+
+/// Returns up to `n` of the given connections that have read and
+/// written the most bytes combined, starting from the most, to find
+/// the connections that use the most bandwidth.
+pub fn top_by_bytes<'a>(
+    connections: impl IntoIterator<Item = &'a ConnectionStats>,
+    n: usize,
+) -> Vec<&'a ConnectionStats> {
+    let mut connections: Vec<(u64, &ConnectionStats)> = connections
+        .into_iter()
+        .map(|stats| (stats.bytes_total(), stats))
+        .collect();
+
+    // snapshot each total once, as the counters keep changing
+    connections.sort_by(|(a, _), (b, _)| b.cmp(a));
+    connections.truncate(n);
+    connections.into_iter().map(|(_, stats)| stats).collect()
+}
+
+/// Why a connection was closed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DisconnectReason {
+    /// The remote closed the connection.
+    Closed,
+    /// The connection failed, or was dropped, such as for being idle.
+    Error(String),
+    /// A client was ejected with a `CLIENT_EJECT` message.
+    Ejected(EjectReason),
+    /// The service closed the connection, such as when shutting down.
+    Shutdown,
+}
+
+impl std::fmt::Display for DisconnectReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Closed => write!(f, "reason=closed"),
+            Self::Error(err) => write!(f, "reason=error error={:?}", err),
+            Self::Ejected(reason) => write!(f, "reason=ejected eject_code={}", u16::from(*reason)),
+            Self::Shutdown => write!(f, "reason=shutdown"),
+        }
+    }
+}
+
+/// Logged when a connection is accepted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AcceptRecord {
+    pub remote: SocketAddr,
+    pub local: SocketAddr,
+    pub channel: Option<Channel>,
+}
+
+impl std::fmt::Display for AcceptRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "connection accepted remote={} local={}",
+            self.remote, self.local
+        )?;
+
+        match self.channel {
+            Some(channel) => write!(f, " channel={}", channel),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Logged when a connection is closed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DisconnectRecord {
+    pub remote: SocketAddr,
+    pub reason: DisconnectReason,
+    pub duration: Duration,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+}
+
+impl std::fmt::Display for DisconnectRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "connection closed remote={} {} duration={:.3}s bytes_in={} bytes_out={}",
+            self.remote,
+            self.reason,
+            self.duration.as_secs_f64(),
+            self.bytes_in,
+            self.bytes_out
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Client, RecvData};
+    use donet_core::datagram::datagram::Datagram;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::sync::mpsc;
+
+    #[tokio::test]
+    async fn disconnect_record_of_closed_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut remote = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        let (stream, remote_addr) = listener.accept().await.unwrap();
+
+        let mut client: Client = Client::from(stream);
+        let stats: ConnectionStats = client.get_stats();
+
+        let accepted: AcceptRecord = stats.accept_record(Some(1000));
+        assert_eq!(accepted.remote, remote_addr);
+        assert!(accepted.to_string().ends_with(" channel=1000"));
+
+        let (tx, mut rx) = mpsc::channel::<RecvData>(1);
+        let (recv_handle, _send_handle) = client.spawn_recv_send_tasks(tx).await;
+
+        let mut dg: Datagram = Datagram::default();
+        dg.add_string("hello").unwrap();
+        client.stage_datagram(dg).await.unwrap();
+
+        // a framed datagram of 3 bytes, then the remote hangs up
+        remote.write_all(&[3, 0, 1, 2, 3]).await.unwrap();
+        assert_eq!(rx.recv().await.unwrap().dg.get_data(), vec![1, 2, 3]);
+
+        // the remote reads what we sent, so that hanging up is not a reset
+        let mut hello: [u8; 9] = [0; 9];
+        remote.read_exact(&mut hello).await.unwrap();
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        drop(remote);
+
+        recv_handle.await.unwrap().unwrap();
+
+        let record: DisconnectRecord = stats.disconnect_record(DisconnectReason::Closed);
+
+        assert_eq!(record.remote, remote_addr);
+        assert_eq!(record.reason, DisconnectReason::Closed);
+        assert!(record.duration >= Duration::from_millis(20));
+        assert_eq!(record.bytes_in, 5);
+        assert_eq!(record.bytes_out, 9); // size tag, string length, "hello"
+
+        let logged: String = record.to_string();
+        assert!(logged.contains(" reason=closed duration="), "{}", logged);
+
+        let ejected = DisconnectReason::Ejected(EjectReason::SecurityViolation);
+        assert_eq!(ejected.to_string(), "reason=ejected eject_code=113");
+    }
+
+    #[tokio::test]
+    async fn traffic_totals_and_top_connections() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let (tx, mut rx) = mpsc::channel::<RecvData>(8);
+
+        let mut remotes: Vec<TcpStream> = vec![];
+        let mut clients: Vec<Client> = vec![];
+
+        for _ in 0..3 {
+            remotes.push(TcpStream::connect(listener.local_addr().unwrap()).await.unwrap());
+
+            let mut client: Client = Client::from(listener.accept().await.unwrap().0);
+            let _ = client.spawn_recv_send_tasks(tx.clone()).await;
+            clients.push(client);
+        }
+        let stats: Vec<ConnectionStats> = clients.iter().map(|client| client.get_stats()).collect();
+
+        // each remote sends framed datagrams of a known size
+        for (remote, payload_size) in remotes.iter_mut().zip([10_usize, 100, 1]) {
+            let mut framed: Vec<u8> = u16::try_from(payload_size).unwrap().to_le_bytes().to_vec();
+            framed.extend(vec![0_u8; payload_size]);
+
+            remote.write_all(&framed).await.unwrap();
+            rx.recv().await.unwrap();
+        }
+
+        // and the last connection is sent a datagram of 20 bytes
+        let mut dg: Datagram = Datagram::default();
+        dg.add_data(vec![0; 20]).unwrap();
+        clients[2].stage_datagram(dg).await.unwrap();
+
+        let mut received: [u8; 22] = [0; 22];
+        remotes[2].read_exact(&mut received).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        let totals: TrafficTotals = TrafficTotals::of(&stats);
+
+        assert_eq!(totals.connections, 3);
+        assert_eq!(totals.bytes_in, 12 + 102 + 3);
+        assert_eq!(totals.bytes_out, 22);
+        assert_eq!(totals.to_string(), "connections=3 bytes_in=117 bytes_out=22");
+
+        let top: Vec<SocketAddr> = top_by_bytes(&stats, 2).iter().map(|s| s.get_remote()).collect();
+
+        assert_eq!(top, vec![stats[1].get_remote(), stats[2].get_remote()]);
+        assert_eq!(stats[2].bytes_total(), 25);
+        assert!(top_by_bytes(&stats, 0).is_empty());
+    }
+}
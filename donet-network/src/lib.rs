@@ -17,30 +17,43 @@
     License along with Donet. If not, see <https://www.gnu.org/licenses/>.
 */
 
+pub mod audit;
 pub mod tcp;
+pub mod transport;
 pub mod udp;
+#[cfg(unix)]
+pub mod unix;
 
+use audit::ConnectionStats;
 use donet_core::datagram::datagram::*;
 use donet_core::datagram::iterator::*;
-use donet_core::globals::*;
 use log::{info, warn};
 use std::collections::VecDeque;
+use std::future::Future;
 use std::io;
 use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use tokio::io::AsyncWriteExt;
-use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
-use tokio::sync::{mpsc, Mutex};
+use tokio::sync::{mpsc, Mutex, Notify};
 use tokio::task::JoinHandle;
+use transport::{ReadHalf, Stream, WriteHalf};
 
 /// Size of the byte buffer for incoming TCP packets.
 ///
-/// Tokio gives us entire TCP messages (after reassembling
-/// segments) so we should expect this buffer to fill above
-/// the TCP max segment size (MSS).
+/// Reads are not aligned to datagram frames, so a read may end in the
+/// middle of a datagram, which is completed by the reads that follow.
 const TCP_READ_BUFFER_SIZE: usize = 300 * 1024; // 300 kb
 
+/// Largest datagram accepted from a stream framed with 32-bit length
+/// prefixes. A larger length prefix is most likely read from a stream
+/// framed with a different width, and buffering a datagram that large
+/// would let the peer exhaust our memory.
+const MAX_FRAMED_DATAGRAM_SIZE: usize = 16 * 1024 * 1024; // 16 mb
+
 /// Data sent via an MPSC channel from a
 /// client receive loop task to a service
 /// handle receive task.
@@ -56,40 +69,64 @@ pub struct RecvData {
 
 pub type RecvSendHandles = (JoinHandle<io::Result<()>>, JoinHandle<io::Result<()>>);
 
+/// The receive or send loop of a [`Client`], to be spawned as a task.
+pub type ClientTask = Pin<Box<dyn Future<Output = io::Result<()>> + Send>>;
+
+/// Spawns the receive and send loops of a [`Client`] as tasks.
+pub type TaskSpawner = fn(ClientTask) -> JoinHandle<io::Result<()>>;
+
 /// Ensures the implementing type owns a reference
 /// to a [`Client`] structure.
 pub trait HasClient {
     fn get_client(&self) -> Arc<Mutex<Client>>;
 }
 
-/// Represents a network client connected over TCP.
-#[derive(Debug)]
+/// Represents a network client connected over a [`transport::Transport`].
 pub struct Client {
     remote: SocketAddr,
     local: SocketAddr,
     /// Queue of datagrams to be sent. Use this to
     /// queue datagrams to be sent to the remote address
-    /// of this [`Client`]'s TCP stream.
+    /// of this [`Client`]'s stream.
     send_queue_channel: Option<mpsc::Sender<Datagram>>,
     /// Wrapped in `Option` as we will consume these halves for tasks
-    tcp_read_half: Option<OwnedReadHalf>,
-    tcp_write_half: Option<OwnedWriteHalf>,
+    read_half: Option<ReadHalf>,
+    write_half: Option<WriteHalf>,
+    /// Width of the length prefix of datagrams in the stream.
+    framing: FramingWidth,
+    /// Maximum time to wait for the remote to accept our writes.
+    /// A remote that stops reading is disconnected once exceeded.
+    write_timeout: Option<Duration>,
+    /// Maximum number of bytes staged to be sent, but not yet written.
+    /// A remote that lets them build up past it is disconnected.
+    max_pending_bytes: Option<u64>,
+    /// Notified to make the send loop drop the connection, once
+    /// more than `max_pending_bytes` are staged to be sent.
+    overflow: Arc<Notify>,
+    /// Maximum time to wait for the remote to send us anything.
+    /// A remote that goes silent is disconnected once exceeded.
+    idle_timeout: Option<Duration>,
+    /// Spawns the receive and send loop tasks.
+    spawner: TaskSpawner,
+    stats: ConnectionStats,
+}
+
+impl std::fmt::Debug for Client {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Client")
+            .field("remote", &self.remote)
+            .field("local", &self.local)
+            .field("framing", &self.framing)
+            .field("write_timeout", &self.write_timeout)
+            .field("max_pending_bytes", &self.max_pending_bytes)
+            .field("idle_timeout", &self.idle_timeout)
+            .finish_non_exhaustive()
+    }
 }
 
 impl From<TcpStream> for Client {
     fn from(value: TcpStream) -> Self {
-        let remote = value.peer_addr().expect("Failed to get remote address.");
-        let local = value.local_addr().expect("Failed to get local address.");
-
-        let (read_half, write_half) = value.into_split();
-
-        Self {
-            remote,
-            local,
-            send_queue_channel: None,
-            tcp_read_half: Some(read_half),
-            tcp_write_half: Some(write_half),
-        }
+        Self::new(value).expect("Failed to get TCP stream addresses.")
     }
 }
 
@@ -102,17 +139,31 @@ impl From<tcp::Connection> for Client {
     }
 }
 
-/// Util macro for truncated datagram scenarios.
-macro_rules! truncated_datagram {
-    ($remote:expr, $err:expr) => {{
-        warn!("Received truncated datagram from {}: {}", $remote, $err);
+impl Client {
+    /// Creates a new [`Client`] from a connected stream
+    /// of any [`transport::Transport`].
+    pub fn new(stream: impl Stream) -> io::Result<Self> {
+        let remote: SocketAddr = stream.peer_addr()?;
+        let local: SocketAddr = stream.local_addr()?;
 
-        // no more bytes to read, break read loop
-        break;
-    }};
-}
+        let (read_half, write_half) = stream.into_halves();
+
+        Ok(Self {
+            remote,
+            local,
+            send_queue_channel: None,
+            read_half: Some(read_half),
+            write_half: Some(write_half),
+            framing: FramingWidth::default(),
+            write_timeout: None,
+            max_pending_bytes: None,
+            overflow: Arc::new(Notify::new()),
+            idle_timeout: None,
+            spawner: tokio::spawn,
+            stats: ConnectionStats::new(remote, local),
+        })
+    }
 
-impl Client {
     /// Returns the remote IPv4/6 address of this client.
     pub fn get_remote(&self) -> SocketAddr {
         self.remote
@@ -123,14 +174,94 @@ impl Client {
         self.local
     }
 
+    /// Returns a handle to the statistics of this client's connection,
+    /// for recording it when it is accepted and when it is closed.
+    pub fn get_stats(&self) -> ConnectionStats {
+        self.stats.clone()
+    }
+
+    /// Sets the width of the length prefix used to frame datagrams
+    /// in this client's TCP stream. Must be set before spawning the
+    /// receive and send tasks.
+    pub fn set_framing(&mut self, width: FramingWidth) {
+        self.framing = width
+    }
+
+    /// Sets the maximum time a write to this client's TCP stream may
+    /// take before the client is disconnected as a slow consumer.
+    /// `None` waits indefinitely. Must be set before spawning the
+    /// receive and send tasks.
+    pub fn set_write_timeout(&mut self, timeout: Option<Duration>) {
+        self.write_timeout = timeout
+    }
+
+    /// Sets the maximum number of bytes that may be staged to be sent to
+    /// this client, but not yet written to its stream, before the client
+    /// is disconnected as a slow consumer. `None` allows any number.
+    pub fn set_max_pending_bytes(&mut self, max: Option<u64>) {
+        self.max_pending_bytes = max
+    }
+
+    /// Sets the maximum time this client's stream may go without
+    /// receiving any data before the client is disconnected as idle.
+    /// `None` waits indefinitely. Must be set before spawning the
+    /// receive and send tasks.
+    pub fn set_idle_timeout(&mut self, timeout: Option<Duration>) {
+        self.idle_timeout = timeout
+    }
+
+    /// Sets the function that spawns the receive and send tasks, such as
+    /// one that runs them as tasks of the service that owns this client.
+    /// Defaults to [`tokio::spawn`]. Must be set before spawning the
+    /// receive and send tasks.
+    pub fn set_task_spawner(&mut self, spawner: TaskSpawner) {
+        self.spawner = spawner
+    }
+
     /// Sends the given [`Datagram`] to the send loop task, via the
     /// [`Client`]'s [`mpsc::Sender<Datagram>`].
+    ///
+    /// If staging the datagram would put more bytes pending than the
+    /// maximum set with [`Self::set_max_pending_bytes`], the datagram
+    /// is not staged, and the connection is dropped.
     pub async fn stage_datagram(&mut self, dg: Datagram) -> Result<(), mpsc::error::SendError<Datagram>> {
         let tx = self
             .send_queue_channel
             .as_mut()
             .expect("recv/send tasks dont exist");
-        tx.send(dg).await
+
+        let size: u64 = dg.size() as u64;
+        let pending_bytes: &AtomicU64 = &self.stats.traffic.bytes_pending;
+
+        if let Some(max) = self.max_pending_bytes {
+            let staged: u64 = pending_bytes.load(Ordering::Relaxed) + size;
+
+            if staged > max {
+                warn!(
+                    "Disconnecting {}: {} bytes staged to be sent, over the limit of {}.",
+                    self.remote, staged, max
+                );
+                self.overflow.notify_one();
+                return Err(mpsc::error::SendError(dg));
+            }
+        }
+
+        // counted first, as the send loop may write it as soon as it is sent
+        let pending: &AtomicU64 = &self.stats.traffic.datagrams_pending;
+        pending.fetch_add(1, Ordering::Relaxed);
+        pending_bytes.fetch_add(size, Ordering::Relaxed);
+
+        tx.send(dg).await.inspect_err(|_| {
+            pending.fetch_sub(1, Ordering::Relaxed);
+            pending_bytes.fetch_sub(size, Ordering::Relaxed);
+        })
+    }
+
+    /// Closes the send queue of this client. Datagrams that are already
+    /// staged are still written, after which the connection is closed.
+    /// No more datagrams may be staged once closed.
+    pub fn close(&mut self) {
+        self.send_queue_channel = None
     }
 
     /// Spawns a tokio task for `Self::receive_loop` and `Self::send_loop`,
@@ -140,10 +271,16 @@ impl Client {
     ///
     /// - The second tuple element is the [`JoinHandle`] for the send loop.
     pub async fn spawn_recv_send_tasks(&mut self, incoming_tx: mpsc::Sender<RecvData>) -> RecvSendHandles {
-        let read_half = self.tcp_read_half.take().unwrap();
-        let write_half = self.tcp_write_half.take().unwrap();
+        let read_half = self.read_half.take().unwrap();
+        let write_half = self.write_half.take().unwrap();
 
-        let recv_handle = tokio::spawn(Self::receive_loop(read_half, incoming_tx));
+        let recv_handle = (self.spawner)(Box::pin(Self::receive_loop(
+            read_half,
+            self.stats.clone(),
+            incoming_tx,
+            self.framing,
+            self.idle_timeout,
+        )));
 
         // send channel.
         // queues datagrams to be sent to the remote address of this client.
@@ -151,50 +288,75 @@ impl Client {
 
         self.send_queue_channel = Some(tx);
 
-        let send_handle = tokio::spawn(Self::send_loop(write_half, rx));
+        let send_loop = Self::send_loop(
+            write_half,
+            self.stats.clone(),
+            rx,
+            self.framing,
+            self.write_timeout,
+            self.overflow.clone(),
+        );
+        let recv_abort_handle = recv_handle.abort_handle();
+
+        // If the send loop ends, because the send queue was closed or it
+        // failed, such as when disconnecting a slow consumer, also stop
+        // the receive loop to close the connection.
+        let send_handle = (self.spawner)(Box::pin(async move {
+            let res: io::Result<()> = send_loop.await;
+
+            recv_abort_handle.abort();
+            res
+        }));
 
         (recv_handle, send_handle)
     }
 
-    /// Main asynchronous loop for handling receiving TCP packets
-    /// from this client's TCP stream.
+    /// Main asynchronous loop for handling receiving packets
+    /// from this client's stream.
     async fn receive_loop(
-        read_half: OwnedReadHalf,
+        mut read_half: ReadHalf,
+        stats: ConnectionStats,
         incoming_queue_tx: mpsc::Sender<RecvData>,
+        framing: FramingWidth,
+        idle_timeout: Option<Duration>,
     ) -> io::Result<()> {
-        let remote: SocketAddr = read_half.peer_addr()?;
+        let remote: SocketAddr = stats.get_remote();
 
-        loop {
-            read_half.readable().await?;
+        // kept on the heap, so it is not stored in the async task.
+        let mut buffer: Vec<u8> = vec![0_u8; TCP_READ_BUFFER_SIZE];
+        // Bytes received, but not yet split into datagrams. The datagram
+        // at the end of a read may not be complete until the next read.
+        let mut stream_bytes: Vec<u8> = vec![];
 
-            // initializing this **after** the `await` point prevents
-            // it from being stored in the async task.
-            let mut buffer = [0_u8; TCP_READ_BUFFER_SIZE];
+        loop {
+            let read = read_half.read(&mut buffer);
+
+            let result: io::Result<usize> = match idle_timeout {
+                Some(timeout) => match tokio::time::timeout(timeout, read).await {
+                    Ok(result) => result,
+                    Err(_) => {
+                        warn!(
+                            "Disconnecting {}: remote sent nothing within {:?}.",
+                            remote, timeout
+                        );
+                        return Err(io::Error::new(io::ErrorKind::TimedOut, "Idle connection."));
+                    }
+                },
+                None => read.await,
+            };
 
-            match read_half.try_read(&mut buffer) {
+            match result {
                 Ok(0) => {
                     info!("Lost connection from {}", remote);
 
                     return Ok(()); // client closed TCP connection
                 }
                 Ok(len) => {
-                    let mut dg: Datagram = Datagram::default();
+                    stats.traffic.bytes_in.fetch_add(len as u64, Ordering::Relaxed);
 
-                    dg.override_cap(TCP_READ_BUFFER_SIZE);
+                    stream_bytes.extend_from_slice(&buffer[..len]);
 
-                    // The buffer is always a fixed size. Let's make a slice that
-                    // contains only the length of the datagram received.
-                    let mut buf_slice: Vec<u8> = buffer.to_vec();
-                    buf_slice.truncate(len);
-
-                    // we can safely unwrap here, since the size cap for `dg` was
-                    // overridden to be the size of the read buffer size.
-                    dg.add_data(buf_slice).unwrap();
-
-                    Self::split_datagrams(remote, &incoming_queue_tx, dg.into()).await;
-                    continue;
-                }
-                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    Self::split_datagrams(remote, &incoming_queue_tx, &mut stream_bytes, framing).await?;
                     continue;
                 }
                 Err(err) => {
@@ -204,33 +366,65 @@ impl Client {
         }
     }
 
-    /// Handles separating TCP packets into separate Datagrams, if multiple
-    /// found in the packet, and sends each individual datagram over the
-    /// mpsc channel using the given [`mpsc::Sender`].
+    /// Handles separating the bytes received from the stream into separate
+    /// Datagrams, and sends each individual datagram over the mpsc channel
+    /// using the given [`mpsc::Sender`].
+    ///
+    /// The split datagrams are removed from `stream_bytes`. The bytes of a
+    /// datagram that is not yet complete are left in it, to be completed by
+    /// the bytes of the next read.
+    ///
+    /// Returns an [`io::ErrorKind::InvalidData`] error if a datagram is larger
+    /// than we accept, such as when the remote frames datagrams with a
+    /// different [`FramingWidth`] than ours.
     async fn split_datagrams(
         remote: SocketAddr,
         incoming_tx: &mpsc::Sender<RecvData>,
-        mut dgi: DatagramIterator,
-    ) {
-        loop {
-            let sizetag: DgSizeTag = match dgi.read_size() {
-                Ok(size) => size,
-                Err(err) => truncated_datagram!(remote, err),
-            };
+        stream_bytes: &mut Vec<u8>,
+        framing: FramingWidth,
+    ) -> io::Result<()> {
+        let max_size: usize = framing.max_payload().min(MAX_FRAMED_DATAGRAM_SIZE);
+        // bytes of the datagrams split so far
+        let mut split: usize = 0;
+
+        // while there is at least another size tag ahead,
+        // try separating another datagram
+        while stream_bytes.len() - split >= framing.size() {
+            let payload_start: usize = split + framing.size();
+
+            let mut size_tag: Datagram = Datagram::default();
+            size_tag.add_data(stream_bytes[split..payload_start].to_vec())?;
+
+            let sizetag: usize = DatagramIterator::from(size_tag).read_frame_size(framing)?;
+
+            if sizetag > max_size {
+                warn!(
+                    "Disconnecting {}: received datagram of {} bytes, over the limit of {}.",
+                    remote, sizetag, max_size
+                );
+                warn!("Check that both ends of the connection use the same datagram framing width.");
+
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "Oversized datagram."));
+            }
 
             if sizetag == 0 {
                 warn!("Received datagram with a size tag of 0. Skipping.");
+                split = payload_start;
+                continue;
+            }
+
+            // the rest of this datagram is yet to be received
+            if stream_bytes.len() - payload_start < sizetag {
                 break;
             }
+            let payload_end: usize = payload_start + sizetag;
 
             let mut individual_dg: Datagram = Datagram::default();
+            individual_dg.override_cap(max_size);
 
-            let payload: Vec<u8> = match dgi.read_data(sizetag.into()) {
-                Ok(data) => data,
-                Err(err) => truncated_datagram!(remote, err),
-            };
-
-            assert!(individual_dg.add_data(payload).is_ok());
+            assert!(individual_dg
+                .add_data(stream_bytes[payload_start..payload_end].to_vec())
+                .is_ok());
 
             // send individual datagram to the receive ncoming queue
             incoming_tx
@@ -242,46 +436,53 @@ impl Client {
                 .await
                 .expect("Tried to send received packet, but MPSC channel closed.");
 
-            let remaining: usize = dgi.get_remaining();
-
-            // if this packet has at least another size tag ahead,
-            // try separating another datagram
-            if remaining < std::mem::size_of::<DgSizeTag>() {
-                // we *should* have 0 bytes left to read, if this is a
-                // good packet. if not, its truncated (or we read it wrong)
-                if remaining != 0 {
-                    truncated_datagram!(remote, "Expected more bytes!");
-                }
-                break;
-            }
+            split = payload_end;
         }
+        stream_bytes.drain(..split);
+        Ok(())
     }
 
-    /// Main asynchronous loop for handling sending TCP packets to the
-    /// remote address of this [`Client`]'s TCP stream.
+    /// Main asynchronous loop for handling sending packets to the
+    /// remote address of this [`Client`]'s stream.
     ///
     /// The queue of datagrams to be sent is received by this task
     /// via the given [`mpsc::Receiver<Datagram>`] struct.
     async fn send_loop(
-        mut write_half: OwnedWriteHalf,
+        mut write_half: WriteHalf,
+        stats: ConnectionStats,
         mut send_queue_rx: mpsc::Receiver<Datagram>,
+        framing: FramingWidth,
+        write_timeout: Option<Duration>,
+        overflow: Arc<Notify>,
     ) -> io::Result<()> {
+        let remote: SocketAddr = stats.get_remote();
+        let overflowed = || io::Error::other("Too many bytes pending.");
+
         loop {
             let mut buffer: Vec<Datagram> = vec![];
 
-            // await until notified that more packets was added to the queue
-            let n = send_queue_rx.recv_many(&mut buffer, 1000).await;
+            // await until notified that more packets was added to the queue,
+            // or that too many bytes were staged while the last were written
+            let n = tokio::select! {
+                n = send_queue_rx.recv_many(&mut buffer, 1000) => n,
+                _ = overflow.notified() => return Err(overflowed()),
+            };
 
-            // if `recv_many` returns 0, it means the MPSC channel was closed.
+            // if `recv_many` returns 0, it means the MPSC channel was closed,
+            // and everything that was staged before has been written.
             if n == 0 {
-                todo!("unhandled error. tcp client dg queue receiver returned 0.")
+                return Ok(());
             }
 
+            let staged_bytes: u64 = buffer.iter().map(|dg| dg.size() as u64).sum();
             let mut queue: VecDeque<Datagram> = VecDeque::from(buffer);
 
             // prepare write buffer by reading the send queue
             let mut write_buffer_dg: Datagram = Datagram::default();
 
+            // The write buffer holds many framed datagrams at once.
+            write_buffer_dg.override_cap(usize::MAX);
+
             while !queue.is_empty() {
                 let mut dgi: DatagramIterator = queue.pop_front().unwrap().into();
 
@@ -293,7 +494,10 @@ impl Client {
 
                 assert!(dg_payload.is_ok(), "Tried to read past datagram.");
 
-                write_buffer_dg.add_size(sizetag as DgSizeTag).unwrap();
+                if let Err(err) = write_buffer_dg.add_frame_size(framing, sizetag) {
+                    warn!("Dropping datagram that cannot be framed: {}", err);
+                    continue;
+                }
                 write_buffer_dg.add_data(dg_payload.unwrap()).unwrap();
 
                 debug_assert!(
@@ -302,10 +506,287 @@ impl Client {
                 );
             }
 
-            // send staged datagrams to client
-            write_half.writable().await?;
-            write_half.write_all(write_buffer_dg.get_buffer()).await?;
-            write_half.flush().await?;
+            // send staged datagrams to client, unless too many bytes
+            // were staged while the remote was not reading them
+            let write = async {
+                tokio::select! {
+                    res = write_half.write_all(write_buffer_dg.get_buffer()) => res?,
+                    _ = overflow.notified() => return Err(overflowed()),
+                }
+                write_half.flush().await
+            };
+
+            match write_timeout {
+                Some(timeout) => {
+                    if tokio::time::timeout(timeout, write).await.is_err() {
+                        warn!(
+                            "Disconnecting {}: remote did not read our writes within {:?}.",
+                            remote, timeout
+                        );
+                        return Err(io::Error::new(io::ErrorKind::TimedOut, "Slow consumer."));
+                    }
+                }
+                None => write.await?,
+            }
+            let written: u64 = write_buffer_dg.size() as u64;
+
+            stats.traffic.bytes_out.fetch_add(written, Ordering::Relaxed);
+            stats
+                .traffic
+                .datagrams_pending
+                .fetch_sub(n as u64, Ordering::Relaxed);
+            stats
+                .traffic
+                .bytes_pending
+                .fetch_sub(staged_bytes, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn split(framing: FramingWidth, payloads: &[Vec<u8>]) -> Vec<Vec<u8>> {
+        let remote: SocketAddr = "127.0.0.1:7199".parse().unwrap();
+        let (tx, mut rx) = mpsc::channel::<RecvData>(payloads.len());
+
+        let mut stream: Datagram = Datagram::default();
+        stream.override_cap(usize::MAX);
+
+        for payload in payloads {
+            stream.add_frame_size(framing, payload.len()).unwrap();
+            stream.add_data(payload.clone()).unwrap();
+        }
+        let mut stream_bytes: Vec<u8> = stream.get_data();
+
+        Client::split_datagrams(remote, &tx, &mut stream_bytes, framing)
+            .await
+            .unwrap();
+        drop(tx);
+
+        assert!(stream_bytes.is_empty(), "Not every datagram was split.");
+
+        let mut received: Vec<Vec<u8>> = vec![];
+
+        while let Some(data) = rx.recv().await {
+            received.push(data.dg.get_data());
         }
+        received
+    }
+
+    #[tokio::test]
+    async fn split_datagrams_16_bit_framing() {
+        let payloads: Vec<Vec<u8>> = vec![vec![1, 2, 3], vec![4, 5], vec![u8::MAX; 300]];
+
+        assert_eq!(split(FramingWidth::U16, &payloads).await, payloads);
+    }
+
+    #[tokio::test]
+    async fn slow_consumer_disconnected() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let stream = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+
+        // the remote end accepts the connection, but never reads
+        let (_remote, _) = listener.accept().await.unwrap();
+
+        let mut client: Client = Client::from(stream);
+        client.set_write_timeout(Some(Duration::from_millis(200)));
+
+        let (tx, _rx) = mpsc::channel::<RecvData>(1);
+        let (recv_handle, send_handle) = client.spawn_recv_send_tasks(tx).await;
+
+        // keep staging datagrams until the socket buffers fill up, and
+        // the send loop drops the connection, closing its queue
+        let mut closed: bool = false;
+
+        for _ in 0..100_000 {
+            let mut dg: Datagram = Datagram::default();
+            dg.add_data(vec![0_u8; 60_000]).unwrap();
+
+            if client.stage_datagram(dg).await.is_err() {
+                closed = true;
+                break;
+            }
+        }
+        assert!(closed, "Connection was not dropped.");
+
+        let err: io::Error = send_handle.await.unwrap().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+        assert!(recv_handle.await.unwrap_err().is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn too_many_pending_bytes_disconnected() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let stream = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+
+        // the remote end accepts the connection, but never reads
+        let (_remote, _) = listener.accept().await.unwrap();
+
+        let mut client: Client = Client::from(stream);
+        client.set_max_pending_bytes(Some(1_000_000));
+
+        let (tx, _rx) = mpsc::channel::<RecvData>(1);
+        let (recv_handle, send_handle) = client.spawn_recv_send_tasks(tx).await;
+
+        // keep staging datagrams until the socket buffers fill up, and
+        // the datagrams left pending go over the limit
+        let mut refused: bool = false;
+
+        for _ in 0..100_000 {
+            let mut dg: Datagram = Datagram::default();
+            dg.add_data(vec![0_u8; 60_000]).unwrap();
+
+            if client.stage_datagram(dg).await.is_err() {
+                refused = true;
+                break;
+            }
+        }
+        assert!(refused, "Datagram over the limit was staged.");
+        assert!(client.get_stats().pending_bytes() <= 1_000_000);
+
+        let err: io::Error = send_handle.await.unwrap().unwrap_err();
+        assert_eq!(err.to_string(), "Too many bytes pending.");
+
+        assert!(recv_handle.await.unwrap_err().is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn split_datagrams_32_bit_framing() {
+        // The last datagram is too large for a 16-bit length prefix.
+        let payloads: Vec<Vec<u8>> = vec![vec![1, 2, 3], vec![4, 5], vec![u8::MAX; 70000]];
+
+        assert_eq!(split(FramingWidth::U32, &payloads).await, payloads);
+    }
+
+    /// The sending and reading clients of a connection, along with
+    /// the receive queue and receive loop of the reading client.
+    type FramedConnection = (
+        Client,
+        Client,
+        mpsc::Receiver<RecvData>,
+        JoinHandle<io::Result<()>>,
+    );
+
+    /// Connects a client framing with `sent` to a client framing with `read`.
+    async fn connect_framed(sent: FramingWidth, read: FramingWidth) -> FramedConnection {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let stream = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        let (remote, _) = listener.accept().await.unwrap();
+
+        let mut sender: Client = Client::from(stream);
+        let mut reader: Client = Client::from(remote);
+
+        sender.set_framing(sent);
+        reader.set_framing(read);
+
+        let (sender_tx, _sender_rx) = mpsc::channel::<RecvData>(1);
+        let (reader_tx, reader_rx) = mpsc::channel::<RecvData>(1);
+
+        let _sender_handles = sender.spawn_recv_send_tasks(sender_tx).await;
+        let (recv_handle, _send_handle) = reader.spawn_recv_send_tasks(reader_tx).await;
+
+        (sender, reader, reader_rx, recv_handle)
+    }
+
+    #[tokio::test]
+    async fn datagram_larger_than_read_buffer() {
+        let (mut sender, _reader, mut reader_rx, _recv_handle) =
+            connect_framed(FramingWidth::U32, FramingWidth::U32).await;
+
+        // received over several reads of the stream
+        let payload: Vec<u8> = (0..TCP_READ_BUFFER_SIZE * 2).map(|i| i as u8).collect();
+
+        let mut dg: Datagram = Datagram::default();
+        dg.override_cap(payload.len());
+        dg.add_data(payload.clone()).unwrap();
+
+        sender.stage_datagram(dg).await.unwrap();
+
+        let received: RecvData = tokio::time::timeout(Duration::from_secs(5), reader_rx.recv())
+            .await
+            .expect("Datagram was not received.")
+            .unwrap();
+
+        assert_eq!(received.dg.get_data(), payload);
+    }
+
+    #[tokio::test]
+    async fn datagram_split_mid_size_tag() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut stream = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        let (remote, _) = listener.accept().await.unwrap();
+
+        let mut reader: Client = Client::from(remote);
+        reader.set_framing(FramingWidth::U32);
+
+        let (reader_tx, mut reader_rx) = mpsc::channel::<RecvData>(1);
+        let _reader_handles = reader.spawn_recv_send_tasks(reader_tx).await;
+
+        let mut framed: Datagram = Datagram::default();
+        framed.add_frame_size(FramingWidth::U32, 3).unwrap();
+        framed.add_data(vec![1, 2, 3]).unwrap();
+
+        let framed: Vec<u8> = framed.get_data();
+
+        // the first write ends in the middle of the size tag
+        stream.write_all(&framed[..2]).await.unwrap();
+        stream.flush().await.unwrap();
+
+        // nothing is received until the datagram is complete
+        let early = tokio::time::timeout(Duration::from_millis(200), reader_rx.recv()).await;
+        assert!(early.is_err(), "Incomplete datagram was received.");
+
+        stream.write_all(&framed[2..]).await.unwrap();
+        stream.flush().await.unwrap();
+
+        let received: RecvData = tokio::time::timeout(Duration::from_secs(5), reader_rx.recv())
+            .await
+            .expect("Datagram was not received.")
+            .unwrap();
+
+        assert_eq!(received.dg.get_data(), vec![1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn mismatched_framing_16_bit_sender() {
+        let (mut sender, _reader, _reader_rx, recv_handle) =
+            connect_framed(FramingWidth::U16, FramingWidth::U32).await;
+
+        let mut dg: Datagram = Datagram::default();
+        dg.add_data(vec![1, 2, 3]).unwrap();
+
+        sender.stage_datagram(dg).await.unwrap();
+
+        // the 16-bit size tag and payload are read as a huge 32-bit size tag
+        let err: io::Error = tokio::time::timeout(Duration::from_secs(5), recv_handle)
+            .await
+            .expect("Peer with mismatched framing was not disconnected.")
+            .unwrap()
+            .unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[tokio::test]
+    async fn mismatched_framing_32_bit_sender() {
+        let (mut sender, _reader, mut reader_rx, _recv_handle) =
+            connect_framed(FramingWidth::U32, FramingWidth::U16).await;
+
+        let mut dg: Datagram = Datagram::default();
+        dg.add_data(vec![1, 2, 3]).unwrap();
+
+        sender.stage_datagram(dg).await.unwrap();
+
+        // Every 16-bit size tag is in the limit, so the mismatch cannot be
+        // told apart from a real datagram, which is read misframed.
+        let received: RecvData = tokio::time::timeout(Duration::from_secs(5), reader_rx.recv())
+            .await
+            .expect("Datagram was not received.")
+            .unwrap();
+
+        assert_eq!(received.dg.get_data(), vec![0, 0, 1]);
     }
 }
@@ -0,0 +1,444 @@
+/*
+    This file is part of Donet.
+
+    Copyright © 2024 Max Rodriguez <me@maxrdz.com>
+
+    Donet is free software; you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License,
+    as published by the Free Software Foundation, either version 3
+    of the License, or (at your option) any later version.
+
+    Donet is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public
+    License along with Donet. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Abstraction over the stream transports that a [`crate::Client`]
+//! can be driven by, so that services are not tied to TCP.
+
+use crate::tcp;
+#[cfg(unix)]
+use crate::unix;
+use std::future::Future;
+use std::io::Result;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+
+/// Boxed read half of a [`Stream`].
+pub type ReadHalf = Box<dyn AsyncRead + Send + Unpin>;
+
+/// Boxed write half of a [`Stream`].
+pub type WriteHalf = Box<dyn AsyncWrite + Send + Unpin>;
+
+/// An ordered, reliable byte stream to a remote peer.
+pub trait Stream: AsyncRead + AsyncWrite + Send + Unpin + Sized + 'static {
+    fn peer_addr(&self) -> Result<SocketAddr>;
+
+    fn local_addr(&self) -> Result<SocketAddr>;
+
+    /// Sets how long the connection may be idle before the transport
+    /// starts probing the remote, so that dead peers are detected even
+    /// when no data is being sent. Transports without keepalive
+    /// probes ignore this.
+    fn set_keepalive(&self, _idle: Duration) -> Result<()> {
+        Ok(())
+    }
+
+    /// Splits this stream into halves that can be
+    /// moved into separate receive and send tasks.
+    fn into_halves(self) -> (ReadHalf, WriteHalf) {
+        let (read_half, write_half) = tokio::io::split(self);
+
+        (Box::new(read_half), Box::new(write_half))
+    }
+}
+
+/// Accepts incoming [`Stream`]s from remote peers.
+pub trait Listener: Send + Sync {
+    type Stream: Stream;
+
+    fn accept(&self) -> impl Future<Output = Result<(Self::Stream, SocketAddr)>> + Send;
+
+    fn local_addr(&self) -> Result<SocketAddr>;
+}
+
+/// A network transport that services listen on and connect over.
+pub trait Transport {
+    type Stream: Stream;
+    type Listener: Listener<Stream = Self::Stream>;
+
+    fn listen(uri: &str) -> impl Future<Output = Result<Self::Listener>> + Send;
+
+    fn connect(uri: &str) -> impl Future<Output = Result<Self::Stream>> + Send;
+}
+
+/// The TCP transport, used by default by all services.
+pub struct TcpTransport;
+
+impl Transport for TcpTransport {
+    type Stream = TcpStream;
+    type Listener = tcp::Acceptor;
+
+    async fn listen(uri: &str) -> Result<Self::Listener> {
+        tcp::Acceptor::bind(uri).await
+    }
+
+    async fn connect(uri: &str) -> Result<Self::Stream> {
+        Ok(tcp::Connection::connect(uri).await?.socket)
+    }
+}
+
+impl Stream for TcpStream {
+    fn peer_addr(&self) -> Result<SocketAddr> {
+        TcpStream::peer_addr(self)
+    }
+
+    fn local_addr(&self) -> Result<SocketAddr> {
+        TcpStream::local_addr(self)
+    }
+
+    fn set_keepalive(&self, idle: Duration) -> Result<()> {
+        let keepalive = socket2::TcpKeepalive::new().with_time(idle);
+
+        socket2::SockRef::from(self).set_tcp_keepalive(&keepalive)
+    }
+
+    fn into_halves(self) -> (ReadHalf, WriteHalf) {
+        let (read_half, write_half) = self.into_split();
+
+        (Box::new(read_half), Box::new(write_half))
+    }
+}
+
+impl Listener for tcp::Acceptor {
+    type Stream = TcpStream;
+
+    async fn accept(&self) -> Result<(Self::Stream, SocketAddr)> {
+        self.socket.accept().await
+    }
+
+    fn local_addr(&self) -> Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+}
+
+/// Transport over TCP, or over a Unix domain socket for URIs
+/// prefixed with `unix:`, such as `unix:/run/donet/md.sock`.
+pub struct SocketTransport;
+
+/// Stream of a [`SocketTransport`].
+pub enum SocketStream {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(unix::Connection),
+}
+
+/// Listener of a [`SocketTransport`].
+pub enum SocketListener {
+    Tcp(tcp::Acceptor),
+    #[cfg(unix)]
+    Unix(unix::Acceptor),
+}
+
+/// Returns the socket path of a `unix:` URI, or an error if this
+/// platform has no Unix domain sockets.
+fn unix_path(uri: &str) -> Result<Option<&str>> {
+    match uri.strip_prefix("unix:") {
+        Some(_) if cfg!(not(unix)) => Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "Unix domain sockets are not supported on this platform.",
+        )),
+        path => Ok(path),
+    }
+}
+
+impl Transport for SocketTransport {
+    type Stream = SocketStream;
+    type Listener = SocketListener;
+
+    async fn listen(uri: &str) -> Result<Self::Listener> {
+        match unix_path(uri)? {
+            #[cfg(unix)]
+            Some(path) => Ok(SocketListener::Unix(unix::Acceptor::bind(path).await?)),
+            _ => Ok(SocketListener::Tcp(TcpTransport::listen(uri).await?)),
+        }
+    }
+
+    async fn connect(uri: &str) -> Result<Self::Stream> {
+        match unix_path(uri)? {
+            #[cfg(unix)]
+            Some(path) => Ok(SocketStream::Unix(unix::Connection::connect(path).await?)),
+            _ => Ok(SocketStream::Tcp(TcpTransport::connect(uri).await?)),
+        }
+    }
+}
+
+impl From<TcpStream> for SocketStream {
+    fn from(value: TcpStream) -> Self {
+        Self::Tcp(value)
+    }
+}
+
+impl AsyncRead for SocketStream {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(stream) => Pin::new(stream).poll_read(cx, buf),
+            #[cfg(unix)]
+            Self::Unix(conn) => Pin::new(&mut conn.socket).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for SocketStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<Result<usize>> {
+        match self.get_mut() {
+            Self::Tcp(stream) => Pin::new(stream).poll_write(cx, buf),
+            #[cfg(unix)]
+            Self::Unix(conn) => Pin::new(&mut conn.socket).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(stream) => Pin::new(stream).poll_flush(cx),
+            #[cfg(unix)]
+            Self::Unix(conn) => Pin::new(&mut conn.socket).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(stream) => Pin::new(stream).poll_shutdown(cx),
+            #[cfg(unix)]
+            Self::Unix(conn) => Pin::new(&mut conn.socket).poll_shutdown(cx),
+        }
+    }
+}
+
+impl Stream for SocketStream {
+    fn peer_addr(&self) -> Result<SocketAddr> {
+        match self {
+            Self::Tcp(stream) => stream.peer_addr(),
+            #[cfg(unix)]
+            Self::Unix(conn) => Ok(conn.peer),
+        }
+    }
+
+    fn local_addr(&self) -> Result<SocketAddr> {
+        match self {
+            Self::Tcp(stream) => stream.local_addr(),
+            #[cfg(unix)]
+            Self::Unix(conn) => Ok(conn.local),
+        }
+    }
+
+    /// Unix domain sockets have no keepalive probes; a connection
+    /// is closed by the kernel once its peer process exits.
+    fn set_keepalive(&self, idle: Duration) -> Result<()> {
+        match self {
+            Self::Tcp(stream) => stream.set_keepalive(idle),
+            #[cfg(unix)]
+            Self::Unix(_) => Ok(()),
+        }
+    }
+
+    fn into_halves(self) -> (ReadHalf, WriteHalf) {
+        match self {
+            Self::Tcp(stream) => stream.into_halves(),
+            #[cfg(unix)]
+            Self::Unix(conn) => {
+                let (read_half, write_half) = conn.socket.into_split();
+
+                (Box::new(read_half), Box::new(write_half))
+            }
+        }
+    }
+}
+
+impl Listener for SocketListener {
+    type Stream = SocketStream;
+
+    async fn accept(&self) -> Result<(Self::Stream, SocketAddr)> {
+        match self {
+            Self::Tcp(acceptor) => {
+                let (stream, remote) = acceptor.accept().await?;
+                Ok((SocketStream::Tcp(stream), remote))
+            }
+            #[cfg(unix)]
+            Self::Unix(acceptor) => {
+                let (conn, remote) = acceptor.accept().await?;
+                Ok((SocketStream::Unix(conn), remote))
+            }
+        }
+    }
+
+    fn local_addr(&self) -> Result<SocketAddr> {
+        match self {
+            Self::Tcp(acceptor) => Listener::local_addr(acceptor),
+            #[cfg(unix)]
+            Self::Unix(acceptor) => Ok(acceptor.address),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Client, RecvData};
+    use donet_core::datagram::datagram::Datagram;
+    use std::io::{Error, ErrorKind};
+    use tokio::io::{DuplexStream, ReadBuf};
+    use tokio::sync::{mpsc, Mutex};
+
+    /// In-memory stream, with fake addresses for both ends.
+    struct MemoryStream {
+        inner: DuplexStream,
+        local: SocketAddr,
+        peer: SocketAddr,
+    }
+
+    impl AsyncRead for MemoryStream {
+        fn poll_read(
+            mut self: std::pin::Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> std::task::Poll<Result<()>> {
+            std::pin::Pin::new(&mut self.inner).poll_read(cx, buf)
+        }
+    }
+
+    impl AsyncWrite for MemoryStream {
+        fn poll_write(
+            mut self: std::pin::Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+            buf: &[u8],
+        ) -> std::task::Poll<Result<usize>> {
+            std::pin::Pin::new(&mut self.inner).poll_write(cx, buf)
+        }
+
+        fn poll_flush(
+            mut self: std::pin::Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<Result<()>> {
+            std::pin::Pin::new(&mut self.inner).poll_flush(cx)
+        }
+
+        fn poll_shutdown(
+            mut self: std::pin::Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<Result<()>> {
+            std::pin::Pin::new(&mut self.inner).poll_shutdown(cx)
+        }
+    }
+
+    impl Stream for MemoryStream {
+        fn peer_addr(&self) -> Result<SocketAddr> {
+            Ok(self.peer)
+        }
+
+        fn local_addr(&self) -> Result<SocketAddr> {
+            Ok(self.local)
+        }
+    }
+
+    /// Listener whose incoming streams are handed over a channel.
+    struct MemoryListener {
+        address: SocketAddr,
+        incoming: Mutex<mpsc::Receiver<MemoryStream>>,
+    }
+
+    impl Listener for MemoryListener {
+        type Stream = MemoryStream;
+
+        async fn accept(&self) -> Result<(Self::Stream, SocketAddr)> {
+            match self.incoming.lock().await.recv().await {
+                Some(stream) => {
+                    let remote: SocketAddr = stream.peer;
+                    Ok((stream, remote))
+                }
+                None => Err(Error::new(ErrorKind::NotConnected, "Listener closed.")),
+            }
+        }
+
+        fn local_addr(&self) -> Result<SocketAddr> {
+            Ok(self.address)
+        }
+    }
+
+    /// Creates a listener, and a function for connecting to it.
+    fn memory_listener(address: SocketAddr) -> (MemoryListener, impl Fn(SocketAddr) -> MemoryStream) {
+        let (tx, rx) = mpsc::channel::<MemoryStream>(8);
+
+        let connect = move |local: SocketAddr| {
+            let (ours, theirs) = tokio::io::duplex(4096);
+
+            tx.try_send(MemoryStream {
+                inner: theirs,
+                local: address,
+                peer: local,
+            })
+            .unwrap();
+
+            MemoryStream {
+                inner: ours,
+                local,
+                peer: address,
+            }
+        };
+
+        let listener = MemoryListener {
+            address,
+            incoming: Mutex::new(rx),
+        };
+        (listener, connect)
+    }
+
+    #[tokio::test]
+    async fn client_over_memory_transport() {
+        let server_addr: SocketAddr = "10.0.0.1:7199".parse().unwrap();
+        let client_addr: SocketAddr = "10.0.0.2:50000".parse().unwrap();
+
+        let (listener, connect) = memory_listener(server_addr);
+
+        let mut client: Client = Client::new(connect(client_addr)).unwrap();
+        let (stream, remote) = listener.accept().await.unwrap();
+
+        assert_eq!(remote, client_addr);
+        assert_eq!(client.get_remote(), server_addr);
+
+        let mut server: Client = Client::new(stream).unwrap();
+        assert_eq!(server.get_remote(), client_addr);
+        assert_eq!(server.get_local(), server_addr);
+
+        let (client_tx, mut client_rx) = mpsc::channel::<RecvData>(8);
+        let (server_tx, mut server_rx) = mpsc::channel::<RecvData>(8);
+
+        let _client_handles = client.spawn_recv_send_tasks(client_tx).await;
+        let _server_handles = server.spawn_recv_send_tasks(server_tx).await;
+
+        // client says hello, server answers
+        let mut hello: Datagram = Datagram::default();
+        hello.add_string("hello").unwrap();
+        client.stage_datagram(hello.clone()).await.unwrap();
+
+        let received: RecvData = server_rx.recv().await.unwrap();
+        assert_eq!(received.remote, client_addr);
+        assert_eq!(received.dg.get_data(), hello.get_data());
+
+        let mut welcome: Datagram = Datagram::default();
+        welcome.add_string("welcome").unwrap();
+        server.stage_datagram(welcome.clone()).await.unwrap();
+
+        let received: RecvData = client_rx.recv().await.unwrap();
+        assert_eq!(received.remote, server_addr);
+        assert_eq!(received.dg.get_data(), welcome.get_data());
+    }
+}
@@ -0,0 +1,103 @@
+/*
+    This file is part of Donet.
+
+    Copyright © 2024 Max Rodriguez <me@maxrdz.com>
+
+    Donet is free software; you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License,
+    as published by the Free Software Foundation, either version 3
+    of the License, or (at your option) any later version.
+
+    Donet is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public
+    License along with Donet. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Unix domain socket streams, for services that are co-located
+//! on one host, bound with a `unix:<path>` URI.
+//!
+//! Peers of a Unix domain socket have no network address, so each
+//! end of a connection is given a unique placeholder address in the
+//! `100::/64` discard-only IPv6 prefix, which identifies it in logs
+//! and wherever services key connections by their remote address.
+
+use log::info;
+use std::io::Result;
+use std::net::{Ipv6Addr, SocketAddr};
+use std::os::unix::fs::FileTypeExt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::net::{UnixListener, UnixStream};
+
+/// Returns a new placeholder address, unique to this process.
+fn placeholder_addr() -> SocketAddr {
+    static NEXT: AtomicU64 = AtomicU64::new(1);
+
+    let n: u64 = NEXT.fetch_add(1, Ordering::Relaxed);
+    let addr: Ipv6Addr = Ipv6Addr::from((0x0100_u128 << 112) | u128::from(n));
+
+    SocketAddr::from((addr, 0))
+}
+
+pub struct Acceptor {
+    pub socket: UnixListener,
+    pub path: String,
+    pub address: SocketAddr,
+}
+
+/// One end of a Unix domain socket connection.
+pub struct Connection {
+    pub socket: UnixStream,
+    pub local: SocketAddr,
+    pub peer: SocketAddr,
+}
+
+impl Acceptor {
+    /// Binds to the socket path, removing a socket file left
+    /// behind at the path by a previous run first.
+    pub async fn bind(path: &str) -> Result<Self> {
+        if let Ok(metadata) = std::fs::metadata(path) {
+            if metadata.file_type().is_socket() {
+                std::fs::remove_file(path)?;
+            }
+        }
+        let socket: UnixListener = UnixListener::bind(path)?;
+
+        info!("Opened new Unix domain socket at {}.", path);
+
+        Ok(Self {
+            socket,
+            path: String::from(path),
+            address: placeholder_addr(),
+        })
+    }
+
+    pub async fn accept(&self) -> Result<(Connection, SocketAddr)> {
+        let (socket, _) = self.socket.accept().await?;
+        let peer: SocketAddr = placeholder_addr();
+
+        let connection = Connection {
+            socket,
+            local: self.address,
+            peer,
+        };
+        Ok((connection, peer))
+    }
+}
+
+impl Connection {
+    pub async fn connect(path: &str) -> Result<Self> {
+        let socket: UnixStream = UnixStream::connect(path).await?;
+
+        info!("Opened new Unix domain socket connection to {}.", path);
+
+        Ok(Self {
+            socket,
+            local: placeholder_addr(),
+            peer: placeholder_addr(),
+        })
+    }
+}
@@ -36,8 +36,8 @@
 extern crate cfg_if;
 use donet_daemon::meson::*;
 
-#[cfg(feature = "requires_dc")]
-use donet_core::{dconfig::DCFileConfig, read_dc_files};
+mod service_kind;
+
 use donet_daemon::config::*;
 use donet_daemon::logger;
 use donet_daemon::logger::DaemonLogger;
@@ -45,7 +45,9 @@ use donet_daemon::service::*;
 use log::*;
 use std::fs::File;
 use std::io::{Error, ErrorKind, Read};
+use std::sync::Arc;
 use tokio::runtime::{Builder, Runtime};
+use tokio::sync::Notify;
 use tokio::task::JoinHandle;
 
 #[derive(Clone, Copy)]
@@ -72,6 +74,8 @@ fn main() -> std::io::Result<()> {
 
     let mut config_file: &str = DEFAULT_TOML;
     let mut want_dc_check: bool = false;
+    let mut want_dc_list: bool = false;
+    let mut want_json: bool = false;
     let mut dc_check_files: Vec<String> = vec![];
     let mut expecting_flag_argument: Option<FlagArguments> = None;
 
@@ -92,6 +96,13 @@ fn main() -> std::io::Result<()> {
                     want_dc_check = true;
                     expecting_flag_argument = Some(FlagArguments::DCFilePath);
                     continue;
+                } else if argument == "-l" || argument == "--list-dc" {
+                    want_dc_list = true;
+                    expecting_flag_argument = Some(FlagArguments::DCFilePath);
+                    continue;
+                } else if argument == "--json" {
+                    want_json = true;
+                    continue;
                 } else {
                     println!("{}: {}: Invalid flag.\n", BINARY, argument);
                     print_help_page();
@@ -156,7 +167,10 @@ fn main() -> std::io::Result<()> {
     drop(contents);
 
     // Now that configuration file is parsed, we can create the logger.
-    if let Some(log_level) = &daemon_config.daemon.log_level {
+    // A DC file listing is printed to stdout, so only log problems.
+    if want_dc_list {
+        init_logger!(log::Level::Warn);
+    } else if let Some(log_level) = &daemon_config.daemon.log_level {
         match log_level.as_str() {
             "error" => {
                 init_logger!(log::Level::Error);
@@ -191,6 +205,28 @@ fn main() -> std::io::Result<()> {
         }
     }
 
+    // If `--list-dc` argument was received, list the DC file elements and exit.
+    if want_dc_list {
+        cfg_if! {
+            if #[cfg(feature = "requires_dc")] {
+                return list_dc_files(&daemon_config, dc_check_files, want_json);
+            } else {
+                error!("This build of Donet does not include DC file support.");
+                return Err(Error::new(ErrorKind::Unsupported, "No DC file support."));
+            }
+        }
+    }
+
+    // Fail fast if a configured service is missing a service it depends on.
+    if let Err(err) = service_kind::check_dependencies(&daemon_config.services) {
+        error!("{}", err);
+        return Err(err);
+    }
+    if let Err(err) = daemon_config.global.check_uberdogs() {
+        error!("{}", err);
+        return Err(err);
+    }
+
     // At this point in execution, the program has not exited, which
     // means all arguments have been read and executed, if executed,
     // and now we can start the process of booting the Donet daemon.
@@ -198,16 +234,14 @@ fn main() -> std::io::Result<()> {
 
     // First step is to read the DC files listed in the daemon configuration.
     // Services like the Event Logger and Message Director do not need the DC file.
+    // The DC file is only parsed once, and shared by all services that need it.
     cfg_if! {
         if #[cfg(feature = "requires_dc")] {
-            let conf: DCFileConfig = daemon_config.clone().into();
-            let files: Vec<String> = daemon_config.global.dc_files.clone();
-
-            let dc: DCFile = match read_dc_files(conf, files) {
+            let dc: SharedDCFile = match load_dc_files(&daemon_config) {
                 Ok(dc) => dc,
                 Err(dc_err) => {
-                    error!("Failed to parse DC file(s): {}", dc_err);
-                    return Err(Error::new(ErrorKind::InvalidInput, "Failed to parse DC file."));
+                    error!("{}", dc_err);
+                    return Err(dc_err);
                 }
             };
         }
@@ -215,49 +249,75 @@ fn main() -> std::io::Result<()> {
 
     // Everything is prepped for the daemon, so we
     // are safe to start the Tokio asynchronous runtime.
+    // Log panics as errors, naming the service that panicked, if any.
+    install_panic_hook(|message| error!("{}", message));
+
+    let thread_name: String = daemon_config
+        .daemon
+        .thread_name
+        .clone()
+        .unwrap_or("donet-worker".to_owned());
+
     let tokio_runtime: Runtime = Builder::new_multi_thread()
         .enable_io()
+        .enable_time()
         .thread_stack_size(2 * 1024 * 1024) // default: 2MB
+        .thread_name(thread_name)
         .build()?;
 
     let daemon_async_main = async move {
         let services: Services = daemon_config.services.clone();
 
-        // Tokio join handles for spawned tasks of services started.
+        // Tokio join handles for the spawned tasks of services started.
         let mut service_handles: Vec<JoinHandle<std::io::Result<()>>> = vec![];
+        // Notified by a service's supervisor if it panicked and was not restarted.
+        let panicked: Arc<Notify> = Arc::new(Notify::new());
 
         let want_client_agent: bool = services.client_agent.is_some();
-        let want_message_director: bool = services.message_director.is_some();
         let want_state_server: bool = services.state_server.is_some();
         let want_database_server: bool = services.database_server.is_some();
         let want_dbss: bool = services.dbss.is_some();
-        let want_event_logger: bool = services.event_logger.is_some();
 
-        cfg_if! {
-            if #[cfg(feature = "client-agent")] {
-                if want_client_agent {
-                    info!("Booting Client Agent service.");
-                    todo!("CA not yet implemented.")
-                }
-            } else {
-                if want_client_agent {
-                    feature_warn("Client Agent");
+        #[cfg(not(feature = "message-director"))]
+        if services.message_director.is_some() {
+            feature_warn("Message Director");
+        }
+        #[cfg(not(feature = "event-logger"))]
+        if services.event_logger.is_some() {
+            feature_warn("Event Logger");
+        }
+
+        // Services are booted in dependency order. The Message Director
+        // comes first, as all other services connect to it, and the
+        // Database Server is booted before the DBSS that uses it.
+        // See `service_kind::boot_order` for the order.
+        for kind in service_kind::boot_order(&services) {
+            info!("Booting {} service.", kind.name());
+
+            // A service that fails to start, such as an MD that gave up
+            // on reaching its upstream, exits the daemon with an error.
+            let name: &str = kind.name();
+
+            match kind
+                .start_supervised(daemon_config.clone(), panicked.clone())
+                .await
+            {
+                Ok(handle) => service_handles.push(handle),
+                Err(err) => {
+                    error!("Failed to start the {} service: {}", name, err);
+                    return Err(err);
                 }
             }
         }
         cfg_if! {
-            if #[cfg(feature = "message-director")] {
-                use donet_message_director::MessageDirector;
-
-                if want_message_director {
-                    info!("Booting Message Director service.");
-
-                    let handle = MessageDirector::start(daemon_config.clone(), None).await?;
-                    service_handles.push(handle);
+            if #[cfg(feature = "database-server")] {
+                if want_database_server {
+                    info!("Booting Database Server service.");
+                    todo!("DB not yet implemented.")
                 }
             } else {
-                if want_message_director {
-                    feature_warn("Message Director");
+                if want_database_server {
+                    feature_warn("Database Server");
                 }
             }
         }
@@ -273,18 +333,6 @@ fn main() -> std::io::Result<()> {
                 }
             }
         }
-        cfg_if! {
-            if #[cfg(feature = "database-server")] {
-                if want_database_server {
-                    info!("Booting Database Server service.");
-                    todo!("DB not yet implemented.")
-                }
-            } else {
-                if want_database_server {
-                    feature_warn("Database Server");
-                }
-            }
-        }
         cfg_if! {
             if #[cfg(feature = "dbss")] {
                 if want_dbss {
@@ -298,18 +346,14 @@ fn main() -> std::io::Result<()> {
             }
         }
         cfg_if! {
-            if #[cfg(feature = "event-logger")] {
-                use donet_event_logger::EventLogger;
-
-                if want_event_logger {
-                    info!("Booting Event Logger service.");
-
-                    let handle = EventLogger::start(daemon_config.clone(), None).await?;
-                    service_handles.push(handle);
+            if #[cfg(feature = "client-agent")] {
+                if want_client_agent {
+                    info!("Booting Client Agent service.");
+                    todo!("CA not yet implemented.")
                 }
             } else {
-                if want_event_logger {
-                    feature_warn("Event Logger");
+                if want_client_agent {
+                    feature_warn("Client Agent");
                 }
             }
         }
@@ -321,15 +365,26 @@ fn main() -> std::io::Result<()> {
         if service_handles.is_empty() {
             warn!("No services spawned, exiting program.")
         } else {
-            match tokio::signal::ctrl_c().await {
-                Ok(()) => {
-                    println!();
-                    info!("Received interrupt (Ctrl + C)");
-                }
-                Err(err) => {
-                    error!("Unable to listen for shutdown signal: {}", err);
-                    panic!("Tokio was not able to listen to the interrupt signal.")
+            // Services may also exit on their own, such as a drained MD.
+            let services_exited = async {
+                while !service_handles.iter().all(|handle| handle.is_finished()) {
+                    tokio::time::sleep(std::time::Duration::from_millis(250)).await;
                 }
+            };
+
+            tokio::select! {
+                signal = tokio::signal::ctrl_c() => match signal {
+                    Ok(()) => {
+                        println!();
+                        info!("Received interrupt (Ctrl + C)");
+                    }
+                    Err(err) => {
+                        error!("Unable to listen for shutdown signal: {}", err);
+                        panic!("Tokio was not able to listen to the interrupt signal.")
+                    }
+                },
+                () = services_exited => info!("All services have exited."),
+                () = panicked.notified() => error!("A service panicked; shutting down."),
             }
         }
         info!("Exiting...");
@@ -338,9 +393,14 @@ fn main() -> std::io::Result<()> {
         for handle in &service_handles {
             handle.abort();
         }
-        // Await task handles to wrap things up; Expect a cancellation error.
+        // Await task handles to wrap things up; Expect a cancellation
+        // error, unless the service had already exited by itself.
         for handle in service_handles {
-            assert!(handle.await.unwrap_err().is_cancelled());
+            match handle.await {
+                Ok(Ok(())) => {}
+                Ok(Err(err)) => error!("Service exited with an error: {}", err),
+                Err(err) => assert!(err.is_cancelled()),
+            }
         }
         Ok(())
     };
@@ -386,7 +446,9 @@ fn print_help_page() {
         \n\
         -h, --help          Print the help page.\n\
         -v, --version       Print Donet binary build version & info.\n\
-        -c, --validate-dc   Run the libdonet DC parser on the given DC file.\n",
+        -c, --validate-dc   Run the libdonet DC parser on the given DC file.\n\
+        -l, --list-dc       List the classes, fields, and IDs of the given DC file.\n\
+        --json              Print the DC file listing as JSON.\n",
         BINARY, DEFAULT_TOML
     );
 }
@@ -439,7 +501,7 @@ fn print_version() {
 fn validate_dc_files(conf: &DonetConfig, files: Vec<String>) -> std::io::Result<()> {
     use donet_core::dconfig::DCFileConfig;
     use donet_core::read_dc_files;
-    use log::{error, info};
+    use log::{error, info, warn};
     use std::io::{Error, ErrorKind};
 
     // DC parser pipeline requires configuration; Build from TOML config.
@@ -451,10 +513,47 @@ fn validate_dc_files(conf: &DonetConfig, files: Vec<String>) -> std::io::Result<
             let signed: i32 = hash as i32;
             let pretty: String = dc_file.get_pretty_hash();
 
-            info!(
-                "No issues found. Legacy file hash is {} (signed {}, hex {})",
-                hash, signed, pretty
-            );
+            // Warnings have already been rendered by the DC parser pipeline.
+            let warnings: usize = dc_file.validate().len();
+
+            if warnings == 0 {
+                info!("No issues found.");
+            } else {
+                warn!("Found {} warning(s) in the DC file(s).", warnings);
+            }
+            info!("Legacy file hash is {} (signed {}, hex {})", hash, signed, pretty);
+            Ok(())
+        }
+        Err(err) => {
+            error!("Failed to parse DC file: {:?}", err);
+
+            Err(Error::new(ErrorKind::InvalidInput, "Failed to parse DC file."))
+        }
+    }
+}
+
+/// Performs the operation for the `-l` flag, or the `--list-dc`
+/// GNU-style long flag in the daemon binary.
+#[cfg(feature = "requires_dc")]
+fn list_dc_files(conf: &DonetConfig, files: Vec<String>, json: bool) -> std::io::Result<()> {
+    use donet_core::dclisting::DCListing;
+    use donet_core::dconfig::DCFileConfig;
+    use donet_core::read_dc_files;
+    use log::error;
+    use std::io::{Error, ErrorKind};
+
+    let dc_config: DCFileConfig = conf.clone().into();
+
+    match read_dc_files(dc_config, files) {
+        Ok(dc_file) => {
+            let listing: DCListing = dc_file.listing();
+
+            // Printed to stdout, not logged, so that it can be parsed.
+            if json {
+                println!("{}", listing.to_json());
+            } else {
+                print!("{}", listing);
+            }
             Ok(())
         }
         Err(err) => {
@@ -0,0 +1,287 @@
+/*
+    This file is part of Donet.
+
+    Copyright © 2024 Max Rodriguez <me@maxrdz.com>
+
+    Donet is free software; you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License,
+    as published by the Free Software Foundation, either version 3
+    of the License, or (at your option) any later version.
+
+    Donet is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public
+    License along with Donet. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Static dispatch over the Donet services built into this binary.
+
+use donet_daemon::config::{DonetConfig, Services};
+use donet_daemon::service::*;
+use std::io::{Error, ErrorKind, Result};
+use std::sync::Arc;
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+
+cfg_if! {
+    if #[cfg(feature = "message-director")] {
+        use donet_message_director::MessageDirector;
+    }
+}
+cfg_if! {
+    if #[cfg(feature = "event-logger")] {
+        use donet_event_logger::EventLogger;
+    }
+}
+
+/// Every concrete service that this build of Donet can boot.
+///
+/// The daemon picks the services to start from its configuration,
+/// and each one is started by matching on its variant, rather than
+/// going through a boxed trait object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServiceKind {
+    #[cfg(feature = "message-director")]
+    MessageDirector,
+    #[cfg(feature = "event-logger")]
+    EventLogger,
+}
+
+impl ServiceKind {
+    /// Human readable name of the service, used for logging.
+    pub fn name(self) -> &'static str {
+        match self {
+            #[cfg(feature = "message-director")]
+            Self::MessageDirector => MessageDirector::NAME,
+            #[cfg(feature = "event-logger")]
+            Self::EventLogger => EventLogger::NAME,
+        }
+    }
+
+    /// Whether the daemon cannot keep running without this service,
+    /// so it is never restarted if it panics.
+    pub fn is_essential(self) -> bool {
+        match self {
+            #[cfg(feature = "message-director")]
+            Self::MessageDirector => true,
+            #[cfg(feature = "event-logger")]
+            Self::EventLogger => false,
+        }
+    }
+
+    /// Starts the service, returning the join handle of its main task.
+    ///
+    /// None of these services need the DC file, so they are given `None`.
+    pub async fn start(self, conf: DonetConfig) -> Result<JoinHandle<Result<()>>> {
+        match self {
+            #[cfg(feature = "message-director")]
+            Self::MessageDirector => MessageDirector::start(conf, None).await,
+            #[cfg(feature = "event-logger")]
+            Self::EventLogger => EventLogger::start(conf, None).await,
+        }
+    }
+
+    /// Starts the service, returning the join handle of a task that
+    /// supervises it. See [`supervise`] for how panics are handled.
+    pub async fn start_supervised(
+        self,
+        conf: DonetConfig,
+        panicked: Arc<Notify>,
+    ) -> Result<JoinHandle<Result<()>>> {
+        let restart: bool = conf.daemon.restart_on_panic.unwrap_or(false) && !self.is_essential();
+        let handle: JoinHandle<Result<()>> = self.start(conf.clone()).await?;
+
+        Ok(supervise(self.name(), handle, restart, panicked, move || {
+            self.start(conf.clone())
+        }))
+    }
+}
+
+/// Returns the configured services of this build, in the order they
+/// are booted. The Message Director comes first, as every other service
+/// connects to it. Services that this build cannot boot are left out.
+pub fn boot_order(services: &Services) -> Vec<ServiceKind> {
+    let mut kinds: Vec<ServiceKind> = vec![];
+
+    #[cfg(feature = "message-director")]
+    if services.message_director.is_some() {
+        kinds.push(ServiceKind::MessageDirector);
+    }
+    #[cfg(feature = "event-logger")]
+    if services.event_logger.is_some() {
+        kinds.push(ServiceKind::EventLogger);
+    }
+    kinds
+}
+
+/// Checks that every configured service also has the services it
+/// depends on configured, returning a descriptive error for the first
+/// missing dependency found.
+pub fn check_dependencies(services: &Services) -> Result<()> {
+    let md: bool = services.message_director.is_some();
+    let db: bool = services.database_server.is_some();
+    let dbss: bool = services.dbss.is_some();
+    let ss: bool = services.state_server.is_some();
+    let ca: bool = services.client_agent.is_some();
+
+    // (service, configured, dependency, configured)
+    let dependencies: [(&str, bool, &str, bool); 5] = [
+        ("DBSS", dbss, "Database Server", db),
+        ("DBSS", dbss, "Message Director", md),
+        ("Database Server", db, "Message Director", md),
+        ("State Server", ss, "Message Director", md),
+        ("Client Agent", ca, "Message Director", md),
+    ];
+
+    for (service, wanted, dependency, present) in dependencies {
+        if wanted && !present {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "The {} requires a {}, but no {} is configured.",
+                    service, dependency, dependency
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use donet_daemon::config;
+    use donet_daemon::config::{Daemon, Global, Services};
+
+    fn test_config() -> DonetConfig {
+        DonetConfig {
+            daemon: Daemon {
+                name: "Service Kind Test".into(),
+                id: None,
+                log_level: None,
+                thread_name: None,
+                restart_on_panic: None,
+            },
+            global: Global {
+                eventlogger: None,
+                dc_files: vec![],
+                dc_multiple_inheritance: None,
+                dc_sort_inheritance_by_file: None,
+                dc_virtual_inheritance: None,
+                dc_hash_compatibility: None,
+                uberdogs: None,
+            },
+            services: Services {
+                client_agent: None,
+                message_director: Some(config::MessageDirector {
+                    bind: "127.0.0.1:0".into(),
+                    upstream: None,
+                    framing: None,
+                    write_timeout: None,
+                    max_pending_bytes: None,
+                    idle_timeout: None,
+                    keepalive: None,
+                    drain_timeout: None,
+                    shutdown_timeout: None,
+                    record: None,
+                    watchdog_timeout: None,
+                    max_reconnect_attempts: None,
+                }),
+                state_server: None,
+                database_server: None,
+                dbss: None,
+                event_logger: Some(config::EventLogger {
+                    bind: "127.0.0.1:0".into(),
+                    output: std::env::temp_dir().to_string_lossy().into_owned(),
+                    log_format: "donet-service-kind-test-%Y-%m-%d-%H-%M-%S.log".into(),
+                    rotate_interval: "1d".into(),
+                    flush_interval: None,
+                    flush_events: None,
+                }),
+            },
+        }
+    }
+
+    #[tokio::test]
+    #[allow(clippy::vec_init_then_push)]
+    async fn start_each_service_kind() -> Result<()> {
+        let mut kinds: Vec<ServiceKind> = vec![];
+
+        #[cfg(feature = "message-director")]
+        kinds.push(ServiceKind::MessageDirector);
+        #[cfg(feature = "event-logger")]
+        kinds.push(ServiceKind::EventLogger);
+
+        for kind in kinds {
+            let handle = kind.start(test_config()).await?;
+
+            assert!(!handle.is_finished(), "{} exited on start.", kind.name());
+            handle.abort();
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn dbss_without_database_server() {
+        let mut conf: DonetConfig = test_config();
+
+        conf.services.message_director = None;
+        conf.services.event_logger = None;
+        conf.services.dbss = Some(config::DBSS {
+            db_channel: 4003,
+            range_min: 100_000_000,
+            range_max: 199_999_999,
+        });
+
+        let err: Error = check_dependencies(&conf.services).unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        assert_eq!(
+            err.to_string(),
+            "The DBSS requires a Database Server, but no Database Server is configured."
+        );
+
+        // every service in the default test config has its dependencies
+        assert!(check_dependencies(&test_config().services).is_ok());
+    }
+
+    #[test]
+    fn state_server_without_message_director() {
+        let mut conf: DonetConfig = test_config();
+
+        conf.services.message_director = None;
+        conf.services.state_server = Some(config::StateServer {
+            control_channel: 4002,
+        });
+
+        let err: Error = check_dependencies(&conf.services).unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "The State Server requires a Message Director, but no Message Director is configured."
+        );
+    }
+
+    #[test]
+    #[allow(clippy::vec_init_then_push)]
+    fn message_director_boots_first() {
+        let mut expected: Vec<ServiceKind> = vec![];
+
+        #[cfg(feature = "message-director")]
+        expected.push(ServiceKind::MessageDirector);
+        #[cfg(feature = "event-logger")]
+        expected.push(ServiceKind::EventLogger);
+
+        assert_eq!(boot_order(&test_config().services), expected);
+
+        // without a Message Director, the Event Logger is booted alone
+        let mut conf: DonetConfig = test_config();
+        conf.services.message_director = None;
+
+        let kinds: Vec<ServiceKind> = boot_order(&conf.services);
+        assert!(!kinds.iter().any(|kind| kind.name() == "Message Director"));
+    }
+}
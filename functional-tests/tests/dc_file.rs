@@ -52,3 +52,39 @@ fn dc_language_functional_testing() {
 
     assert!(donet.wait().unwrap().success(), "Test failed.");
 }
+
+#[test]
+fn dc_file_listing_matches_computed_ids() {
+    use donet_core::dcfile::DCFile;
+    use donet_core::dclisting::DCListing;
+    use donet_core::dconfig::DCFileConfig;
+
+    let build_dir: String =
+        env::var("MESON_BUILD_ROOT").expect("Functional tests need to be ran through Meson.");
+
+    let src_dir: String =
+        env::var("MESON_SOURCE_ROOT").expect("Functional tests need to be ran through Meson.");
+
+    let pwd: String = format!("{}/functional-tests/tests", src_dir);
+
+    // The DC parser configuration in the daemon TOML is the default.
+    let dc_file: DCFile =
+        donet_core::read_dc_files(DCFileConfig::default(), vec![format!("{}/{}", pwd, DC_FILE)]).unwrap();
+    let listing: DCListing = dc_file.listing();
+
+    for (flags, expected) in [
+        (vec!["-l"], listing.to_string()),
+        (vec!["--json", "-l"], listing.to_json() + "\n"),
+    ] {
+        let output = Command::new(format!("{}/{}", build_dir, DAEMON_BIN))
+            .current_dir(&pwd)
+            .args(flags)
+            .arg(DC_FILE)
+            .arg(DAEMON_TOML)
+            .output()
+            .expect("Donet daemon failed to launch.");
+
+        assert!(output.status.success(), "Test failed.");
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+    }
+}
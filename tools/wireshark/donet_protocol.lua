@@ -899,6 +899,18 @@ local message_table = {
 			return "" -- TODO: Dissect
 		end
 	},
+	[9020] = {
+		name="CONTROL_GET_SUBSCRIPTIONS",
+		dissector=function(buf, root)
+			return ""
+		end
+	},
+	[9021] = {
+		name="CONTROL_GET_SUBSCRIPTIONS_RESP",
+		dissector=function(buf, root)
+			return "" -- TODO: Dissect
+		end
+	},
 }
 
 -- Adds SRC PORT -> DST PORT prefix to the packet info, similar to